@@ -0,0 +1,357 @@
+//! Summary rows: group converted rows by one or more columns and compute count, sum,
+//! min, max, mean, distinct count and first/last values over others -- e.g. total revenue
+//! by region straight from an uploaded workbook.
+//!
+//! Each group keeps one running accumulator per metric rather than its rows, so memory
+//! grows with the number of groups, not the number of rows. Rows are folded in as they
+//! are read (see `AggregateStream`), unless a pivot or unsorted grouping must see the
+//! whole row set first.
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+use serde_json::{json, Value};
+
+use crate::error::GenericError;
+use crate::helpers::{float_value, integer_value};
+use crate::ResultSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOp {
+    /// Rows in the group, or non-null values of the metric's field when it has one.
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+    DistinctCount,
+    /// First non-null value.
+    First,
+    /// Last non-null value.
+    Last,
+}
+
+impl AggregateOp {
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.trim().to_lowercase().as_str() {
+            "count" => Some(AggregateOp::Count),
+            "sum" | "total" => Some(AggregateOp::Sum),
+            "min" => Some(AggregateOp::Min),
+            "max" => Some(AggregateOp::Max),
+            "mean" | "avg" | "average" => Some(AggregateOp::Mean),
+            "distinct_count" | "distinct" | "count_distinct" => Some(AggregateOp::DistinctCount),
+            "first" => Some(AggregateOp::First),
+            "last" => Some(AggregateOp::Last),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AggregateOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            AggregateOp::Count => "count",
+            AggregateOp::Sum => "sum",
+            AggregateOp::Min => "min",
+            AggregateOp::Max => "max",
+            AggregateOp::Mean => "mean",
+            AggregateOp::DistinctCount => "distinct_count",
+            AggregateOp::First => "first",
+            AggregateOp::Last => "last",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+/// One computed summary field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub op: AggregateOp,
+    /// Output key the metric reads. Only `Count` may leave it unset.
+    pub field: Option<String>,
+    /// Output key of the summary field.
+    pub name: String,
+}
+
+impl Metric {
+    /// A metric named `{op}_{field}`, e.g. `sum_revenue`.
+    pub fn new(op: AggregateOp, field: &str) -> Self {
+        Metric { op, field: Some(field.to_string()), name: format!("{}_{}", op, field) }
+    }
+
+    /// Row count per group, named `count`.
+    pub fn count() -> Self {
+        Metric { op: AggregateOp::Count, field: None, name: "count".to_string() }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Parses `{"op": "sum", "field": "revenue", "as": "total_revenue"}`. `"as"` is
+    /// optional, as is `"field"` for `count`. Fails for an unknown op or a missing field.
+    pub fn from_json(json: &Value) -> Result<Self, GenericError> {
+        let op = json
+            .get("op")
+            .and_then(|v| v.as_str())
+            .and_then(AggregateOp::from_key)
+            .ok_or(GenericError("invalid_aggregate_op"))?;
+        let metric = match json.get("field").and_then(|v| v.as_str()) {
+            Some(field) => Metric::new(op, field),
+            None if op == AggregateOp::Count => Metric::count(),
+            None => return Err(GenericError("aggregate_metric_without_field")),
+        };
+        Ok(match json.get("as").and_then(|v| v.as_str()) {
+            Some(name) => metric.named(name),
+            None => metric,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({"op": self.op.to_string(), "field": self.field, "as": self.name})
+    }
+}
+
+/// Running state for one metric within one group.
+#[derive(Debug, Clone, Default)]
+struct Accumulator {
+    count: usize,
+    int_sum: Option<i64>,
+    float_sum: f64,
+    all_integers: bool,
+    numeric_count: usize,
+    min: Option<Value>,
+    max: Option<Value>,
+    distinct: HashSet<String>,
+    first: Option<Value>,
+    last: Option<Value>,
+}
+
+/// Orders numbers numerically and strings lexically (which suits ISO dates). Numbers sort
+/// before strings; anything else doesn't take part.
+fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Number(_), Value::String(_)) => Some(std::cmp::Ordering::Less),
+        (Value::String(_), Value::Number(_)) => Some(std::cmp::Ordering::Greater),
+        _ => None,
+    }
+}
+
+impl Accumulator {
+    fn new() -> Self {
+        Accumulator { int_sum: Some(0), all_integers: true, ..Default::default() }
+    }
+
+    fn push(&mut self, op: AggregateOp, value: Option<&Value>) {
+        let Some(value) = value.filter(|v| !v.is_null()) else {
+            return;
+        };
+        self.count += 1;
+        match op {
+            AggregateOp::Count => {}
+            AggregateOp::Sum | AggregateOp::Mean => {
+                if let Value::Number(n) = value {
+                    self.numeric_count += 1;
+                    self.float_sum += n.as_f64().unwrap_or_default();
+                    match n.as_i64() {
+                        Some(i) => self.int_sum = self.int_sum.and_then(|sum| sum.checked_add(i)),
+                        None => self.all_integers = false,
+                    }
+                }
+            }
+            AggregateOp::Min => {
+                if self.min.as_ref().is_none_or(|m| compare_values(value, m).is_some_and(|o| o.is_lt())) && compare_values(value, value).is_some() {
+                    self.min = Some(value.clone());
+                }
+            }
+            AggregateOp::Max => {
+                if self.max.as_ref().is_none_or(|m| compare_values(value, m).is_some_and(|o| o.is_gt())) && compare_values(value, value).is_some() {
+                    self.max = Some(value.clone());
+                }
+            }
+            AggregateOp::DistinctCount => {
+                self.distinct.insert(value.to_string());
+            }
+            AggregateOp::First => {
+                if self.first.is_none() {
+                    self.first = Some(value.clone());
+                }
+            }
+            AggregateOp::Last => self.last = Some(value.clone()),
+        }
+    }
+
+    fn result(&self, op: AggregateOp, rows: usize, has_field: bool) -> Value {
+        match op {
+            AggregateOp::Count if has_field => json!(self.count),
+            AggregateOp::Count => json!(rows),
+            AggregateOp::Sum if self.numeric_count == 0 => Value::Null,
+            AggregateOp::Sum => match (self.all_integers, self.int_sum) {
+                (true, Some(sum)) => integer_value(sum),
+                _ => float_value(self.float_sum),
+            },
+            AggregateOp::Mean if self.numeric_count == 0 => Value::Null,
+            AggregateOp::Mean => float_value(self.float_sum / self.numeric_count as f64),
+            AggregateOp::Min => self.min.clone().unwrap_or(Value::Null),
+            AggregateOp::Max => self.max.clone().unwrap_or(Value::Null),
+            AggregateOp::DistinctCount => json!(self.distinct.len()),
+            AggregateOp::First => self.first.clone().unwrap_or(Value::Null),
+            AggregateOp::Last => self.last.clone().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Group-by keys plus the metrics to compute for each group.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Aggregation {
+    /// Output keys to group by. Empty gives one summary row over every row.
+    pub group_by: Vec<String>,
+    pub metrics: Vec<Metric>,
+}
+
+impl Aggregation {
+    pub fn new(group_by: &[&str]) -> Self {
+        Aggregation { group_by: group_by.iter().map(|s| s.to_string()).collect(), metrics: vec![] }
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metrics.push(metric);
+        self
+    }
+
+    /// Parses `{"group_by": ["region"], "metrics": [{"op": "sum", "field": "revenue"}]}`,
+    /// failing on the first malformed metric. A missing or empty metric list means a row
+    /// count.
+    pub fn from_json(json: &Value) -> Result<Self, GenericError> {
+        let group_by = match json.get("group_by") {
+            Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+            Some(Value::String(s)) => vec![s.clone()],
+            _ => vec![],
+        };
+        let mut metrics: Vec<Metric> = match json.get("metrics") {
+            Some(Value::Array(items)) => items.iter().map(Metric::from_json).collect::<Result<_, _>>()?,
+            _ => vec![],
+        };
+        if metrics.is_empty() {
+            metrics.push(Metric::count());
+        }
+        Ok(Aggregation { group_by, metrics })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "group_by": self.group_by,
+            "metrics": self.metrics.iter().map(|m| m.to_json()).collect::<Vec<Value>>(),
+        })
+    }
+
+    /// Summary rows, one per group in the order each group was first seen: the
+    /// `group_by` fields followed by each metric.
+    pub fn apply(&self, rows: &[IndexMap<String, Value>]) -> Vec<IndexMap<String, Value>> {
+        let mut stream = AggregateStream::new(self.clone());
+        for row in rows {
+            stream.push(row);
+        }
+        stream.finish()
+    }
+}
+
+/// Incremental aggregation: each pushed row is folded into its group's accumulators and
+/// dropped, and the summary rows come out once input is exhausted.
+#[derive(Debug, Clone)]
+pub struct AggregateStream {
+    aggregation: Aggregation,
+    groups: IndexMap<String, (Vec<Value>, usize, Vec<Accumulator>)>,
+}
+
+impl AggregateStream {
+    pub fn new(aggregation: Aggregation) -> Self {
+        AggregateStream { aggregation, groups: IndexMap::new() }
+    }
+
+    pub fn push(&mut self, row: &IndexMap<String, Value>) {
+        let metrics = &self.aggregation.metrics;
+        let key_values: Vec<Value> = self.aggregation.group_by.iter().map(|k| row.get(k).cloned().unwrap_or(Value::Null)).collect();
+        let (_, row_count, accumulators) = self
+            .groups
+            .entry(Value::Array(key_values.clone()).to_string())
+            .or_insert_with(|| (key_values, 0, vec![Accumulator::new(); metrics.len()]));
+        *row_count += 1;
+        for (metric, acc) in metrics.iter().zip(accumulators.iter_mut()) {
+            acc.push(metric.op, metric.field.as_ref().and_then(|f| row.get(f)));
+        }
+    }
+
+    /// Summary rows, one per group in the order each group was first seen.
+    pub fn finish(&mut self) -> Vec<IndexMap<String, Value>> {
+        let Aggregation { group_by, metrics } = &self.aggregation;
+        let mut groups = std::mem::take(&mut self.groups);
+        if groups.is_empty() && group_by.is_empty() {
+            groups.insert(String::new(), (vec![], 0, vec![Accumulator::new(); metrics.len()]));
+        }
+        groups
+            .into_values()
+            .map(|(key_values, row_count, accumulators)| {
+                let mut summary: IndexMap<String, Value> = group_by.iter().cloned().zip(key_values).collect();
+                for (metric, acc) in metrics.iter().zip(accumulators.iter()) {
+                    summary.insert(metric.name.clone(), acc.result(metric.op, row_count, metric.field.is_some()));
+                }
+                summary
+            })
+            .collect()
+    }
+}
+
+impl ResultSet {
+    /// A result set of summary rows computed from this one's rows (each sheet's, in
+    /// multi-sheet mode) -- see `aggregate::Aggregation`.
+    pub fn aggregate(&self, aggregation: &Aggregation) -> ResultSet {
+        self.clone().map_row_set(|rows| aggregation.apply(&rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sales() -> Vec<IndexMap<String, Value>> {
+        serde_json::from_value(json!([
+            {"region": "west", "rep": "a", "revenue": 10, "date": "2026-01-03"},
+            {"region": "east", "rep": "b", "revenue": 2.5, "date": "2026-01-01"},
+            {"region": "west", "rep": "c", "revenue": 5, "date": "2026-01-01"},
+            {"region": "west", "rep": "a", "revenue": null, "date": "2026-01-02"},
+        ])).unwrap()
+    }
+
+    #[test]
+    fn test_aggregate_metrics_by_group() {
+        let aggregation = Aggregation::new(&["region"])
+            .metric(Metric::count())
+            .metric(Metric::new(AggregateOp::Sum, "revenue").named("total_revenue"))
+            .metric(Metric::new(AggregateOp::Mean, "revenue"))
+            .metric(Metric::new(AggregateOp::Count, "revenue"))
+            .metric(Metric::new(AggregateOp::Min, "date"))
+            .metric(Metric::new(AggregateOp::Max, "revenue"))
+            .metric(Metric::new(AggregateOp::DistinctCount, "rep"))
+            .metric(Metric::new(AggregateOp::Last, "rep"));
+        assert_eq!(json!(aggregation.apply(&sales())), json!([
+            {"region": "west", "count": 3, "total_revenue": 15, "mean_revenue": 7.5, "count_revenue": 2,
+             "min_date": "2026-01-01", "max_revenue": 10, "distinct_count_rep": 2, "last_rep": "a"},
+            {"region": "east", "count": 1, "total_revenue": 2.5, "mean_revenue": 2.5, "count_revenue": 1,
+             "min_date": "2026-01-01", "max_revenue": 2.5, "distinct_count_rep": 1, "last_rep": "b"},
+        ]));
+    }
+
+    #[test]
+    fn test_aggregate_from_json_and_without_group_by() {
+        let aggregation = Aggregation::from_json(&json!({
+            "metrics": [{"op": "avg", "field": "revenue", "as": "avg_revenue"}, {"op": "first", "field": "rep"}]
+        })).unwrap();
+        assert_eq!(json!(aggregation.apply(&sales())), json!([{"avg_revenue": 17.5 / 3.0, "first_rep": "a"}]));
+        assert_eq!(json!(Aggregation::from_json(&json!({})).unwrap().apply(&[])), json!([{"count": 0}]));
+        assert!(Aggregation::from_json(&json!({"metrics": [{"op": "sum"}]})).is_err(), "a sum needs a field");
+        assert!(Aggregation::from_json(&json!({"metrics": [{"op": "median", "field": "revenue"}]})).is_err());
+    }
+}
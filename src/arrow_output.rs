@@ -0,0 +1,585 @@
+//! Arrow `RecordBatch` and Parquet output, behind the optional `arrow` feature -- for
+//! loading conversions straight into DuckDB/Polars/DataFusion rather than round-tripping
+//! every row through JSON text first.
+//!
+//! The schema is derived from two sources, strongest first: each resolved `Column`'s
+//! explicit `Format` (an `integer` override is `Int64` even if every sampled cell happens
+//! to be blank), then the types actually observed in the converted rows. Nested output
+//! built via `KeySegment::Object`/`Array`/`InnerObject` maps onto Arrow `Struct` fields,
+//! and `KeySegment::Array`/`PlainArray` containers onto `List`s.
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_json::reader::Decoder;
+use arrow_schema::{ArrowError, DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use indexmap::IndexMap;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde_json::Value;
+
+use crate::error::GenericError;
+use crate::headers::column_output_keys;
+use crate::key_segment::{Identifier, KeySegment};
+use crate::reader::SaveRowFn;
+use crate::{Column, Format, ResultSet, SheetDataSet};
+
+/// Default number of rows per `RecordBatch`, matching arrow-json's own default.
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Intermediate, mergeable type tree -- Arrow's own `DataType`/`Fields` are immutable
+/// once built, so observed and declared types are unified here first and only converted
+/// to a real `Schema` at the end.
+#[derive(Debug, Clone, PartialEq)]
+enum TypeNode {
+    Null,
+    Boolean,
+    Integer,
+    Float,
+    Text,
+    Date,
+    /// `true` when the source strings carry a trailing `Z` (the default `DateTimeMode::Full`
+    /// rendering), so the column is UTC-anchored rather than naive.
+    DateTime(bool),
+    Time,
+    Struct(IndexMap<String, TypeNode>),
+    List(Box<TypeNode>),
+}
+
+impl TypeNode {
+    /// The least general type both sides fit into. Integer widens to Float; anything
+    /// else that disagrees (e.g. a number in one row and free text in another) falls back
+    /// to Text, which arrow-json fills by coercing the primitive -- never by erroring.
+    fn merge(self, other: TypeNode) -> TypeNode {
+        match (self, other) {
+            (TypeNode::Null, t) | (t, TypeNode::Null) => t,
+            (a, b) if a == b => a,
+            (TypeNode::Integer, TypeNode::Float) | (TypeNode::Float, TypeNode::Integer) => TypeNode::Float,
+            (TypeNode::DateTime(a), TypeNode::DateTime(b)) => TypeNode::DateTime(a && b),
+            (TypeNode::Struct(mut a), TypeNode::Struct(b)) => {
+                for (key, node) in b {
+                    let merged = match a.swap_remove(&key) {
+                        Some(existing) => existing.merge(node),
+                        None => node,
+                    };
+                    a.insert(key, merged);
+                }
+                TypeNode::Struct(a)
+            }
+            (TypeNode::List(a), TypeNode::List(b)) => TypeNode::List(Box::new(a.merge(*b))),
+            _ => TypeNode::Text,
+        }
+    }
+
+    fn from_value(value: &Value) -> TypeNode {
+        match value {
+            Value::Null => TypeNode::Null,
+            Value::Bool(_) => TypeNode::Boolean,
+            Value::Number(n) if n.is_i64() || n.is_u64() => TypeNode::Integer,
+            Value::Number(_) => TypeNode::Float,
+            Value::String(s) => infer_string_type(s),
+            Value::Array(items) => TypeNode::List(Box::new(
+                items.iter().map(TypeNode::from_value).fold(TypeNode::Null, TypeNode::merge),
+            )),
+            Value::Object(map) => TypeNode::Struct(
+                map.iter().map(|(k, v)| (k.clone(), TypeNode::from_value(v))).collect(),
+            ),
+        }
+    }
+
+    /// The leaf type an explicit `Format` override pins a column to, or `None` for
+    /// `Format::Auto`, which defers to whatever the data shows.
+    fn from_format(format: &Format) -> Option<TypeNode> {
+        match format {
+            Format::Auto => None,
+            Format::Text | Format::DateTimeCustom(_) => Some(TypeNode::Text),
+            Format::Integer => Some(TypeNode::Integer),
+            Format::Decimal(_) | Format::Float => Some(TypeNode::Float),
+            Format::Boolean | Format::Truthy | Format::TruthyCustom(_) => Some(TypeNode::Boolean),
+            Format::Date => Some(TypeNode::Date),
+            Format::DateTime => Some(TypeNode::DateTime(true)),
+            Format::DateTimeSimple => Some(TypeNode::DateTime(false)),
+            Format::Time | Format::Hm => Some(TypeNode::Time),
+            Format::Array(element, _) => Some(TypeNode::List(Box::new(
+                TypeNode::from_format(element).unwrap_or(TypeNode::Null),
+            ))),
+        }
+    }
+
+    fn from_identifier(id: &Identifier) -> TypeNode {
+        match id {
+            Identifier::String(_) => TypeNode::Text,
+            Identifier::Integer(_) => TypeNode::Integer,
+        }
+    }
+
+    fn to_data_type(&self) -> DataType {
+        match self {
+            // An all-null column still needs a concrete, writable type -- Utf8 is the one
+            // every downstream engine accepts and casts from without complaint.
+            TypeNode::Null | TypeNode::Text => DataType::Utf8,
+            TypeNode::Boolean => DataType::Boolean,
+            TypeNode::Integer => DataType::Int64,
+            TypeNode::Float => DataType::Float64,
+            TypeNode::Date => DataType::Date32,
+            TypeNode::DateTime(true) => DataType::Timestamp(TimeUnit::Millisecond, Some(Arc::from("UTC"))),
+            TypeNode::DateTime(false) => DataType::Timestamp(TimeUnit::Millisecond, None),
+            TypeNode::Time => DataType::Time32(TimeUnit::Second),
+            TypeNode::Struct(fields) => DataType::Struct(struct_fields(fields)),
+            TypeNode::List(element) => DataType::List(Arc::new(Field::new("item", element.to_data_type(), true))),
+        }
+    }
+}
+
+/// Recognises only the exact shapes this crate itself renders dates and times as (see
+/// `process_excel_datetime_value` in `reader.rs`), never a fuzzy guess -- a free-text
+/// column that merely contains something date-like stays `Utf8`.
+fn infer_string_type(value: &str) -> TypeNode {
+    if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+        TypeNode::Date
+    } else if value.ends_with('Z') && DateTime::parse_from_rfc3339(value).is_ok() {
+        TypeNode::DateTime(true)
+    } else if NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").is_ok() {
+        TypeNode::DateTime(false)
+    } else if NaiveTime::parse_from_str(value, "%H:%M:%S").is_ok() || NaiveTime::parse_from_str(value, "%H:%M").is_ok() {
+        TypeNode::Time
+    } else {
+        TypeNode::Text
+    }
+}
+
+fn struct_fields(fields: &IndexMap<String, TypeNode>) -> Fields {
+    fields
+        .iter()
+        .map(|(name, node)| Field::new(name, node.to_data_type(), true))
+        .collect()
+}
+
+/// Pins the leaf at the end of `segment`'s path inside `current` to `leaf`, creating any
+/// intermediate struct/list levels on the way -- the schema-side mirror of
+/// `insert_key_segment`.
+fn declare_segment(current: &mut IndexMap<String, TypeNode>, segment: &KeySegment, leaf: Option<TypeNode>) {
+    match segment {
+        KeySegment::Excluded => {}
+        KeySegment::Simple(key) => {
+            if let Some(leaf) = leaf {
+                current.insert(key.to_string(), leaf);
+            }
+        }
+        KeySegment::Object(key, next) => {
+            let entry = current
+                .entry(key.to_string())
+                .or_insert_with(|| TypeNode::Struct(IndexMap::new()));
+            if !matches!(entry, TypeNode::Struct(_)) {
+                *entry = TypeNode::Struct(IndexMap::new());
+            }
+            if let TypeNode::Struct(nested) = entry {
+                declare_segment(nested, next, leaf);
+            }
+        }
+        KeySegment::Array(container, id, key_field, next) => {
+            let entry = current
+                .entry(container.to_string())
+                .or_insert_with(|| TypeNode::List(Box::new(TypeNode::Struct(IndexMap::new()))));
+            if !matches!(entry, TypeNode::List(inner) if matches!(**inner, TypeNode::Struct(_))) {
+                *entry = TypeNode::List(Box::new(TypeNode::Struct(IndexMap::new())));
+            }
+            if let TypeNode::List(inner) = entry {
+                if let TypeNode::Struct(item) = inner.as_mut() {
+                    item.insert(key_field.to_string(), TypeNode::from_identifier(id));
+                    declare_segment(item, next, leaf);
+                }
+            }
+        }
+        KeySegment::InnerObject(id, field, next) => {
+            current.insert(field.to_string(), TypeNode::from_identifier(id));
+            declare_segment(current, next, leaf);
+        }
+        KeySegment::PlainArray(container) => {
+            if let Some(leaf) = leaf {
+                current.insert(container.to_string(), TypeNode::List(Box::new(leaf)));
+            }
+        }
+    }
+}
+
+/// Builds the Arrow schema for `rows`, with each resolved column's explicit `Format`
+/// (aligned by index with `keys`) taking precedence over the types observed in the rows.
+pub fn derive_schema(rows: &[IndexMap<String, Value>], keys: &[String], columns: &[Column]) -> SchemaRef {
+    let observed = rows.iter().fold(TypeNode::Struct(IndexMap::new()), |acc, row| {
+        let node = TypeNode::Struct(row.iter().map(|(k, v)| (k.clone(), TypeNode::from_value(v))).collect());
+        acc.merge(node)
+    });
+    let mut fields = match observed {
+        TypeNode::Struct(fields) => fields,
+        _ => IndexMap::new(),
+    };
+    for (index, key) in keys.iter().enumerate().filter(|(_, key)| !key.is_empty()) {
+        let col = columns.get(index);
+        let leaf = col.and_then(|c| TypeNode::from_format(&c.format));
+        match col.and_then(|c| c.key.as_ref()) {
+            Some(segment) => declare_segment(&mut fields, segment, leaf),
+            None => {
+                if let Some(leaf) = leaf {
+                    fields.insert(key.clone(), leaf);
+                } else {
+                    // Keep every header key in the schema, even one whose cells were all
+                    // dropped by omit_null_values -- a consistent column set across files
+                    // matters more downstream than a minimal one.
+                    fields.entry(key.clone()).or_insert(TypeNode::Null);
+                }
+            }
+        }
+    }
+    Arc::new(Schema::new(struct_fields(&fields)))
+}
+
+/// The Arrow schema for a result set's (first sheet's) rows.
+pub fn arrow_schema(result: &ResultSet) -> SchemaRef {
+    derive_schema(&result.data.first_sheet(), &result.keys, &result.columns)
+}
+
+fn build_decoder(schema: SchemaRef, batch_size: usize) -> Result<Decoder, GenericError> {
+    arrow_json::ReaderBuilder::new(schema)
+        .with_batch_size(batch_size)
+        // A cell that doesn't fit its column's type (free text in a column overridden as
+        // `integer`) becomes null rather than failing the whole conversion -- the same
+        // outcome the JSON output already gives such a cell.
+        .with_ignore_type_conflicts(true)
+        .with_coerce_primitive(true)
+        .build_decoder()
+        .map_err(|_| GenericError("arrow_schema_error"))
+}
+
+/// Iterates a sheet's rows as `RecordBatch`es of at most `batch_size` rows, implementing
+/// `RecordBatchReader` so it can be handed directly to anything that consumes an Arrow
+/// stream.
+pub struct SheetBatchReader {
+    schema: SchemaRef,
+    rows: Vec<IndexMap<String, Value>>,
+    batch_size: usize,
+    offset: usize,
+    decoder: Decoder,
+}
+
+impl SheetBatchReader {
+    pub fn new(rows: Vec<IndexMap<String, Value>>, keys: &[String], columns: &[Column], batch_size: usize) -> Result<Self, GenericError> {
+        let schema = derive_schema(&rows, keys, columns);
+        let batch_size = batch_size.max(1);
+        let decoder = build_decoder(schema.clone(), batch_size)?;
+        Ok(SheetBatchReader { schema, rows, batch_size, offset: 0, decoder })
+    }
+
+    pub fn from_result_set(result: &ResultSet, batch_size: usize) -> Result<Self, GenericError> {
+        Self::new(result.data.first_sheet(), &result.keys, &result.columns, batch_size)
+    }
+
+    pub fn from_sheet(sheet: &SheetDataSet, batch_size: usize) -> Result<Self, GenericError> {
+        Self::new(sheet.rows.clone(), &sheet.keys, &[], batch_size)
+    }
+}
+
+impl Iterator for SheetBatchReader {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.rows.len() {
+            return None;
+        }
+        let end = (self.offset + self.batch_size).min(self.rows.len());
+        let chunk = &self.rows[self.offset..end];
+        self.offset = end;
+        match self.decoder.serialize(chunk) {
+            Ok(()) => self.decoder.flush().transpose(),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl RecordBatchReader for SheetBatchReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// Converts a result set's (first sheet's) rows into `RecordBatch`es of at most
+/// `batch_size` rows each.
+pub fn to_record_batches(result: &ResultSet, batch_size: usize) -> Result<Vec<RecordBatch>, GenericError> {
+    SheetBatchReader::from_result_set(result, batch_size)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| GenericError("arrow_conversion_error"))
+}
+
+fn parquet_writer(path: &Path, schema: SchemaRef) -> Result<ArrowWriter<File>, GenericError> {
+    let file = File::create(path)?;
+    let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    ArrowWriter::try_new(file, schema, Some(props)).map_err(|_| GenericError("parquet_write_error"))
+}
+
+fn write_batches(reader: SheetBatchReader, path: &Path) -> Result<usize, GenericError> {
+    let mut writer = parquet_writer(path, reader.schema())?;
+    let mut num_rows = 0;
+    for batch in reader {
+        let batch = batch.map_err(|_| GenericError("arrow_conversion_error"))?;
+        num_rows += batch.num_rows();
+        writer.write(&batch).map_err(|_| GenericError("parquet_write_error"))?;
+    }
+    writer.close().map_err(|_| GenericError("parquet_write_error"))?;
+    Ok(num_rows)
+}
+
+/// Writes a result set's (first sheet's) rows to a Snappy-compressed Parquet file,
+/// returning the number of rows written.
+pub fn write_parquet(result: &ResultSet, path: &Path) -> Result<usize, GenericError> {
+    write_batches(SheetBatchReader::from_result_set(result, DEFAULT_BATCH_SIZE)?, path)
+}
+
+/// Writes every sheet of a multi-sheet result set to its own Parquet file in `dir`,
+/// named after the sheet's snake_cased key (`SheetDataSet::key`), returning the paths
+/// written. A single-sheet result set writes one file named after `result.filename`'s
+/// stem.
+pub fn write_parquet_sheets(result: &ResultSet, dir: &Path) -> Result<Vec<PathBuf>, GenericError> {
+    let sheets = result.data.sheets();
+    if sheets.is_empty() {
+        let stem = Path::new(&result.filename).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("data".to_string());
+        let path = dir.join(format!("{}.parquet", stem));
+        write_parquet(result, &path)?;
+        return Ok(vec![path]);
+    }
+    let mut paths = Vec::with_capacity(sheets.len());
+    for sheet in &sheets {
+        let path = dir.join(format!("{}.parquet", sheet.key()));
+        write_batches(SheetBatchReader::from_sheet(sheet, DEFAULT_BATCH_SIZE)?, &path)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Streams rows into a Parquet file a batch at a time, for the asynchronous
+/// (`ReadMode::Async`) path where rows never all sit in memory at once. The schema is
+/// fixed from the first `batch_size` rows plus `columns` (matched by output key, since a
+/// save callback only ever sees converted rows, not positions) -- later rows are coerced
+/// to it, with anything that doesn't fit written as null.
+pub struct ParquetRowWriter {
+    path: PathBuf,
+    columns: Vec<Column>,
+    batch_size: usize,
+    buffer: Vec<IndexMap<String, Value>>,
+    writer: Option<(ArrowWriter<File>, Decoder)>,
+    num_rows: usize,
+}
+
+impl ParquetRowWriter {
+    pub fn new(path: &Path, columns: &[Column], batch_size: usize) -> Self {
+        ParquetRowWriter {
+            path: path.to_path_buf(),
+            columns: columns.to_vec(),
+            batch_size: batch_size.max(1),
+            buffer: Vec::new(),
+            writer: None,
+            num_rows: 0,
+        }
+    }
+
+    pub fn push_row(&mut self, row: IndexMap<String, Value>) -> Result<(), GenericError> {
+        self.buffer.push(row);
+        if self.buffer.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), GenericError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        if self.writer.is_none() {
+            let row_keys: Vec<String> = self.buffer[0].keys().cloned().collect();
+            let keys = column_output_keys(&self.columns, &row_keys);
+            let schema = derive_schema(&self.buffer, &keys, &self.columns);
+            let decoder = build_decoder(schema.clone(), self.batch_size)?;
+            self.writer = Some((parquet_writer(&self.path, schema)?, decoder));
+        }
+        if let Some((writer, decoder)) = self.writer.as_mut() {
+            decoder.serialize(&self.buffer).map_err(|_| GenericError("arrow_conversion_error"))?;
+            if let Some(batch) = decoder.flush().map_err(|_| GenericError("arrow_conversion_error"))? {
+                self.num_rows += batch.num_rows();
+                writer.write(&batch).map_err(|_| GenericError("parquet_write_error"))?;
+            }
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Flushes any buffered rows and closes the file, returning the number of rows
+    /// written. Must be called once the read has finished -- a Parquet file is unreadable
+    /// until its footer is written.
+    pub fn finish(&mut self) -> Result<usize, GenericError> {
+        self.flush()?;
+        if self.writer.is_none() {
+            // No rows at all -- still leave a valid, empty file behind.
+            let keys = column_output_keys(&self.columns, &[]);
+            let schema = derive_schema(&[], &keys, &self.columns);
+            self.writer = Some((parquet_writer(&self.path, schema.clone())?, build_decoder(schema, self.batch_size)?));
+        }
+        if let Some((writer, _)) = self.writer.take() {
+            writer.close().map_err(|_| GenericError("parquet_write_error"))?;
+        }
+        Ok(self.num_rows)
+    }
+}
+
+/// Wraps a shared `ParquetRowWriter` as a `SaveRowFn` for `process_spreadsheet_async`.
+/// Keep a clone of the `Arc` to call `finish()` on once processing returns.
+pub fn parquet_save_fn(writer: Arc<Mutex<ParquetRowWriter>>) -> SaveRowFn {
+    Box::new(move |row| {
+        let mut guard = writer.lock().map_err(|_| GenericError("parquet_write_error"))?;
+        guard.push_row(row)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_spreadsheet_direct, DateTimeMode, OptionSet};
+    use arrow_array::{Array, Date32Array, Int64Array, ListArray, StringArray, StructArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    fn rows(values: Value) -> Vec<IndexMap<String, Value>> {
+        serde_json::from_value(values).unwrap()
+    }
+
+    fn write_csv_fixture(filename: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(filename);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_schema_infers_scalar_types_and_widens_int_to_float() {
+        let sample = rows(json!([
+            {"sku": "A1", "qty": 3, "price": 2, "active": true, "added": "2023-06-15"},
+            {"sku": "A2", "qty": 4, "price": 2.5, "active": null, "added": "2023-06-16"}
+        ]));
+        let keys: Vec<String> = ["sku", "qty", "price", "active", "added"].iter().map(|s| s.to_string()).collect();
+        let schema = derive_schema(&sample, &keys, &[]);
+        assert_eq!(schema.field_with_name("sku").unwrap().data_type(), &DataType::Utf8);
+        assert_eq!(schema.field_with_name("qty").unwrap().data_type(), &DataType::Int64);
+        assert_eq!(schema.field_with_name("price").unwrap().data_type(), &DataType::Float64);
+        assert_eq!(schema.field_with_name("active").unwrap().data_type(), &DataType::Boolean);
+        assert_eq!(schema.field_with_name("added").unwrap().data_type(), &DataType::Date32);
+    }
+
+    #[test]
+    fn test_explicit_format_wins_over_observed_values() {
+        // Every sampled "zip" value is numeric-looking, but the column is explicitly text.
+        let sample = rows(json!([{"zip": 2134}, {"zip": 90210}]));
+        let keys = vec!["zip".to_string()];
+        let cols = vec![Column::new_format(Format::Text, None)];
+        let schema = derive_schema(&sample, &keys, &cols);
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+    }
+
+    #[test]
+    fn test_nested_key_segments_map_onto_struct_and_list_types() {
+        let mut weight = Column::new_format(Format::Integer, None);
+        weight.key = Some(KeySegment::Object(Arc::from("measurements"), Arc::new(KeySegment::Simple(Arc::from("weight")))));
+        let mut sales = Column::new_format(Format::Float, None);
+        sales.key = Some(KeySegment::Array(
+            Arc::from("sales"),
+            Identifier::from_int(2025),
+            Arc::from("year"),
+            Arc::new(KeySegment::Simple(Arc::from("amount"))),
+        ));
+        let mut tags = Column::new_format(Format::Text, None);
+        tags.key = Some(KeySegment::PlainArray(Arc::from("tags")));
+        let keys: Vec<String> = ["measurements", "sales", "tags"].iter().map(|s| s.to_string()).collect();
+        let sample = rows(json!([
+            {"measurements": {"weight": 60}, "sales": [{"year": 2025, "amount": 10.5}], "tags": ["a", "b"]}
+        ]));
+        let schema = derive_schema(&sample, &keys, &[weight, sales, tags]);
+
+        let DataType::Struct(measurements) = schema.field_with_name("measurements").unwrap().data_type() else {
+            panic!("measurements should be a struct");
+        };
+        assert_eq!(measurements.find("weight").unwrap().1.data_type(), &DataType::Int64);
+
+        let DataType::List(item) = schema.field_with_name("sales").unwrap().data_type() else {
+            panic!("sales should be a list");
+        };
+        let DataType::Struct(item_fields) = item.data_type() else {
+            panic!("sales items should be structs");
+        };
+        assert_eq!(item_fields.find("year").unwrap().1.data_type(), &DataType::Int64);
+        assert_eq!(item_fields.find("amount").unwrap().1.data_type(), &DataType::Float64);
+
+        let DataType::List(tag) = schema.field_with_name("tags").unwrap().data_type() else {
+            panic!("tags should be a list");
+        };
+        assert_eq!(tag.data_type(), &DataType::Utf8);
+
+        // and the rows actually decode into that schema
+        let reader = SheetBatchReader::new(sample, &keys, &[], 10).unwrap();
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+        let measurements = batches[0].column_by_name("measurements").unwrap().as_any().downcast_ref::<StructArray>().unwrap();
+        let weight = measurements.column_by_name("weight").unwrap().as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(weight.value(0), 60);
+        let tags = batches[0].column_by_name("tags").unwrap().as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(tags.value(0).len(), 2);
+    }
+
+    #[test]
+    fn test_record_batches_respect_batch_size_and_null_out_type_conflicts() {
+        let path = write_csv_fixture("arrow_batches.csv", "sku,qty,added\nA1,3,2023-06-15\nA2,n/a,2023-06-16\nA3,5,2023-06-17\n");
+        let opts = OptionSet::new(&path).override_columns(&[
+            json!({"source_key": "qty", "format": "integer"}),
+            json!({"source_key": "added", "format": "date"}),
+        ]);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let batches = to_record_batches(&result, 2).unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 2);
+        let qty = batches[0].column_by_name("qty").unwrap().as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(qty.value(0), 3);
+        assert!(qty.is_null(1), "a cell that failed its integer override is null, not an error");
+        let added = batches[0].column_by_name("added").unwrap().as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(added.value_as_date(0), NaiveDate::from_ymd_opt(2023, 6, 15));
+    }
+
+    #[test]
+    fn test_write_parquet_round_trips_rows() {
+        let result = process_spreadsheet_direct(&OptionSet::new("data/sample-data-1.csv").max_row_count(50)).unwrap();
+        let path = std::env::temp_dir().join("arrow_round_trip.parquet");
+        assert_eq!(write_parquet(&result, &path).unwrap(), 50);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap().build().unwrap();
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 50);
+        let first_name = batches[0].column_by_name("first_name").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(first_name.value(0), result.to_vec()[0]["first_name"].as_str().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_parquet_save_fn_streams_rows_in_async_mode() {
+        let path = std::env::temp_dir().join("arrow_async_stream.parquet");
+        let cols = vec![
+            Column::from_source_key_with_format("weight", Some("weight"), Format::Float, None, DateTimeMode::Full, false),
+            Column::from_source_key_with_format("height", None, Format::Float, None, DateTimeMode::Full, false),
+        ];
+        let writer = Arc::new(Mutex::new(ParquetRowWriter::new(&path, &cols, 16)));
+        let opts = OptionSet::new("data/sample-data-1.csv").read_mode_async();
+        crate::process_spreadsheet_async(&opts, parquet_save_fn(writer.clone()), None).await.unwrap();
+        // async mode streams every row, not just the first max_row_count
+        assert_eq!(writer.lock().unwrap().finish().unwrap(), 400);
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(builder.schema().field_with_name("weight").unwrap().data_type(), &DataType::Float64);
+        // a column matched only by source key is typed under that key
+        assert_eq!(builder.schema().field_with_name("height").unwrap().data_type(), &DataType::Float64);
+        assert!(builder.schema().field_with_name("").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! Explicit cell ranges in A1 notation, e.g. `C5:K240`, or `C5:K` to read down to the
+//! last row. Reading is restricted to the rectangle before header detection, so
+//! `header_row` and `data_row_index` count from the first row of the range.
+use calamine::{Data, Range};
+
+use crate::error::GenericError;
+use crate::headers::to_a1_col_key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRange {
+    /// 0-based (row, column) of the top-left cell
+    pub start: (u32, u32),
+    /// 0-based last row, `None` to read to the end of the sheet
+    pub end_row: Option<u32>,
+    /// 0-based last column
+    pub end_col: u32,
+}
+
+impl CellRange {
+    /// Parses `C5:K240` or the open-ended `C5:K`, ignoring case and `$` anchors.
+    pub fn parse(text: &str) -> Result<Self, GenericError> {
+        let invalid = GenericError("invalid_cell_range");
+        let (first, last) = text.trim().split_once(':').ok_or(invalid.clone())?;
+        let (start_col, start_row) = split_cell_ref(first).ok_or(invalid.clone())?;
+        let (end_col, end_row) = split_cell_ref(last).ok_or(invalid.clone())?;
+        let start_row = start_row.ok_or(invalid.clone())?;
+        if end_col < start_col || end_row.is_some_and(|row| row < start_row) {
+            return Err(invalid);
+        }
+        Ok(CellRange { start: (start_row, start_col), end_row, end_col })
+    }
+
+    pub fn width(&self) -> usize {
+        (self.end_col - self.start.1) as usize + 1
+    }
+
+    /// Whether a 0-based sheet row falls inside the range.
+    pub fn contains_row(&self, row: usize) -> bool {
+        row >= self.start.0 as usize && self.end_row.is_none_or(|end| row <= end as usize)
+    }
+
+    /// The part of a worksheet range inside this rectangle, padded with empty cells where
+    /// the sheet has none. Positions in a calamine range are absolute, so this works
+    /// whichever cell the used range starts at. Rows stop at the sheet's last used row.
+    pub fn restrict(&self, range: &Range<Data>) -> Range<Data> {
+        let Some((sheet_end_row, _)) = range.end() else {
+            return Range::empty();
+        };
+        let last_row = self.end_row.map_or(sheet_end_row, |row| row.min(sheet_end_row));
+        if last_row < self.start.0 {
+            return Range::empty();
+        }
+        range.range(self.start, (last_row, self.end_col))
+    }
+
+    /// The cells of a CSV record inside the range's columns, padded to its width.
+    pub fn slice_fields<'a>(&self, fields: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+        let mut cells: Vec<&str> = fields.skip(self.start.1 as usize).take(self.width()).collect();
+        cells.resize(self.width(), "");
+        cells
+    }
+}
+
+impl std::fmt::Display for CellRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let col = |index: u32| to_a1_col_key(index as usize).to_uppercase();
+        write!(f, "{}{}:{}", col(self.start.1), self.start.0 + 1, col(self.end_col))?;
+        if let Some(row) = self.end_row {
+            write!(f, "{}", row + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits `C5` into 0-based (column, row), with no row for a bare column like `K`.
+fn split_cell_ref(cell: &str) -> Option<(u32, Option<u32>)> {
+    let cell = cell.trim().replace('$', "");
+    let split = cell.find(|c: char| c.is_ascii_digit()).unwrap_or(cell.len());
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) || letters.len() > 3 {
+        return None;
+    }
+    let col = letters
+        .to_ascii_uppercase()
+        .bytes()
+        .fold(0u32, |acc, b| acc * 26 + (b - b'A') as u32 + 1)
+        - 1;
+    let row = match digits {
+        "" => None,
+        _ => Some(digits.parse::<u32>().ok().filter(|row| *row > 0)? - 1),
+    };
+    Some((col, row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cell_ranges() {
+        let range = CellRange::parse("C5:K240").unwrap();
+        assert_eq!((range.start, range.end_row, range.end_col), ((4, 2), Some(239), 10));
+        assert_eq!(range.width(), 9);
+        assert_eq!(range.to_string(), "C5:K240");
+        let open = CellRange::parse("$c$5:aa").unwrap();
+        assert_eq!((open.end_row, open.end_col), (None, 26));
+        assert_eq!(open.to_string(), "C5:AA");
+        assert!(open.contains_row(1_000_000) && !open.contains_row(3));
+        for invalid in ["C5", "C:K", "K5:C9", "C9:K5", "C0:K5", "5C:K9"] {
+            assert!(CellRange::parse(invalid).is_err(), "{invalid}");
+        }
+        assert_eq!(range.slice_fields(["a", "b", "c", "d"].into_iter()), vec!["c", "d", "", "", "", "", "", "", ""]);
+    }
+}
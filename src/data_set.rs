@@ -1,11 +1,17 @@
 use std::{fs::File, io::BufReader};
-use calamine::{Reader, Sheets};
+use calamine::Sheets;
 use heck::ToSnakeCase;
 use indexmap::IndexMap;
 use serde::Serialize;
 use serde_json::{json, Value};
 
 use crate::key_segment::insert_key_segment;
+use crate::sheets::select_worksheets;
+use crate::workbook::{DefinedName, DocumentProperties, SheetInfo};
+use crate::cell_range::CellRange;
+use crate::error::GenericError;
+use crate::tables::TableInfo;
+use crate::reshape::Unpivot;
 use crate::{Column, OptionSet, PathData, ReadMode};
 
 
@@ -16,6 +22,12 @@ pub struct WorkbookInfo {
     pub extension: String,
     pub selected: Option<Vec<String>>,
     pub sheets: Vec<String>,
+    /// Dimensions, visibility and type per sheet, only populated by `read_workbook_info`
+    pub sheet_info: Vec<SheetInfo>,
+    pub defined_names: Vec<DefinedName>,
+    /// Excel tables, xlsx and xlsm only
+    pub tables: Vec<TableInfo>,
+    pub properties: Option<DocumentProperties>,
 }
 
 impl WorkbookInfo {
@@ -25,6 +37,10 @@ impl WorkbookInfo {
             filename: path_data.filename(), 
             selected: Some(selected.to_vec()),
             sheets: sheet_refs.to_vec(),
+            sheet_info: vec![],
+            defined_names: vec![],
+            tables: vec![],
+            properties: None,
         }
     }
 
@@ -35,6 +51,10 @@ impl WorkbookInfo {
             filename: path_data.filename(), 
             selected: None,
             sheets: vec![sheet_name.to_owned()],
+            sheet_info: vec![],
+            defined_names: vec![],
+            tables: vec![],
+            properties: None,
         }
     }
 
@@ -54,6 +74,17 @@ impl WorkbookInfo {
     pub fn sheets(&self) -> Vec<String> {
         self.sheets.clone()
     }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "filename": self.filename,
+            "extension": self.extension,
+            "sheets": self.sheet_info.iter().map(|s| s.to_json()).collect::<Vec<Value>>(),
+            "defined_names": self.defined_names.iter().map(|d| d.to_json()).collect::<Vec<Value>>(),
+            "tables": self.tables.iter().map(|t| t.to_json()).collect::<Vec<Value>>(),
+            "properties": self.properties.as_ref().map(|p| p.to_json()),
+        })
+    }
 }
 
 
@@ -79,6 +110,37 @@ pub struct ResultSet {
     /// from an explicit `OptionSet.data_row_index` override or auto-detection. Always
     /// concrete: a file always starts reading data *somewhere*, unlike the header row.
     pub body_start_index: usize,
+    /// Column overrides resolved against the sheet's natural header keys at read time,
+    /// aligned by index with `keys` -- the same `resolve_columns()` output the reader
+    /// itself converted each cell with, so downstream consumers (e.g. `arrow_output.rs`)
+    /// see each column's effective `Format`/`KeySegment` rather than the raw, possibly
+    /// sparse or `source_key`-matched list in `opts.rows.columns`. Empty for result sets
+    /// that didn't come from a single-sheet read.
+    pub columns: Vec<Column>,
+    /// Number of rows dropped by `OptionSet.filter` -- across all sheets in multi-sheet
+    /// mode. Only rows actually read count, so with a `max` set, rows after the last
+    /// kept one are neither kept nor filtered.
+    pub filtered_count: usize,
+    /// Distinct keys without a match per lookup, keyed by `Lookup::name` -- see
+    /// `lookup.rs`. Empty without lookups.
+    pub unmatched_keys: IndexMap<String, Vec<Value>>,
+    /// Non-fatal issues found while reading, such as sheets in a union whose columns
+    /// differ from the first sheet's.
+    pub warnings: Vec<String>,
+    /// The cell range actually read when `OptionSet.cell_range` is set, with an open end
+    /// resolved to the last row. `header_row_index` and `body_start_index` are relative
+    /// to its first row. Only set for single-sheet and CSV reads.
+    pub cell_range: Option<CellRange>,
+    /// 0-based index one past the last data row, counted like `body_start_index`, when
+    /// `data_end_row`, `skip_footer` or footer detection ended the read early. `None`
+    /// when rows were read to the end of the sheet or file.
+    pub body_end_index: Option<usize>,
+    /// Whether the table was read with records down its columns -- see
+    /// `OptionSet.orientation`. Header and data row indices then count columns.
+    pub transposed: bool,
+    /// Values extracted with `OptionSet.form`, by key, from the whole sheet or file
+    /// whatever range the rows were read from.
+    pub form: Option<IndexMap<String, Value>>,
 }
 
 impl ResultSet {
@@ -104,9 +166,134 @@ impl ResultSet {
       opts: opts.to_owned(),
       header_row_index,
       body_start_index,
+      columns: vec![],
+      filtered_count: 0,
+      unmatched_keys: IndexMap::new(),
+      warnings: vec![],
+      cell_range: None,
+      body_end_index: None,
+      transposed: false,
+      form: None,
     }
   }
 
+  /// Attaches the resolved column overrides actually used for this read -- see the
+  /// `columns` field doc.
+  pub fn with_columns(mut self, columns: &[Column]) -> Self {
+    self.columns = columns.to_vec();
+    self
+  }
+
+  /// Records how many rows the row filter dropped -- see the `filtered_count` field doc.
+  pub fn with_filtered_count(mut self, count: usize) -> Self {
+    self.filtered_count = count;
+    self
+  }
+
+  pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+    self.warnings = warnings;
+    self
+  }
+
+  pub fn with_cell_range(mut self, cell_range: Option<CellRange>) -> Self {
+    self.cell_range = cell_range;
+    self
+  }
+
+  pub fn with_body_end_index(mut self, body_end_index: Option<usize>) -> Self {
+    self.body_end_index = body_end_index;
+    self
+  }
+
+  pub fn with_transposed(mut self, transposed: bool) -> Self {
+    self.transposed = transposed;
+    self
+  }
+
+  pub fn with_form(mut self, form: Option<IndexMap<String, Value>>) -> Self {
+    self.form = form;
+    self
+  }
+
+  /// Records the keys each lookup failed to match -- see the `unmatched_keys` field doc.
+  pub fn with_unmatched_keys(mut self, keys: IndexMap<String, Vec<Value>>) -> Self {
+    self.unmatched_keys = keys;
+    self
+  }
+
+  /// Replaces each sheet's rows with `transform(rows)` -- for stages that reshape the
+  /// whole row set after reading (see `reshape::transform_rows`). Keys then list the
+  /// top-level output fields in first-seen order, and `columns` is cleared, since the
+  /// source columns no longer line up with the output. Both are left alone when no rows
+  /// were captured (async mode), as there's no output to take keys from.
+  pub fn map_row_set<F>(mut self, transform: F) -> Self
+  where
+    F: Fn(Vec<IndexMap<String, Value>>) -> Vec<IndexMap<String, Value>>,
+  {
+    match self.data {
+      SpreadData::Single(rows) if rows.is_empty() => {
+        self.data = SpreadData::Single(rows);
+        return self;
+      }
+      SpreadData::Single(rows) => {
+        let rows = transform(rows);
+        self.keys = row_set_keys(&rows);
+        self.data = SpreadData::Single(rows);
+      }
+      SpreadData::Multiple(sheets) => {
+        let sheets: Vec<SheetDataSet> = sheets.into_iter().map(|mut sheet| {
+          if sheet.rows.is_empty() {
+            return sheet;
+          }
+          sheet.rows = transform(sheet.rows);
+          sheet.keys = row_set_keys(&sheet.rows);
+          sheet
+        }).collect();
+        if let Some(first) = sheets.first() {
+          self.keys = first.keys.clone();
+        }
+        self.data = SpreadData::Multiple(sheets);
+      }
+    }
+    self.columns = vec![];
+    self
+  }
+
+  /// Replaces a single data set's rows with `rows` built from the whole read, such as
+  /// aggregated summary rows, restoring `opts` as the options the caller asked for. The
+  /// rows are kept unless `opts` defers them to a save callback, and `num_rows` and
+  /// `keys` describe them either way.
+  pub fn with_row_set(mut self, rows: Vec<IndexMap<String, Value>>, opts: &OptionSet) -> Self {
+    self.keys = row_set_keys(&rows);
+    self.num_rows = rows.len();
+    self.data = SpreadData::from_single(if opts.capture_rows() { rows } else { vec![] });
+    self.opts = opts.clone();
+    self.columns = vec![];
+    self
+  }
+
+  /// Describes rows that a row-set stage passed to a save callback rather than capturing:
+  /// `num_rows` counts them and `keys` lists their top-level fields in first-seen order.
+  pub fn with_saved_row_set(mut self, num_rows: usize, keys: Vec<String>) -> Self {
+    self.num_rows = num_rows;
+    self.keys = keys;
+    self.columns = vec![];
+    self
+  }
+
+  /// Lists the long-format keys of unpivoted rows in place of the header keys, for each
+  /// sheet too, and clears `columns`, which no longer line up with the output.
+  pub fn with_unpivot_keys(mut self, unpivot: &Unpivot) -> Self {
+    self.keys = unpivot.output_keys(&self.keys);
+    if let SpreadData::Multiple(sheets) = &mut self.data {
+      for sheet in sheets.iter_mut() {
+        sheet.keys = unpivot.output_keys(&sheet.keys);
+      }
+    }
+    self.columns = vec![];
+    self
+  }
+
   pub fn from_multiple(sheets: &[SheetDataSet], info: &WorkbookInfo, opts: &OptionSet) -> Self {
     let selected = None;
     let mut sheet_names = vec![];
@@ -136,6 +323,14 @@ impl ResultSet {
       // single-sheet reads), per-sheet resolved indices aren't tracked here yet.
       header_row_index: None,
       body_start_index: 0,
+      columns: vec![],
+      filtered_count: 0,
+      unmatched_keys: IndexMap::new(),
+      warnings: vec![],
+      cell_range: None,
+      body_end_index: None,
+      transposed: false,
+      form: None,
     }
   }
 
@@ -160,6 +355,30 @@ impl ResultSet {
     if let Some(out_ref_str) = self.out_ref.clone() {
       result["outref"] = json!(out_ref_str);
     }
+    if self.opts.filter.is_some() {
+      result["filtered_count"] = json!(self.filtered_count);
+    }
+    if !self.opts.lookups.is_empty() {
+      result["unmatched_keys"] = json!(self.unmatched_keys);
+    }
+    if !self.warnings.is_empty() {
+      result["warnings"] = json!(self.warnings);
+    }
+    if let Some(cell_range) = &self.cell_range {
+      result["range"] = json!(cell_range.to_string());
+      result["header_row_index"] = json!(self.header_row_index);
+      result["body_start_index"] = json!(self.body_start_index);
+    }
+    if let Some(end) = self.body_end_index {
+      result["body_start_index"] = json!(self.body_start_index);
+      result["body_end_index"] = json!(end);
+    }
+    if self.transposed {
+      result["transposed"] = json!(true);
+    }
+    if let Some(form) = &self.form {
+      result["form"] = json!(form);
+    }
     result
   }
 
@@ -182,6 +401,18 @@ impl ResultSet {
       lines.push(format!("selected sheet{}: {}", plural, selected_names.join(", ")));
     }
     lines.push(format!("row count: {}", self.num_rows));
+    if self.opts.filter.is_some() {
+      lines.push(format!("filtered out: {}", self.filtered_count));
+    }
+    for (key, unmatched) in &self.unmatched_keys {
+      lines.push(format!("unmatched {}: {}", key, unmatched.len()));
+    }
+    for warning in &self.warnings {
+      lines.push(format!("warning: {}", warning));
+    }
+    if let Some(cell_range) = &self.cell_range {
+      lines.push(format!("range read: {}", cell_range));
+    }
     lines.push(format!("fields: {}", self.keys.join(",")));
     lines.push(format!("multimode: {}", self.multimode()));
     for opt_line in self.opts.to_lines() {
@@ -245,7 +476,12 @@ pub struct SheetDataSet {
   pub sheet: (String, String),
   pub num_rows: usize,
   pub keys: Vec<String>,
-  pub rows: Vec<IndexMap<String, Value>>
+  pub rows: Vec<IndexMap<String, Value>>,
+  /// 0-based index of the header row this sheet was read with, as for
+  /// `ResultSet.header_row_index`.
+  pub header_row_index: Option<usize>,
+  /// 0-based index of this sheet's first data row, as for `ResultSet.body_start_index`.
+  pub body_start_index: usize,
 }
 
 impl SheetDataSet {
@@ -257,10 +493,19 @@ impl SheetDataSet {
       sheet: (name.to_string(), name.to_snake_case()),
       keys: keys.to_vec(),
       rows: rows.to_vec(),
-      num_rows: total
+      num_rows: total,
+      header_row_index: None,
+      body_start_index: 0,
     }
   }
 
+  /// Records the header row and first data row this sheet resolved to at read time.
+  pub fn with_indices(mut self, header_row_index: Option<usize>, body_start_index: usize) -> Self {
+    self.header_row_index = header_row_index;
+    self.body_start_index = body_start_index;
+    self
+  }
+
   pub fn key(&self) -> String {
     self.sheet.1.clone()
   }
@@ -321,11 +566,22 @@ pub enum DataSet {
    Count(usize) 
 }
 
+/// Top-level keys of `rows` in first-seen order.
+fn row_set_keys(rows: &[IndexMap<String, Value>]) -> Vec<String> {
+  let mut keys: IndexMap<String, ()> = IndexMap::new();
+  for row in rows {
+    for key in row.keys() {
+      keys.entry(key.clone()).or_default();
+    }
+  }
+  keys.into_keys().collect()
+}
+
 impl DataSet {
   pub fn from_count_and_rows(count: usize, rows: Vec<IndexMap<String, Value>>, opts: &OptionSet) -> Self {
     match opts.read_mode() {
-      ReadMode::Sync | ReadMode::PreviewMultiple => DataSet::WithRows(count, rows),
-      ReadMode::Async => DataSet::Count(count),
+      ReadMode::Sync | ReadMode::PreviewMultiple | ReadMode::Multiple => DataSet::WithRows(count, rows),
+      ReadMode::Async | ReadMode::AsyncMultiple => DataSet::Count(count),
     }
   }
 }
@@ -384,34 +640,22 @@ fn strip_nested_nulls(value: &mut Value) {
     }
 }
 
+/// Selected sheet names, all sheet names and the selected indices.
+pub type SheetSelection = (Vec<String>, Vec<String>, Vec<usize>);
+
+/// See `sheets::select_worksheets`. Never fails: without a match the first sheet is
+/// selected, even when the sheet filter is strict.
 pub fn match_sheet_name_and_index(workbook: &mut Sheets<BufReader<File>>, opts: &OptionSet) -> (Vec<String>, Vec<String>, Vec<usize>) {
-  let mut sheet_indices = vec![];
-  let mut selected_names: Vec<String> = vec![];
-  let sheet_names = workbook.worksheets().into_iter().map(|ws| ws.0).collect::<Vec<String>>();
-  if let Some(sheet_keys) = opts.selected.clone() {
-      for sheet_key in sheet_keys {
-          if let Some(sheet_index) = sheet_names.iter().position(|s| s.to_snake_case() == sheet_key.to_snake_case()) {
-              sheet_indices.push(sheet_index);
-              selected_names.push(sheet_names[sheet_index].clone());
-          }
-      }
-  }
-  if sheet_indices.is_empty() && !opts.indices.is_empty() {
-    for s_index in opts.indices.clone() {
-      let sheet_index = s_index as usize;
-      if let Some(sheet_name) = sheet_names.get(sheet_index) {
-          sheet_indices.push(sheet_index);
-          selected_names.push(sheet_name.to_owned());
-      }
-    }
-  }
-  if sheet_indices.is_empty() {
-    sheet_indices = vec![0];
-    if !sheet_names.is_empty() {
-      selected_names.push(sheet_names[0].clone());
-    }
-  }
-  (selected_names, sheet_names, sheet_indices)
+  let mut lenient = opts.clone();
+  lenient.sheet_filter.strict = false;
+  try_match_sheet_name_and_index(workbook, &lenient).unwrap_or_default()
+}
+
+/// As `match_sheet_name_and_index`, but a strict sheet filter that matches nothing
+/// fails with `no_matching_sheets`.
+pub fn try_match_sheet_name_and_index(workbook: &mut Sheets<BufReader<File>>, opts: &OptionSet) -> Result<SheetSelection, GenericError> {
+  let matched = select_worksheets(workbook, opts)?;
+  Ok((matched.selected, matched.sheet_names, matched.indices))
 }
 
 #[cfg(test)]
@@ -484,6 +728,10 @@ mod tests {
     // no match found -> falls back to the first sheet, not an error
     assert_eq!(selected_names, vec!["Rsults-2".to_string()]);
     assert_eq!(sheet_indices, vec![0]);
+    // even when the filter is strict, unless the strict selection is asked for
+    let strict = opts_selecting("nonexistent sheet name").strict_sheet_match();
+    assert_eq!(match_sheet_name_and_index(&mut workbook, &strict).0, vec!["Rsults-2".to_string()]);
+    assert_eq!(try_match_sheet_name_and_index(&mut workbook, &strict).unwrap_err(), GenericError("no_matching_sheets"));
   }
 
   #[test]
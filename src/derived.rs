@@ -0,0 +1,438 @@
+//! Derived columns: output fields computed from each converted row rather than read from
+//! a cell -- concatenations, arithmetic, constants, the source row number and the sheet
+//! name. Each one targets a `KeySegment`, so it can land in nested output exactly like a
+//! physical column.
+//!
+//! Expressions reference the row's header keys (`qty`, or `` `unit price` `` for keys
+//! that aren't plain identifiers), falling back to the output keys built so far, which
+//! includes earlier derived columns. `+` adds two numbers and otherwise concatenates,
+//! reading null as an empty string, so `first_name + " " + last_name` works on text
+//! while `qty * unit_price` stays numeric. `-`, `*` and `/` yield null when either side
+//! isn't a number, as does division by zero. `row_number()` is the 1-based row in the
+//! source sheet (the line number for CSV) and `sheet_name()` the worksheet name (the file
+//! stem for CSV).
+use std::fmt;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use serde_json::{json, Map, Value};
+
+use crate::error::GenericError;
+use crate::filter::read_quoted;
+use crate::helpers::{float_value, integer_value};
+use crate::key_segment::{insert_key_segment, KeySegment};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DerivedExpr {
+    Constant(Value),
+    /// A header key, or failing that an output key already in the row.
+    Field(String),
+    Binary(ArithOp, Box<DerivedExpr>, Box<DerivedExpr>),
+    Negate(Box<DerivedExpr>),
+    RowNumber,
+    SheetName,
+}
+
+/// What a derived expression can see while one row is being built.
+pub struct RowContext<'a> {
+    pub values: &'a [Value],
+    pub headers: &'a [String],
+    pub row: &'a Map<String, Value>,
+    /// 1-based row number in the source sheet.
+    pub row_number: usize,
+    pub sheet_name: &'a str,
+}
+
+impl DerivedExpr {
+    /// Parses an expression such as `qty * unit_price` or `first + " " + last`.
+    pub fn parse(expr: &str) -> Result<Self, GenericError> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.parse_sum()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(GenericError("derived_unexpected_token"));
+        }
+        Ok(parsed)
+    }
+
+    pub fn eval(&self, ctx: &RowContext) -> Value {
+        match self {
+            DerivedExpr::Constant(value) => value.clone(),
+            DerivedExpr::Field(key) => ctx
+                .headers
+                .iter()
+                .position(|h| h == key)
+                .and_then(|index| ctx.values.get(index))
+                .or_else(|| ctx.row.get(key))
+                .cloned()
+                .unwrap_or(Value::Null),
+            DerivedExpr::RowNumber => json!(ctx.row_number),
+            DerivedExpr::SheetName => json!(ctx.sheet_name),
+            DerivedExpr::Negate(inner) => arithmetic(ArithOp::Subtract, &json!(0), &inner.eval(ctx)),
+            DerivedExpr::Binary(op, left, right) => arithmetic(*op, &left.eval(ctx), &right.eval(ctx)),
+        }
+    }
+}
+
+/// Writes the expression as source text that `DerivedExpr::parse` reads back, with
+/// nested operations parenthesised.
+impl fmt::Display for DerivedExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerivedExpr::Constant(Value::String(text)) => write!(f, "\"{}\"", escape_quoted(text, '"')),
+            DerivedExpr::Constant(value) => write!(f, "{}", value),
+            DerivedExpr::Field(key) if is_plain_ident(key) => write!(f, "{}", key),
+            DerivedExpr::Field(key) => write!(f, "`{}`", escape_quoted(key, '`')),
+            DerivedExpr::RowNumber => write!(f, "row_number()"),
+            DerivedExpr::SheetName => write!(f, "sheet_name()"),
+            DerivedExpr::Negate(inner) => write!(f, "-{}", Operand(inner)),
+            DerivedExpr::Binary(op, left, right) => {
+                let symbol = match op {
+                    ArithOp::Add => '+',
+                    ArithOp::Subtract => '-',
+                    ArithOp::Multiply => '*',
+                    ArithOp::Divide => '/',
+                };
+                write!(f, "{} {} {}", Operand(left), symbol, Operand(right))
+            }
+        }
+    }
+}
+
+/// An operand of a larger expression, parenthesised when it's itself an operation.
+struct Operand<'a>(&'a DerivedExpr);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            DerivedExpr::Binary(..) => write!(f, "({})", self.0),
+            expr => write!(f, "{}", expr),
+        }
+    }
+}
+
+/// Whether a key can be written bare rather than in backticks.
+fn is_plain_ident(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && !matches!(key, "true" | "false" | "null")
+}
+
+fn escape_quoted(text: &str, quote: char) -> String {
+    text.replace('\\', "\\\\").replace(quote, &format!("\\{}", quote))
+}
+
+fn to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn arithmetic(op: ArithOp, left: &Value, right: &Value) -> Value {
+    if op == ArithOp::Add && (left.is_string() || right.is_string()) {
+        return Value::String(to_text(left) + &to_text(right));
+    }
+    if let (Some(a), Some(b)) = (left.as_i64(), right.as_i64()) {
+        let exact = match op {
+            ArithOp::Add => a.checked_add(b),
+            ArithOp::Subtract => a.checked_sub(b),
+            ArithOp::Multiply => a.checked_mul(b),
+            ArithOp::Divide => None,
+        };
+        if let Some(result) = exact {
+            return integer_value(result);
+        }
+    }
+    match (to_number(left), to_number(right)) {
+        (Some(a), Some(b)) => match op {
+            ArithOp::Add => float_value(a + b),
+            ArithOp::Subtract => float_value(a - b),
+            ArithOp::Multiply => float_value(a * b),
+            ArithOp::Divide if b != 0.0 => float_value(a / b),
+            ArithOp::Divide => Value::Null,
+        },
+        _ => Value::Null,
+    }
+}
+
+/// An output field computed per row, see the module doc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedColumn {
+    pub key: KeySegment,
+    pub expr: DerivedExpr,
+    /// The expression text this column was parsed from, if any, for display.
+    pub source: Option<Arc<str>>,
+}
+
+impl DerivedColumn {
+    pub fn new(key: KeySegment, expr: DerivedExpr) -> Self {
+        DerivedColumn { key, expr, source: None }
+    }
+
+    /// Parses `expr` into a column under the plain output key `key`.
+    pub fn from_expr(key: &str, expr: &str) -> Result<Self, GenericError> {
+        Ok(DerivedColumn {
+            key: KeySegment::Simple(Arc::from(key)),
+            expr: DerivedExpr::parse(expr)?,
+            source: Some(Arc::from(expr.trim())),
+        })
+    }
+
+    /// A constant-valued column, e.g. an import batch label.
+    pub fn constant(key: &str, value: Value) -> Self {
+        DerivedColumn::new(KeySegment::Simple(Arc::from(key)), DerivedExpr::Constant(value))
+    }
+
+    /// Parses a derived column from JSON: `"key"` is a `KeySegment` (a plain string or
+    /// the tagged object form, see `KeySegment::from_json`), plus either `"expr"` (an
+    /// expression string) or `"value"` (a constant).
+    pub fn from_json(json: &Value) -> Result<Self, GenericError> {
+        let key = json.get("key").and_then(KeySegment::from_json).ok_or(GenericError("derived_invalid_key"))?;
+        if let Some(text) = json.get("expr").and_then(|v| v.as_str()) {
+            let expr = DerivedExpr::parse(text)?;
+            return Ok(DerivedColumn { key, expr, source: Some(Arc::from(text.trim())) });
+        }
+        json.get("value")
+            .map(|value| DerivedColumn::new(key, DerivedExpr::Constant(value.clone())))
+            .ok_or(GenericError("derived_missing_expr"))
+    }
+
+    /// The JSON form read by `from_json`. Columns built from a `DerivedExpr` rather than
+    /// parsed get their expression written back out as source text.
+    pub fn to_json(&self) -> Value {
+        match (&self.source, &self.expr) {
+            (Some(text), _) => json!({"key": self.key.to_string(), "expr": text.to_string()}),
+            (None, DerivedExpr::Constant(value)) => json!({"key": self.key.to_string(), "value": value}),
+            (None, expr) => json!({"key": self.key.to_string(), "expr": expr.to_string()}),
+        }
+    }
+}
+
+impl fmt::Display for DerivedColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.source, &self.expr) {
+            (Some(text), _) => write!(f, "{} = {}", self.key, text),
+            (None, DerivedExpr::Constant(value)) => write!(f, "{} = {}", self.key, value),
+            (None, expr) => write!(f, "{} = {}", self.key, expr),
+        }
+    }
+}
+
+/// Evaluates each derived column against a converted row and inserts the results at
+/// their `KeySegment` locations, in definition order.
+pub fn insert_derived_columns(
+    row: IndexMap<String, Value>,
+    derived: &[DerivedColumn],
+    values: &[Value],
+    headers: &[String],
+    row_number: usize,
+    sheet_name: &str,
+) -> IndexMap<String, Value> {
+    if derived.is_empty() {
+        return row;
+    }
+    let mut map: Map<String, Value> = row.into_iter().collect();
+    for column in derived {
+        let ctx = RowContext { values, headers, row: &map, row_number, sheet_name };
+        let value = column.expr.eval(&ctx);
+        insert_key_segment(&mut map, &column.key, value);
+    }
+    map.into_iter().collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Value),
+    Text(String),
+    Ident(String),
+    Quoted(String),
+    Op(ArithOp),
+    LParen,
+    RParen,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, GenericError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => { tokens.push(Token::LParen); i += 1; }
+            ')' => { tokens.push(Token::RParen); i += 1; }
+            '+' => { tokens.push(Token::Op(ArithOp::Add)); i += 1; }
+            '-' => { tokens.push(Token::Op(ArithOp::Subtract)); i += 1; }
+            '*' => { tokens.push(Token::Op(ArithOp::Multiply)); i += 1; }
+            '/' => { tokens.push(Token::Op(ArithOp::Divide)); i += 1; }
+            '"' | '\'' => {
+                let (text, end) = read_quoted(&chars, i, c).map_err(|_| GenericError("derived_unterminated_string"))?;
+                tokens.push(Token::Text(text));
+                i = end;
+            }
+            '`' => {
+                let (text, end) = read_quoted(&chars, i, c).map_err(|_| GenericError("derived_unterminated_string"))?;
+                tokens.push(Token::Quoted(text));
+                i = end;
+            }
+            _ if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number: serde_json::Number = text.parse().map_err(|_| GenericError("derived_invalid_number"))?;
+                tokens.push(Token::Number(Value::Number(number)));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => return Err(GenericError("derived_unexpected_character")),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self, ops: &[ArithOp]) -> Option<ArithOp> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<DerivedExpr, GenericError> {
+        let mut expr = self.parse_product()?;
+        while let Some(op) = self.peek_op(&[ArithOp::Add, ArithOp::Subtract]) {
+            self.pos += 1;
+            expr = DerivedExpr::Binary(op, Box::new(expr), Box::new(self.parse_product()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_product(&mut self) -> Result<DerivedExpr, GenericError> {
+        let mut expr = self.parse_factor()?;
+        while let Some(op) = self.peek_op(&[ArithOp::Multiply, ArithOp::Divide]) {
+            self.pos += 1;
+            expr = DerivedExpr::Binary(op, Box::new(expr), Box::new(self.parse_factor()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_factor(&mut self) -> Result<DerivedExpr, GenericError> {
+        match self.next() {
+            Some(Token::Op(ArithOp::Subtract)) => Ok(DerivedExpr::Negate(Box::new(self.parse_factor()?))),
+            Some(Token::LParen) => {
+                let expr = self.parse_sum()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(GenericError("derived_unbalanced_parentheses")),
+                }
+            }
+            Some(Token::Number(value)) => Ok(DerivedExpr::Constant(value)),
+            Some(Token::Text(text)) => Ok(DerivedExpr::Constant(Value::String(text))),
+            Some(Token::Quoted(key)) => Ok(DerivedExpr::Field(key)),
+            Some(Token::Ident(word)) => {
+                if self.tokens.get(self.pos) == Some(&Token::LParen) {
+                    if self.tokens.get(self.pos + 1) != Some(&Token::RParen) {
+                        return Err(GenericError("derived_unknown_function"));
+                    }
+                    self.pos += 2;
+                    return match word.as_str() {
+                        "row_number" => Ok(DerivedExpr::RowNumber),
+                        "sheet_name" => Ok(DerivedExpr::SheetName),
+                        _ => Err(GenericError("derived_unknown_function")),
+                    };
+                }
+                Ok(match word.as_str() {
+                    "true" => DerivedExpr::Constant(Value::Bool(true)),
+                    "false" => DerivedExpr::Constant(Value::Bool(false)),
+                    "null" => DerivedExpr::Constant(Value::Null),
+                    _ => DerivedExpr::Field(word),
+                })
+            }
+            _ => Err(GenericError("derived_expected_operand")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_row(expr: &str, headers: &[&str], values: Value) -> Value {
+        let headers: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+        let values: Vec<Value> = serde_json::from_value(values).unwrap();
+        let column = DerivedColumn::from_expr("out", expr).unwrap();
+        let row = insert_derived_columns(IndexMap::new(), &[column], &values, &headers, 7, "Orders");
+        row["out"].clone()
+    }
+
+    #[test]
+    fn test_derived_expressions() {
+        let headers = ["first", "last", "qty", "unit price"];
+        let values = json!(["Ada", null, 3, 2.5]);
+        assert_eq!(eval_row(r#"first + " " + last"#, &headers, values.clone()), json!("Ada "));
+        assert_eq!(eval_row("qty * `unit price`", &headers, values.clone()), json!(7.5));
+        assert_eq!(eval_row("(qty + 1) * 2 - -1", &headers, values.clone()), json!(9));
+        assert_eq!(eval_row("qty / 0", &headers, values.clone()), Value::Null);
+        assert_eq!(eval_row("first * 2", &headers, values.clone()), Value::Null);
+        assert_eq!(eval_row("sheet_name() + '#' + row_number()", &headers, values), json!("Orders#7"));
+    }
+
+    #[test]
+    fn test_derived_column_from_json_targets_key_segments() {
+        let batch = DerivedColumn::from_json(&json!({"key": {"type": "object", "key": "meta", "next": "batch"}, "value": "2026-10"})).unwrap();
+        let total = DerivedColumn::from_json(&json!({"key": "total", "expr": "qty * 2"})).unwrap();
+        let row = insert_derived_columns(IndexMap::new(), &[batch, total], &[json!(4)], &["qty".to_string()], 2, "Sheet1");
+        assert_eq!(json!(row), json!({"meta": {"batch": "2026-10"}, "total": 8}));
+        assert_eq!(DerivedColumn::from_json(&json!({"key": "x", "expr": "qty *"})).unwrap_err(), GenericError("derived_expected_operand"));
+        assert_eq!(DerivedColumn::from_json(&json!({"key": "x"})).unwrap_err(), GenericError("derived_missing_expr"));
+        assert_eq!(DerivedColumn::from_json(&json!({"expr": "qty"})).unwrap_err(), GenericError("derived_invalid_key"));
+        assert_eq!(DerivedExpr::parse("'x").unwrap_err(), GenericError("derived_unterminated_string"));
+        assert_eq!(DerivedExpr::parse("now()").unwrap_err(), GenericError("derived_unknown_function"));
+    }
+
+    #[test]
+    fn test_built_expressions_serialise_as_parseable_source() {
+        let expr = DerivedExpr::parse(r#"-(qty + 1) * `unit price` / 2 + " \"x\"" + sheet_name()"#).unwrap();
+        let built = DerivedColumn::new(KeySegment::Simple(Arc::from("out")), expr.clone());
+        let text = built.to_json()["expr"].as_str().unwrap().to_string();
+        assert_eq!(text, r#"(((-(qty + 1) * `unit price`) / 2) + " \"x\"") + sheet_name()"#);
+        assert_eq!(DerivedExpr::parse(&text).unwrap(), expr);
+    }
+}
@@ -1,7 +1,8 @@
 use alphanumeric::IsNumeric;
 use fuzzy_datetime::{iso_fuzzy_to_date_string, iso_fuzzy_to_datetime_string};
+use serde_json::{json, Value};
 
-use crate::OptionSet;
+use crate::{OptionSet, Orientation};
 
 /// How many rows from the top of a sheet/file to sample when guessing the header and
 /// first-data row. Generous enough to cover realistic title/notes preambles without
@@ -19,6 +20,10 @@ const LENGTH_COMPARISON_SAMPLE: usize = 3;
 /// type-based signal (numeric/boolean/date) exists anywhere in the sample.
 const LENGTH_CONFIDENCE_RATIO: f64 = 0.7;
 
+/// A chosen header whose score is within this of another candidate's is less certain
+/// than its evidence alone suggests.
+const CONFIDENCE_MARGIN: f64 = 0.1;
+
 /// Resolved header/data row indices for a read. `header_index: None` means detection
 /// found no confident evidence a header row exists at all (see `looks_like_labels`) --
 /// callers should fall back to A1/C01-style field names (the same as `--omit-header`)
@@ -149,7 +154,26 @@ fn looks_like_labels(candidate: &[String], comparison_rows: &[&Vec<String>]) ->
 ///   exists, and `header_index` comes back `None` (see `DetectedRows`) rather than
 ///   guessing wrong and losing the candidate row as data.
 pub(crate) fn detect_header_and_data_rows(sample_rows: &[Vec<String>]) -> DetectedRows {
-  const FALLBACK: DetectedRows = DetectedRows { header_index: Some(0), data_index: 1 };
+  choose_header_and_data_rows(sample_rows).0
+}
+
+/// What settled the choice in `choose_header_and_data_rows`, strongest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Evidence {
+  /// The header labels columns every row below it leaves blank
+  WidthGap,
+  /// Rows below hold numbers, booleans or dates and the header doesn't
+  TypeSignals,
+  /// Text only, with the header confidently shorter than the rows below
+  LabelLength,
+  /// Text only, with nothing marking any row as a header
+  NoHeader,
+  /// No usable rows, or the widest row looked like data: row 0 assumed
+  Fallback,
+}
+
+fn choose_header_and_data_rows(sample_rows: &[Vec<String>]) -> (DetectedRows, Evidence) {
+  const FALLBACK: (DetectedRows, Evidence) = (DetectedRows { header_index: Some(0), data_index: 1 }, Evidence::Fallback);
   if sample_rows.is_empty() {
     return FALLBACK;
   }
@@ -196,6 +220,13 @@ pub(crate) fn detect_header_and_data_rows(sample_rows: &[Vec<String>]) -> Detect
   let width_gap_confirms_header = target_width > comparison_max_width;
 
   let any_type_signal = sample_rows.iter().any(|r| r.iter().any(|c| is_data_signal(c)));
+  let evidence = if width_gap_confirms_header {
+    Evidence::WidthGap
+  } else if any_type_signal {
+    Evidence::TypeSignals
+  } else {
+    Evidence::LabelLength
+  };
   if !any_type_signal && !width_gap_confirms_header {
     let comparison_rows: Vec<&Vec<String>> = matching_after
       .iter()
@@ -206,12 +237,182 @@ pub(crate) fn detect_header_and_data_rows(sample_rows: &[Vec<String>]) -> Detect
       // Purely textual content, and the candidate isn't confidently shorter than what
       // follows -- no reliable evidence of a real header, so the candidate itself is
       // treated as the first row of data instead of being consumed as a label row.
-      return DetectedRows { header_index: None, data_index: header_index };
+      return (DetectedRows { header_index: None, data_index: header_index }, Evidence::NoHeader);
     }
   }
 
   let data_index = matching_after.first().copied().unwrap_or(header_index + 1);
-  DetectedRows { header_index: Some(header_index), data_index }
+  (DetectedRows { header_index: Some(header_index), data_index }, evidence)
+}
+
+/// How sure header detection is of its choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DetectionConfidence {
+  /// No header found, a row-0 guess, or another row scores as well: worth confirming
+  Low,
+  /// Chosen on cell length alone, or with a close runner-up
+  Medium,
+  /// Confirmed by width or data types with no close runner-up
+  High,
+}
+
+impl DetectionConfidence {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DetectionConfidence::Low => "low",
+      DetectionConfidence::Medium => "medium",
+      DetectionConfidence::High => "high",
+    }
+  }
+
+  fn lower(self) -> Self {
+    match self {
+      DetectionConfidence::High => DetectionConfidence::Medium,
+      _ => DetectionConfidence::Low,
+    }
+  }
+}
+
+/// One row considered as the header, with the signals behind its score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderCandidate {
+  /// 0-based row index in the sample
+  pub row_index: usize,
+  /// Where data would start below this row, to pass as `data_row_index` with it
+  pub data_index: usize,
+  /// From 0 to 1, higher is more header-like
+  pub score: f64,
+  /// Populated cells
+  pub width: usize,
+  /// `width` over the widest row in the sample
+  pub width_ratio: f64,
+  /// Share of populated cells that are numbers, booleans or dates
+  pub data_signal_ratio: f64,
+  /// Average cell length over that of the rows below, `None` without rows to compare
+  pub label_length_ratio: Option<f64>,
+  /// Names of the signals that count in this row's favour: `full_width`,
+  /// `wider_than_data`, `no_data_signals` and `shorter_than_data`
+  pub signals: Vec<&'static str>,
+}
+
+impl HeaderCandidate {
+  pub fn to_json(&self) -> Value {
+    json!({
+      "row_index": self.row_index,
+      "data_index": self.data_index,
+      "score": self.score,
+      "width": self.width,
+      "width_ratio": self.width_ratio,
+      "data_signal_ratio": self.data_signal_ratio,
+      "label_length_ratio": self.label_length_ratio,
+      "signals": self.signals,
+    })
+  }
+}
+
+/// Header detection with its alternatives, for callers that want to confirm the choice
+/// rather than take it on trust. `header_index` and `data_index` are exactly what a read
+/// with `detect_header` would use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderDetection {
+  pub header_index: Option<usize>,
+  pub data_index: usize,
+  pub confidence: DetectionConfidence,
+  /// Every row with two or more populated cells, best score first
+  pub candidates: Vec<HeaderCandidate>,
+}
+
+impl HeaderDetection {
+  /// Whether to ask the user rather than take the detected rows silently.
+  pub fn needs_confirmation(&self) -> bool {
+    self.confidence == DetectionConfidence::Low
+  }
+
+  pub fn to_json(&self) -> Value {
+    json!({
+      "header_index": self.header_index,
+      "data_index": self.data_index,
+      "confidence": self.confidence.as_str(),
+      "candidates": self.candidates.iter().map(|c| c.to_json()).collect::<Vec<Value>>(),
+    })
+  }
+}
+
+/// Runs header detection over sample rows (up to `DETECT_SAMPLE_SIZE` from the top) and
+/// scores every candidate row. The choice itself is the one `detect_header_and_data_rows`
+/// makes; scores only rank the alternatives and set the confidence, which drops when
+/// another row scores within `CONFIDENCE_MARGIN` of the chosen one, or to low when one
+/// scores higher.
+pub fn detect_header_rows(sample_rows: &[Vec<String>]) -> HeaderDetection {
+  let (rows, evidence) = choose_header_and_data_rows(sample_rows);
+  let mut candidates = score_header_candidates(sample_rows);
+  candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+  let mut confidence = match evidence {
+    Evidence::WidthGap | Evidence::TypeSignals => DetectionConfidence::High,
+    Evidence::LabelLength => DetectionConfidence::Medium,
+    Evidence::NoHeader | Evidence::Fallback => DetectionConfidence::Low,
+  };
+  let chosen_score = candidates.iter().find(|c| Some(c.row_index) == rows.header_index).map(|c| c.score);
+  let best_other = candidates.iter().find(|c| Some(c.row_index) != rows.header_index).map(|c| c.score);
+  if let (Some(chosen), Some(other)) = (chosen_score, best_other) {
+    if other >= chosen {
+      confidence = DetectionConfidence::Low;
+    } else if chosen - other < CONFIDENCE_MARGIN {
+      confidence = confidence.lower();
+    }
+  }
+  HeaderDetection { header_index: rows.header_index, data_index: rows.data_index, confidence, candidates }
+}
+
+/// Scores each row with two or more populated cells as a header: 0.4 for its width
+/// against the widest row, 0.4 for having no data-like cells and up to 0.2 for being
+/// shorter than the rows below it, the same signals `choose_header_and_data_rows` relies on.
+fn score_header_candidates(sample_rows: &[Vec<String>]) -> Vec<HeaderCandidate> {
+  let widths: Vec<usize> = sample_rows.iter().map(|r| row_width(r)).collect();
+  let target_width = widths.iter().copied().max().unwrap_or(0);
+  let round = |value: f64| (value * 1000.0).round() / 1000.0;
+  let mut candidates = vec![];
+  for (row_index, &width) in widths.iter().enumerate() {
+    if width < 2 {
+      continue;
+    }
+    let row = &sample_rows[row_index];
+    let min_data_width = width.div_ceil(2).max(2);
+    let below: Vec<usize> = (row_index + 1..sample_rows.len()).filter(|&i| widths[i] >= min_data_width).collect();
+    let comparison: Vec<usize> = below.iter().copied().take(LENGTH_COMPARISON_SAMPLE).collect();
+    let signal_count = row.iter().filter(|c| is_data_signal(c)).count();
+    let data_signal_ratio = signal_count as f64 / width as f64;
+    let data_len: f64 = comparison.iter().map(|&i| avg_cell_length(&sample_rows[i])).sum::<f64>() / comparison.len().max(1) as f64;
+    let label_length_ratio = (data_len > 0.0).then(|| avg_cell_length(row) / data_len);
+    let width_ratio = width as f64 / target_width as f64;
+    // Full marks at `LENGTH_CONFIDENCE_RATIO` or below, none from parity up. A row with
+    // nothing below it to label gets none either.
+    let length_score = label_length_ratio.map_or(0.0, |ratio| ((1.0 - ratio) / (1.0 - LENGTH_CONFIDENCE_RATIO)).clamp(0.0, 1.0));
+    let mut signals = vec![];
+    if width == target_width {
+      signals.push("full_width");
+    }
+    if comparison.iter().all(|&i| widths[i] < width) && !comparison.is_empty() {
+      signals.push("wider_than_data");
+    }
+    if signal_count == 0 {
+      signals.push("no_data_signals");
+    }
+    if label_length_ratio.is_some_and(|ratio| ratio < LENGTH_CONFIDENCE_RATIO) {
+      signals.push("shorter_than_data");
+    }
+    candidates.push(HeaderCandidate {
+      row_index,
+      data_index: below.first().copied().unwrap_or(row_index + 1),
+      score: round(0.4 * width_ratio + 0.4 * (1.0 - data_signal_ratio) + 0.2 * length_score),
+      width,
+      width_ratio: round(width_ratio),
+      data_signal_ratio: round(data_signal_ratio),
+      label_length_ratio: label_length_ratio.map(round),
+      signals,
+    });
+  }
+  candidates
 }
 
 /// Resolves the effective header/data row configuration for a read. Runs auto-detection
@@ -244,6 +445,149 @@ pub(crate) fn resolve_header_and_data_rows<F: FnOnce() -> Vec<Vec<String>>>(
   DetectedRows { header_index, data_index }
 }
 
+/// Consecutive blank rows that end the data when footer detection is on and
+/// `OptionSet.footer_blank_rows` isn't set.
+pub const DEFAULT_FOOTER_BLANK_ROWS: usize = 2;
+
+/// Labels that mark a trailing row as a summary rather than data, compared ignoring case
+/// and a trailing colon.
+const SUMMARY_LABELS: [&str; 7] = ["total", "totals", "grand total", "sum", "subtotal", "sub total", "sub-total"];
+
+/// Whether a row's first populated cell labels it as a summary, e.g. "Total" or
+/// "Grand total:" -- or "Total revenue", as a total row often names what it adds up.
+fn is_summary_row(row: &[String]) -> bool {
+  let Some(label) = row.iter().map(|c| c.trim()).find(|c| !c.is_empty()) else {
+    return false;
+  };
+  let label = label.trim_end_matches(':').trim().to_lowercase();
+  SUMMARY_LABELS.contains(&label.as_str()) || label.starts_with("total ") || label.starts_with("grand total")
+}
+
+/// Best-guess end of the data (exclusive row index), mirroring
+/// `detect_header_and_data_rows` at the other end of the table. `rows` yields each row's
+/// text from `data_index` onward.
+///
+/// Data stops at the first run of `blank_run` blank rows, as anything below is usually
+/// notes or a separate table. Trailing rows are then dropped while they are blank, too
+/// narrow to be a table row (under half the widest row, the same threshold data rows are
+/// held to below a header), or labelled as a summary like "Total" -- covering total
+/// rows, blank padding and "Generated by..." notes. Only the last `DETECT_SAMPLE_SIZE`
+/// rows are kept for that, so the whole table is never held as text.
+pub(crate) fn detect_data_end<I: Iterator<Item = Vec<String>>>(rows: I, data_index: usize, blank_run: usize) -> usize {
+  let blank_run = blank_run.max(1);
+  let mut tail: std::collections::VecDeque<(usize, Vec<String>)> = std::collections::VecDeque::with_capacity(DETECT_SAMPLE_SIZE);
+  let mut table_width = 0;
+  let mut blanks = 0;
+  let mut end = data_index;
+  for (index, row) in rows.enumerate().map(|(i, row)| (i + data_index, row)) {
+    let width = row_width(&row);
+    if width == 0 {
+      blanks += 1;
+      if blanks >= blank_run {
+        break;
+      }
+    } else {
+      blanks = 0;
+      table_width = table_width.max(width);
+    }
+    end = index + 1;
+    if tail.len() == DETECT_SAMPLE_SIZE {
+      tail.pop_front();
+    }
+    tail.push_back((index, row));
+  }
+  let min_width = if table_width >= 3 { table_width.div_ceil(2) } else { 1 };
+  while let Some((index, row)) = tail.back() {
+    let width = row_width(row);
+    if width > 0 && width >= min_width && !is_summary_row(row) {
+      break;
+    }
+    end = *index;
+    tail.pop_back();
+  }
+  end
+}
+
+/// Resolves where data capture stops (exclusive row index, relative like the header and
+/// data row indices): an explicit `data_end_row`, else the detected end when
+/// `detect_footer` is on, else `total`, then less `skip_footer` rows. `None` when no
+/// footer option is set, so reads stay unbounded as before. `detect` is only called when
+/// detection actually runs.
+pub(crate) fn resolve_data_end<F: FnOnce() -> usize>(opts: &OptionSet, data_index: usize, total: usize, detect: F) -> Option<usize> {
+  if !opts.handles_footer() {
+    return None;
+  }
+  let end = match opts.data_end_row {
+    Some(last) => total.min(last + 1),
+    None if opts.detect_footer => detect().min(total),
+    None => total,
+  };
+  Some(end.saturating_sub(opts.skip_footer).max(data_index))
+}
+
+/// Broad type of a populated cell, for telling which way a table's records run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+  Text,
+  Boolean,
+  Number,
+  Date,
+}
+
+fn cell_kind(cell: &str) -> Option<CellKind> {
+  let trimmed = cell.trim();
+  if trimmed.is_empty() {
+    None
+  } else if is_boolean_like(trimmed) {
+    Some(CellKind::Boolean)
+  } else if trimmed.is_numeric() {
+    Some(CellKind::Number)
+  } else if is_date_like(trimmed) {
+    Some(CellKind::Date)
+  } else {
+    Some(CellKind::Text)
+  }
+}
+
+/// Whether a sample looks like a transposed table, with field labels down the first
+/// column and one record per column. Each field holds one type of value, so in a normal
+/// table the cells below the header share a type down each column, while in a
+/// transposed one the cells right of the labels share a type along each row. The
+/// sample counts as transposed when its first column is all text and more of its rows
+/// than its columns are of one type. Purely textual tables never are.
+pub(crate) fn detect_column_records(rows: &[Vec<String>]) -> bool {
+  let rows: Vec<&Vec<String>> = rows.iter().filter(|row| row_width(row) > 0).collect();
+  let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+  if rows.len() < 2 || width < 3 {
+    return false;
+  }
+  if rows.iter().any(|row| cell_kind(&row[0]) != Some(CellKind::Text)) {
+    return false;
+  }
+  let kind = |row: usize, col: usize| rows[row].get(col).and_then(|c| cell_kind(c));
+  // Share of lines with two or more populated cells whose cells are all of one kind
+  let uniform_share = |lines: Vec<Vec<Option<CellKind>>>| {
+    let lines: Vec<Vec<CellKind>> = lines.into_iter().map(|line| line.into_iter().flatten().collect::<Vec<_>>()).filter(|line| line.len() >= 2).collect();
+    if lines.is_empty() {
+      return 0.0;
+    }
+    lines.iter().filter(|line| line.iter().all(|k| *k == line[0])).count() as f64 / lines.len() as f64
+  };
+  let along_rows = uniform_share((0..rows.len()).map(|r| (1..width).map(|c| kind(r, c)).collect()).collect());
+  let down_columns = uniform_share((1..width).map(|c| (1..rows.len()).map(|r| kind(r, c)).collect()).collect());
+  let typed = rows.iter().any(|row| row.iter().skip(1).any(|c| cell_kind(c).is_some_and(|k| k != CellKind::Text)));
+  typed && along_rows > down_columns
+}
+
+/// Whether to transpose the range being read, sampling it only for `Orientation::Detect`.
+pub(crate) fn resolve_orientation<F: FnOnce() -> Vec<Vec<String>>>(opts: &OptionSet, sample: F) -> bool {
+  match opts.orientation {
+    Orientation::Rows => false,
+    Orientation::Columns => true,
+    Orientation::Detect => detect_column_records(&sample()),
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -394,4 +738,79 @@ mod tests {
     assert_eq!(result.header_index, None);
     assert_eq!(result.data_index, 0);
   }
+
+  #[test]
+  fn test_header_detection_scores_candidates_and_rates_confidence() {
+    let sample = vec![
+      row(&["Sales 2025", "", ""]),
+      row(&["region", "revenue", "updated"]),
+      row(&["north", "1200", "2025-03-01"]),
+      row(&["south", "950", "2025-03-02"]),
+    ];
+    let detection = detect_header_rows(&sample);
+    assert_eq!((detection.header_index, detection.data_index), (Some(1), 2));
+    assert_eq!(detection.confidence, DetectionConfidence::High);
+    let best = &detection.candidates[0];
+    assert_eq!(best.row_index, 1);
+    assert_eq!(best.signals, vec!["full_width", "no_data_signals"]);
+    assert!(!detection.needs_confirmation());
+
+    // Text only, with a header no shorter than the rows below: nothing to go on
+    let sample = vec![
+      row(&["greeting", "farewell"]),
+      row(&["hello", "goodbye"]),
+      row(&["welcome", "see you"]),
+    ];
+    let detection = detect_header_rows(&sample);
+    assert_eq!(detection.header_index, None);
+    assert!(detection.needs_confirmation());
+    assert_eq!(detection.candidates[0].row_index, 1);
+
+    let sample = vec![
+      row(&["key", "english"]),
+      row(&["intro_title", "Welcome to the annual report"]),
+      row(&["intro_body", "This year brought steady growth"]),
+    ];
+    let detection = detect_header_rows(&sample);
+    assert_eq!((detection.header_index, detection.confidence), (Some(0), DetectionConfidence::Medium));
+    assert_eq!(detection.candidates[0].signals, vec!["full_width", "no_data_signals", "shorter_than_data"]);
+    assert_eq!(detection.candidates[0].label_length_ratio, Some(0.25));
+    assert_eq!(detection.to_json()["confidence"], json!("medium"));
+  }
+
+  #[test]
+  fn test_detects_records_running_down_columns() {
+    let transposed = vec![
+      row(&["name", "Acme", "Brook"]),
+      row(&["price", "12", "9.5"]),
+      row(&["joined", "2025-01-01", "2025-02-01"]),
+    ];
+    assert!(detect_column_records(&transposed));
+    let normal = vec![
+      row(&["name", "price", "joined"]),
+      row(&["Acme", "12", "2025-01-01"]),
+      row(&["Brook", "9.5", "2025-02-01"]),
+    ];
+    assert!(!detect_column_records(&normal));
+    // text only, so nothing to tell the layouts apart
+    assert!(!detect_column_records(&[row(&["name", "Acme", "Brook"]), row(&["city", "Leeds", "Bath"])]));
+  }
+
+  #[test]
+  fn test_data_end_skips_total_rows_blank_padding_and_notes() {
+    let rows = vec![
+      row(&["north", "12", "4"]),
+      row(&["south", "", "3"]),
+      row(&["Total", "12", "7"]),
+      row(&["", "", ""]),
+      row(&["Generated by Ledger 4.2", "", ""]),
+    ];
+    assert_eq!(detect_data_end(rows.clone().into_iter(), 1, 2), 3);
+    // a blank run ends the data before anything below it is considered
+    let mut stacked = rows[..2].to_vec();
+    stacked.extend([row(&["", "", ""]), row(&["", "", ""]), row(&["east", "9", "1"])]);
+    assert_eq!(detect_data_end(stacked.into_iter(), 0, 2), 2);
+    assert!(is_summary_row(&row(&["", "Grand Total:", "19"])));
+    assert!(!is_summary_row(&row(&["Totally Fresh Ltd", "5"])));
+  }
 }
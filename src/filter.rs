@@ -0,0 +1,469 @@
+//! Row filters, evaluated on each converted row before it is captured or passed to a
+//! `SaveRowFn`, so a large sheet can be narrowed down while it is read rather than after.
+//!
+//! A filter is either a Rust closure or a small expression over the row's output keys:
+//!
+//! ```text
+//! qty > 0 && region in ["west", "east"]
+//! status == "active" || !archived
+//! totals.amount >= 1000.5 && sku not in ["X1", "X2"]
+//! ```
+//!
+//! Fields are output keys, with `.` reaching into nested objects (see `KeySegment`) and
+//! backticks quoting keys that aren't plain identifiers (`` `unit price` > 2 ``). Literals
+//! are double- or single-quoted strings, numbers, `true`, `false` and `null`. Comparison
+//! operators are `==`, `!=`, `>`, `>=`, `<`, `<=`, `in` and `not in`, combined with `&&`,
+//! `||`, `!` and parentheses. A field on its own tests whether it's set: not null, false,
+//! zero or an empty string. Numbers compare numerically and strings lexically (which
+//! orders ISO dates correctly); ordering between a number and a string is always false.
+use std::fmt;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::error::GenericError;
+
+/// Closure form of a row filter: keep the row when it returns `true`.
+pub type RowPredicate = Arc<dyn Fn(&IndexMap<String, Value>) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub enum RowFilter {
+    /// The source text, kept for display and JSON output, and its parsed form.
+    Expression(Arc<str>, Arc<FilterExpr>),
+    Closure(RowPredicate),
+}
+
+impl RowFilter {
+    /// Parses a filter expression, e.g. `qty > 0 && region in ["west","east"]`.
+    pub fn parse(expr: &str) -> Result<Self, GenericError> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(GenericError("filter_unexpected_token"));
+        }
+        Ok(RowFilter::Expression(Arc::from(expr.trim()), Arc::new(parsed)))
+    }
+
+    pub fn from_fn<F>(predicate: F) -> Self
+    where
+        F: Fn(&IndexMap<String, Value>) -> bool + Send + Sync + 'static,
+    {
+        RowFilter::Closure(Arc::new(predicate))
+    }
+
+    /// Whether the converted row should be kept.
+    pub fn matches(&self, row: &IndexMap<String, Value>) -> bool {
+        match self {
+            RowFilter::Expression(_, expr) => expr.eval(row),
+            RowFilter::Closure(predicate) => predicate(row),
+        }
+    }
+
+    /// The source expression, if this filter was parsed from one.
+    pub fn expression(&self) -> Option<&str> {
+        match self {
+            RowFilter::Expression(text, _) => Some(text),
+            RowFilter::Closure(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for RowFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowFilter::Expression(text, _) => write!(f, "{}", text),
+            RowFilter::Closure(_) => write!(f, "<closure>"),
+        }
+    }
+}
+
+impl fmt::Debug for RowFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowFilter::Expression(text, _) => f.debug_tuple("Expression").field(text).finish(),
+            RowFilter::Closure(_) => f.write_str("Closure"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// Path of output keys, one entry per nesting level.
+    Field(Vec<String>),
+    Literal(Value),
+}
+
+impl Operand {
+    fn resolve<'a>(&'a self, row: &'a IndexMap<String, Value>) -> Option<&'a Value> {
+        match self {
+            Operand::Literal(value) => Some(value),
+            Operand::Field(path) => {
+                let (first, rest) = path.split_first()?;
+                let mut current = row.get(first)?;
+                for key in rest {
+                    current = current.as_object()?.get(key)?;
+                }
+                Some(current)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Compare(Operand, CompareOp, Operand),
+    /// `field in [...]`, or `field not in [...]` when the flag is set.
+    In(Operand, Vec<Value>, bool),
+    /// A bare operand, tested for being set.
+    Truthy(Operand),
+}
+
+impl FilterExpr {
+    pub fn eval(&self, row: &IndexMap<String, Value>) -> bool {
+        match self {
+            FilterExpr::And(a, b) => a.eval(row) && b.eval(row),
+            FilterExpr::Or(a, b) => a.eval(row) || b.eval(row),
+            FilterExpr::Not(inner) => !inner.eval(row),
+            FilterExpr::Compare(left, op, right) => {
+                let null = Value::Null;
+                let l = left.resolve(row).unwrap_or(&null);
+                let r = right.resolve(row).unwrap_or(&null);
+                compare(l, *op, r)
+            }
+            FilterExpr::In(operand, values, negated) => {
+                let null = Value::Null;
+                let value = operand.resolve(row).unwrap_or(&null);
+                values.iter().any(|v| values_equal(value, v)) != *negated
+            }
+            FilterExpr::Truthy(operand) => operand.resolve(row).is_some_and(is_set),
+        }
+    }
+}
+
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn compare(left: &Value, op: CompareOp, right: &Value) -> bool {
+    let ordering = match (left, right) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64().zip(y.as_f64()).and_then(|(x, y)| x.partial_cmp(&y)),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        _ => None,
+    };
+    match op {
+        CompareOp::Eq => values_equal(left, right),
+        CompareOp::Ne => !values_equal(left, right),
+        CompareOp::Gt => ordering.is_some_and(|o| o.is_gt()),
+        CompareOp::Gte => ordering.is_some_and(|o| o.is_ge()),
+        CompareOp::Lt => ordering.is_some_and(|o| o.is_lt()),
+        CompareOp::Lte => ordering.is_some_and(|o| o.is_le()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Field(Vec<String>),
+    Literal(Value),
+    Op(CompareOp),
+    In,
+    NotIn,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, GenericError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => { tokens.push(Token::LParen); i += 1; }
+            ')' => { tokens.push(Token::RParen); i += 1; }
+            '[' => { tokens.push(Token::LBracket); i += 1; }
+            ']' => { tokens.push(Token::RBracket); i += 1; }
+            ',' => { tokens.push(Token::Comma); i += 1; }
+            '&' if next == Some('&') => { tokens.push(Token::And); i += 2; }
+            '|' if next == Some('|') => { tokens.push(Token::Or); i += 2; }
+            '=' if next == Some('=') => { tokens.push(Token::Op(CompareOp::Eq)); i += 2; }
+            '!' if next == Some('=') => { tokens.push(Token::Op(CompareOp::Ne)); i += 2; }
+            '!' => { tokens.push(Token::Not); i += 1; }
+            '>' if next == Some('=') => { tokens.push(Token::Op(CompareOp::Gte)); i += 2; }
+            '>' => { tokens.push(Token::Op(CompareOp::Gt)); i += 1; }
+            '<' if next == Some('=') => { tokens.push(Token::Op(CompareOp::Lte)); i += 2; }
+            '<' => { tokens.push(Token::Op(CompareOp::Lt)); i += 1; }
+            '"' | '\'' => {
+                let (text, end) = read_quoted(&chars, i, c)?;
+                tokens.push(Token::Literal(Value::String(text)));
+                i = end;
+            }
+            '`' => {
+                let mut path = Vec::new();
+                loop {
+                    let (text, end) = read_quoted(&chars, i, '`')?;
+                    path.push(text);
+                    i = end;
+                    if chars.get(i) == Some(&'.') && chars.get(i + 1) == Some(&'`') {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Field(path));
+            }
+            _ if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit() || n == '.')) || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == 'e' || chars[i] == 'E'
+                    || ((chars[i] == '-' || chars[i] == '+') && matches!(chars[i - 1], 'e' | 'E'))) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number: serde_json::Number = text.parse().map_err(|_| GenericError("filter_invalid_number"))?;
+                tokens.push(Token::Literal(Value::Number(number)));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.as_str() {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    "in" => Token::In,
+                    "not" if tokens_follow_with_in(&chars, i) => {
+                        i = skip_in_keyword(&chars, i);
+                        Token::NotIn
+                    }
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Field(word.split('.').map(|s| s.to_string()).collect()),
+                };
+                tokens.push(token);
+            }
+            _ => return Err(GenericError("filter_unexpected_character")),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads a quoted run starting at `start` (the opening quote), with backslash escapes,
+/// returning the unescaped text and the index just past the closing quote.
+pub(crate) fn read_quoted(chars: &[char], start: usize, quote: char) -> Result<(String, usize), GenericError> {
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            c if c == quote => return Ok((text, i + 1)),
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err(GenericError("filter_unterminated_string"))
+}
+
+fn tokens_follow_with_in(chars: &[char], from: usize) -> bool {
+    let rest: String = chars[from..].iter().collect();
+    let rest = rest.trim_start();
+    rest.starts_with("in") && !rest[2..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+fn skip_in_keyword(chars: &[char], mut i: usize) -> usize {
+    while chars[i].is_whitespace() {
+        i += 1;
+    }
+    i + 2
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, GenericError> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = FilterExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, GenericError> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = FilterExpr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, GenericError> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(FilterExpr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(GenericError("filter_unbalanced_parentheses")),
+                }
+            }
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, GenericError> {
+        match self.next() {
+            Some(Token::Field(path)) => Ok(Operand::Field(path)),
+            Some(Token::Literal(value)) => Ok(Operand::Literal(value)),
+            _ => Err(GenericError("filter_expected_operand")),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<FilterExpr, GenericError> {
+        let left = self.parse_operand()?;
+        match self.peek().cloned() {
+            Some(Token::Op(op)) => {
+                self.pos += 1;
+                let right = self.parse_operand()?;
+                Ok(FilterExpr::Compare(left, op, right))
+            }
+            Some(token @ (Token::In | Token::NotIn)) => {
+                self.pos += 1;
+                let values = self.parse_list()?;
+                Ok(FilterExpr::In(left, values, token == Token::NotIn))
+            }
+            _ => Ok(FilterExpr::Truthy(left)),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<Value>, GenericError> {
+        if self.next() != Some(Token::LBracket) {
+            return Err(GenericError("filter_expected_list"));
+        }
+        let mut values = Vec::new();
+        if self.peek() == Some(&Token::RBracket) {
+            self.pos += 1;
+            return Ok(values);
+        }
+        loop {
+            match self.next() {
+                Some(Token::Literal(value)) => values.push(value),
+                _ => return Err(GenericError("filter_expected_literal")),
+            }
+            match self.next() {
+                Some(Token::Comma) => {}
+                Some(Token::RBracket) => return Ok(values),
+                _ => return Err(GenericError("filter_expected_list")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> IndexMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_filter_expression_comparisons_and_lists() {
+        let filter = RowFilter::parse(r#"qty > 0 && region in ["west","east"]"#).unwrap();
+        assert!(filter.matches(&row(json!({"qty": 3, "region": "west"}))));
+        assert!(!filter.matches(&row(json!({"qty": 0, "region": "west"}))));
+        assert!(!filter.matches(&row(json!({"qty": 2.5, "region": "north"}))));
+        assert!(!filter.matches(&row(json!({"region": "east"}))));
+
+        let filter = RowFilter::parse("status == 'active' || (score >= 9.5 and not archived)").unwrap();
+        assert!(filter.matches(&row(json!({"status": "active", "score": 1}))));
+        assert!(filter.matches(&row(json!({"status": "closed", "score": 9.5, "archived": false}))));
+        assert!(!filter.matches(&row(json!({"status": "closed", "score": 10, "archived": true}))));
+
+        let filter = RowFilter::parse("code not in [1, 2] && added < \"2024-01-01\"").unwrap();
+        assert!(filter.matches(&row(json!({"code": 3, "added": "2023-06-15"}))));
+        assert!(!filter.matches(&row(json!({"code": 2.0, "added": "2023-06-15"}))));
+        assert!(!filter.matches(&row(json!({"code": 3, "added": "2024-02-01"}))));
+    }
+
+    #[test]
+    fn test_filter_expression_nested_and_quoted_fields() {
+        let filter = RowFilter::parse("totals.amount >= -1.5e2 && `unit price` != null").unwrap();
+        assert!(filter.matches(&row(json!({"totals": {"amount": -100}, "unit price": 2}))));
+        assert!(!filter.matches(&row(json!({"totals": {"amount": -200}, "unit price": 2}))));
+        assert!(!filter.matches(&row(json!({"totals": {"amount": 5}, "unit price": null}))));
+    }
+
+    #[test]
+    fn test_filter_expression_errors() {
+        assert_eq!(RowFilter::parse("qty >").unwrap_err(), GenericError("filter_expected_operand"));
+        assert_eq!(RowFilter::parse("(qty > 1").unwrap_err(), GenericError("filter_unbalanced_parentheses"));
+        assert_eq!(RowFilter::parse("region in \"west\"").unwrap_err(), GenericError("filter_expected_list"));
+        assert_eq!(RowFilter::parse("name == 'x").unwrap_err(), GenericError("filter_unterminated_string"));
+        assert_eq!(RowFilter::parse("qty > 1 2").unwrap_err(), GenericError("filter_unexpected_token"));
+    }
+}
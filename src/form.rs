@@ -0,0 +1,276 @@
+//! Key/value "form" sheets, where each value sits beside or below its label rather than
+//! in a table column. Labels are found by pattern, or automatically as cells ending in
+//! `:`, and each value is taken from the cell at a fixed offset from its label. The
+//! values make one object, read alongside any table on the same sheet.
+use heck::ToSnakeCase;
+use serde_json::{json, Value};
+
+use crate::error::GenericError;
+use crate::sheets::SheetPattern;
+use crate::Format;
+
+/// The value offset used when neither the field nor the form sets one: the next cell to
+/// the right.
+pub const DEFAULT_FORM_OFFSET: (usize, usize) = (0, 1);
+
+/// A labelled value to extract.
+#[derive(Debug, Clone)]
+pub struct FormField {
+    /// Matched against each cell's text without a trailing `:`. Names compare in
+    /// snake_case, so `Customer name` matches `Customer Name:`.
+    pub label: SheetPattern,
+    /// Output key, otherwise the label in snake_case
+    pub key: Option<String>,
+    /// Rows down and columns right of the label, otherwise the form's offset
+    pub offset: Option<(usize, usize)>,
+    pub format: Format,
+}
+
+impl FormField {
+    /// Reads the label like `SheetPattern::parse`: `/.../` as a regex, text with `*` or
+    /// `?` as a glob, anything else as a name.
+    pub fn new(label: &str) -> Result<Self, GenericError> {
+        Ok(FormField { label: SheetPattern::parse(label)?, key: None, offset: None, format: Format::Auto })
+    }
+
+    pub fn key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    pub fn offset(mut self, rows: usize, cols: usize) -> Self {
+        self.offset = Some((rows, cols));
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The output key, from the matched label text when there is one.
+    fn output_key(&self, label: Option<&str>) -> String {
+        match (&self.key, label) {
+            (Some(key), _) => key.clone(),
+            (None, Some(label)) => label.to_snake_case(),
+            (None, None) => self.label.to_string().to_snake_case(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "label": self.label.to_string(),
+            "key": self.key,
+            "offset": self.offset,
+            "format": self.format.to_string(),
+        })
+    }
+}
+
+/// Which labels to look for and where their values are.
+#[derive(Debug, Clone)]
+pub struct FormExtraction {
+    pub fields: Vec<FormField>,
+    /// Also extracts every other cell ending in `:`, keyed by its label in snake_case
+    pub auto_labels: bool,
+    /// Rows down and columns right of each label
+    pub offset: (usize, usize),
+}
+
+impl Default for FormExtraction {
+    fn default() -> Self {
+        FormExtraction { fields: vec![], auto_labels: false, offset: DEFAULT_FORM_OFFSET }
+    }
+}
+
+impl FormExtraction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fields for a list of label patterns, each read as in `FormField::new`.
+    pub fn from_labels(labels: &[&str]) -> Result<Self, GenericError> {
+        let fields = labels.iter().map(|label| FormField::new(label)).collect::<Result<Vec<_>, _>>()?;
+        Ok(FormExtraction { fields, ..Self::default() })
+    }
+
+    pub fn field(mut self, field: FormField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    pub fn auto_labels(mut self) -> Self {
+        self.auto_labels = true;
+        self
+    }
+
+    pub fn offset(mut self, rows: usize, cols: usize) -> Self {
+        self.offset = (rows, cols);
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "fields": self.fields.iter().map(|f| f.to_json()).collect::<Vec<Value>>(),
+            "auto_labels": self.auto_labels,
+            "offset": self.offset,
+        })
+    }
+}
+
+/// Where one form value was found.
+#[derive(Debug, Clone)]
+pub struct FormValue {
+    pub key: String,
+    /// 0-based (row, column) of the label, `None` for a field whose label wasn't found
+    pub label_cell: Option<(usize, usize)>,
+    /// 0-based (row, column) of the value, `None` when the label wasn't found
+    pub value_cell: Option<(usize, usize)>,
+    pub format: Format,
+}
+
+/// Finds each field's label in a sheet's cell text, scanning row by row, and the cell
+/// its value sits in. Only the first match of each field counts, and a cell serves one
+/// field at most. Fields come first in the order given, with a `None` value cell where
+/// the label is missing so every field always has a key. Automatic labels follow in
+/// sheet order, skipping keys already taken.
+pub fn locate_form_values(rows: &[Vec<String>], form: &FormExtraction) -> Vec<FormValue> {
+    let mut locator = FormLocator::new(form);
+    for row in rows {
+        locator.scan_row(row);
+    }
+    locator.finish()
+}
+
+/// Locates form values one row at a time, as for `locate_form_values`, so a reader can
+/// stop as soon as no later row can change the result.
+#[derive(Debug)]
+pub struct FormLocator<'f> {
+    form: &'f FormExtraction,
+    found: Vec<Option<FormValue>>,
+    auto: Vec<FormValue>,
+    rows_scanned: usize,
+}
+
+impl<'f> FormLocator<'f> {
+    pub fn new(form: &'f FormExtraction) -> Self {
+        FormLocator { form, found: vec![None; form.fields.len()], auto: vec![], rows_scanned: 0 }
+    }
+
+    /// Scans the next row's cells.
+    pub fn scan_row(&mut self, row: &[String]) {
+        let row_index = self.rows_scanned;
+        self.rows_scanned += 1;
+        let form = self.form;
+        for (col_index, cell) in row.iter().enumerate() {
+            let text = cell.trim();
+            let label = text.trim_end_matches(':').trim();
+            if label.is_empty() {
+                continue;
+            }
+            let at = |offset: (usize, usize)| Some((row_index + offset.0, col_index + offset.1));
+            let field_index = form
+                .fields
+                .iter()
+                .enumerate()
+                .position(|(index, field)| self.found[index].is_none() && field.label.matches(label));
+            if let Some(index) = field_index {
+                let field = &form.fields[index];
+                self.found[index] = Some(FormValue {
+                    key: field.output_key(Some(label)),
+                    label_cell: Some((row_index, col_index)),
+                    value_cell: at(field.offset.unwrap_or(form.offset)),
+                    format: field.format.clone(),
+                });
+            } else if form.auto_labels && text.ends_with(':') {
+                self.auto.push(FormValue {
+                    key: label.to_snake_case(),
+                    label_cell: Some((row_index, col_index)),
+                    value_cell: at(form.offset),
+                    format: Format::Auto,
+                });
+            }
+        }
+    }
+
+    /// Whether every field's label and value row have been scanned. Never true with
+    /// automatic labels, which may turn up in any row.
+    pub fn is_complete(&self) -> bool {
+        !self.form.auto_labels
+            && self.found.iter().all(|value| {
+                value.as_ref().and_then(|v| v.value_cell).is_some_and(|(row, _)| row < self.rows_scanned)
+            })
+    }
+
+    pub fn finish(self) -> Vec<FormValue> {
+        let mut values: Vec<FormValue> = self
+            .found
+            .into_iter()
+            .zip(&self.form.fields)
+            .map(|(value, field)| {
+                value.unwrap_or_else(|| FormValue {
+                    key: field.output_key(None),
+                    label_cell: None,
+                    value_cell: None,
+                    format: field.format.clone(),
+                })
+            })
+            .collect();
+        for value in self.auto {
+            if !values.iter().any(|v| v.key == value.key) {
+                values.push(value);
+            }
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_locates_labelled_values_by_pattern_and_colon() {
+        let rows = grid(&[
+            &["Intake form", "", "", ""],
+            &["Customer Name:", "Ada Byron", "Ref:", "A-17"],
+            &["Order date", "", "Notes:", ""],
+            &["", "2026-03-04", "", ""],
+        ]);
+        let form = FormExtraction::new()
+            .field(FormField::new("customer name").unwrap())
+            .field(FormField::new("order*").unwrap().key("ordered").offset(1, 1))
+            .field(FormField::new("/^Phone/").unwrap().key("phone"))
+            .auto_labels();
+        let values = locate_form_values(&rows, &form);
+        let summary: Vec<(&str, Option<(usize, usize)>)> = values.iter().map(|v| (v.key.as_str(), v.value_cell)).collect();
+        assert_eq!(summary, vec![
+            ("customer_name", Some((1, 1))),
+            ("ordered", Some((3, 1))),
+            ("phone", None),
+            ("ref", Some((1, 3))),
+            ("notes", Some((2, 3))),
+        ]);
+        assert!(FormExtraction::from_labels(&["/[/"]).is_err());
+    }
+
+    #[test]
+    fn test_locator_completes_once_every_value_row_is_scanned() {
+        let rows = grid(&[&["Customer:", "Ada"], &["Delivery:", ""], &["", "Friday"], &["Ref:", "A-17"]]);
+        let form = FormExtraction::new()
+            .field(FormField::new("customer").unwrap())
+            .field(FormField::new("delivery").unwrap().offset(1, 1));
+        let mut locator = FormLocator::new(&form);
+        let scanned = rows.iter().take_while(|row| {
+            locator.scan_row(row);
+            !locator.is_complete()
+        });
+        assert_eq!(scanned.count(), 2);
+        assert!(locator.is_complete());
+        assert!(!FormLocator::new(&form.clone().auto_labels()).is_complete());
+    }
+}
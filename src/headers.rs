@@ -180,6 +180,21 @@ pub fn resolve_columns(columns: &[Column], natural_keys: &[String]) -> Vec<Colum
     resolved
 }
 
+/// The output key each column override applies to, for writers that only see converted
+/// rows: its `key`, else the natural key its `source_key` matches, else the key at its
+/// position in `row_keys`. Aligned by index with `columns`, and empty where none applies.
+pub fn column_output_keys(columns: &[Column], row_keys: &[String]) -> Vec<String> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(index, col)| match (&col.key, &col.source_key) {
+            (Some(_), _) => col.key_name(),
+            (None, Some(source_key)) => source_key.to_snake_case(),
+            (None, None) => row_keys.get(index).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
 /// Assign keys with A1+ notation
 pub fn build_a1_headers(first_row: &[String]) -> Vec<String> {
     build_header_keys(first_row, &[], &FieldNameMode::A1)
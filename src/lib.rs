@@ -8,12 +8,44 @@ pub mod helpers;
 pub mod round_decimal;
 pub mod error;
 pub mod key_segment;
+pub mod sql;
+pub mod profile;
+pub mod schema;
+pub mod filter;
+pub mod derived;
+pub mod reshape;
+pub mod aggregate;
+pub mod sort;
+pub mod lookup;
+pub mod sheets;
+pub mod workbook;
+pub mod cell_range;
+pub mod tables;
+pub mod segment;
+pub mod form;
+#[cfg(feature = "arrow")]
+pub mod arrow_output;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_output;
 
 // make tokio available to implementers if not imported directly
 pub use options::*;
 pub use reader::*;
 pub use data_set::*;
 pub use key_segment::*;
+pub use filter::RowFilter;
+pub use derived::DerivedColumn;
+pub use reshape::{GroupRows, Pivot, PivotAggregate, Unpivot};
+pub use aggregate::{AggregateOp, Aggregation, Metric};
+pub use sort::{Collation, Dedup, DedupKeep, SortKey, SortSpec};
+pub use lookup::{JoinKind, Lookup, LookupSource};
+pub use sheets::{MatchedSheets, SheetFilter, SheetPattern};
+pub use workbook::{DefinedName, DocumentProperties, SheetInfo};
+pub use cell_range::CellRange;
+pub use tables::{RangeSource, TableInfo};
+pub use segment::{DetectedTable, TableSegmentation};
+pub use form::{FormExtraction, FormField};
+pub use detect::{detect_header_rows, DetectionConfidence, HeaderCandidate, HeaderDetection};
 
 // re-export these crates
 pub use tokio;
@@ -0,0 +1,352 @@
+//! Cross-sheet lookups and joins: a key column in the main sheet is resolved against a
+//! key column in another sheet of the same workbook or in another file, e.g.
+//! `orders.product_code` against `products.code`.
+//!
+//! Each lookup table is read in full and indexed by key before the main sheet is read.
+//! Every converted row then embeds the selected fields of its match, or the whole
+//! matched row, either merged into the row or nested under a `KeySegment`. Keys are
+//! compared as text, so a numeric `101` matches the string `"101"`. A left join keeps rows
+//! without a match; an inner join drops them. Either way their keys are reported in
+//! `ResultSet.unmatched_keys`.
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+use serde_json::{json, Map, Value};
+
+use crate::error::GenericError;
+use crate::key_segment::{insert_key_segment, KeySegment};
+use crate::{read_csv_core, read_workbook_core, OptionSet, PathData, ReadMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinKind {
+    /// Keep rows without a match.
+    #[default]
+    Left,
+    /// Drop rows without a match.
+    Inner,
+}
+
+#[derive(Debug, Clone)]
+pub enum LookupSource {
+    /// A sheet of the workbook being read, by name.
+    Sheet(String),
+    /// Another file, read with its own options (sheet, header row, column overrides).
+    File(Box<OptionSet>),
+}
+
+/// The indexed rows of a loaded lookup table, plus the unmatched keys seen so far.
+#[derive(Debug, Default)]
+pub struct LookupTable {
+    rows: HashMap<String, IndexMap<String, Value>>,
+    unmatched: Mutex<IndexMap<String, Value>>,
+}
+
+impl LookupTable {
+    /// Indexes rows by `foreign_key`. The first row wins when a key repeats.
+    pub fn new(rows: Vec<IndexMap<String, Value>>, foreign_key: &str) -> Self {
+        let mut index = HashMap::new();
+        for row in rows {
+            if let Some(key) = row.get(foreign_key).and_then(lookup_key) {
+                index.entry(key).or_insert(row);
+            }
+        }
+        LookupTable { rows: index, unmatched: Mutex::new(IndexMap::new()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// Text form of a key value, or `None` for nulls and blank strings. Whole floats read as
+/// integers, so `101.0` from a spreadsheet cell matches `101` from a CSV.
+fn lookup_key(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() && f.fract() == 0.0 && f.abs() < 1e15 => Some(format!("{}", f as i64)),
+            _ => Some(n.to_string()),
+        },
+        other => Some(other.to_string()),
+    }
+}
+
+/// One lookup stage.
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    pub source: LookupSource,
+    /// Output key of the main row holding the value to look up.
+    pub local_key: String,
+    /// Output key of the lookup rows to match against.
+    pub foreign_key: String,
+    /// Fields of the matched row to embed. Empty embeds the whole row.
+    pub fields: Vec<String>,
+    /// Where the matched object goes. `None` merges its fields into the row, keeping any
+    /// the row already has.
+    pub target: Option<KeySegment>,
+    pub join: JoinKind,
+    /// Set by `load_lookups` before the main sheet is read.
+    pub table: Option<Arc<LookupTable>>,
+}
+
+impl Lookup {
+    fn new(source: LookupSource, local_key: &str, foreign_key: &str) -> Self {
+        Lookup {
+            source,
+            local_key: local_key.to_string(),
+            foreign_key: foreign_key.to_string(),
+            fields: vec![],
+            target: None,
+            join: JoinKind::Left,
+            table: None,
+        }
+    }
+
+    /// Looks `local_key` up in another sheet of the same workbook.
+    pub fn sheet(sheet_name: &str, local_key: &str, foreign_key: &str) -> Self {
+        Lookup::new(LookupSource::Sheet(sheet_name.to_string()), local_key, foreign_key)
+    }
+
+    /// Looks `local_key` up in the sheet `opts` reads.
+    pub fn file(opts: OptionSet, local_key: &str, foreign_key: &str) -> Self {
+        Lookup::new(LookupSource::File(Box::new(opts)), local_key, foreign_key)
+    }
+
+    pub fn fields(mut self, fields: &[&str]) -> Self {
+        self.fields = fields.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Nests the matched object under `target` instead of merging its fields.
+    pub fn nest_under(mut self, target: KeySegment) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Nests the matched object under a plain key.
+    pub fn nest_in(self, key: &str) -> Self {
+        self.nest_under(KeySegment::Simple(Arc::from(key)))
+    }
+
+    /// Drops rows without a match.
+    pub fn inner(mut self) -> Self {
+        self.join = JoinKind::Inner;
+        self
+    }
+
+    /// Parses `{"sheet": "products", "local_key": "product_code", "foreign_key": "code"}`
+    /// or `{"path": "products.csv", ...}`, with optional `fields`, `target` (a
+    /// `KeySegment`) and `join` (`"left"` or `"inner"`). A `path` may add `sheet` too.
+    /// Fails without a `local_key`, without a `sheet` or `path`, or with another `join`.
+    pub fn from_json(json: &Value) -> Result<Self, GenericError> {
+        let get_str = |field: &str| json.get(field).and_then(|v| v.as_str());
+        let local_key = get_str("local_key").ok_or(GenericError("lookup_missing_local_key"))?;
+        let foreign_key = get_str("foreign_key").unwrap_or(local_key);
+        let mut lookup = match (get_str("path"), get_str("sheet")) {
+            (Some(path), sheet) => {
+                let opts = OptionSet::new(path);
+                let opts = match sheet {
+                    Some(name) => opts.sheet_name(name),
+                    None => opts,
+                };
+                Lookup::file(opts, local_key, foreign_key)
+            }
+            (None, Some(sheet)) => Lookup::sheet(sheet, local_key, foreign_key),
+            (None, None) => return Err(GenericError("lookup_missing_source")),
+        };
+        if let Some(Value::Array(fields)) = json.get("fields") {
+            lookup.fields = fields.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect();
+        }
+        lookup.target = json.get("target").and_then(KeySegment::from_json);
+        lookup.join = match get_str("join").map(|join| join.to_lowercase()).as_deref() {
+            None | Some("left") => JoinKind::Left,
+            Some("inner") => JoinKind::Inner,
+            Some(_) => return Err(GenericError("lookup_invalid_join")),
+        };
+        Ok(lookup)
+    }
+
+    /// Identifies the lookup in `ResultSet.unmatched_keys`: its source sheet, or its
+    /// file name plus any sheet, then the local key, e.g. `products:product_code` or
+    /// `customers.csv:customer_id`.
+    pub fn name(&self) -> String {
+        let source = match &self.source {
+            LookupSource::Sheet(name) => name.clone(),
+            LookupSource::File(opts) => {
+                let path = opts.path.as_deref().unwrap_or_default();
+                let file_name = Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path);
+                match opts.selected.as_ref().and_then(|names| names.first()) {
+                    Some(sheet) => format!("{}/{}", file_name, sheet),
+                    None => file_name.to_string(),
+                }
+            }
+        };
+        format!("{}:{}", source, self.local_key)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut output = json!({
+            "local_key": self.local_key,
+            "foreign_key": self.foreign_key,
+            "fields": self.fields,
+            "target": self.target.as_ref().map(|t| t.to_string()),
+            "join": if self.join == JoinKind::Inner { "inner" } else { "left" },
+        });
+        match &self.source {
+            LookupSource::Sheet(name) => output["sheet"] = json!(name),
+            LookupSource::File(opts) => {
+                output["path"] = json!(opts.path);
+                if let Some(names) = &opts.selected {
+                    output["sheet"] = json!(names.first());
+                }
+            }
+        }
+        output
+    }
+
+    /// Embeds the match for one row, or returns `None` to drop it from an inner join.
+    /// Rows pass unchanged until the table is loaded.
+    pub fn apply(&self, mut row: IndexMap<String, Value>) -> Option<IndexMap<String, Value>> {
+        let Some(table) = &self.table else {
+            return Some(row);
+        };
+        let key_value = row.get(&self.local_key).cloned().unwrap_or(Value::Null);
+        let matched = lookup_key(&key_value).and_then(|key| {
+            let matched = table.rows.get(&key);
+            if matched.is_none() {
+                if let Ok(mut unmatched) = table.unmatched.lock() {
+                    unmatched.entry(key).or_insert(key_value);
+                }
+            }
+            matched
+        });
+        if matched.is_none() && self.join == JoinKind::Inner {
+            return None;
+        }
+        let embedded: Option<Map<String, Value>> = matched.map(|found| {
+            if self.fields.is_empty() {
+                found.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+            } else {
+                self.fields.iter().map(|f| (f.clone(), found.get(f).cloned().unwrap_or(Value::Null))).collect()
+            }
+        });
+        match &self.target {
+            Some(target) => {
+                let mut map: Map<String, Value> = row.into_iter().collect();
+                insert_key_segment(&mut map, target, embedded.map(Value::Object).unwrap_or(Value::Null));
+                row = map.into_iter().collect();
+            }
+            None => {
+                let fields = match embedded {
+                    Some(fields) => fields,
+                    None => self.fields.iter().map(|f| (f.clone(), Value::Null)).collect(),
+                };
+                for (key, value) in fields {
+                    row.entry(key).or_insert(value);
+                }
+            }
+        }
+        Some(row)
+    }
+
+    /// Distinct keys without a match so far, in the order first seen.
+    pub fn unmatched_keys(&self) -> Vec<Value> {
+        self.table
+            .as_ref()
+            .and_then(|table| table.unmatched.lock().ok().map(|keys| keys.values().cloned().collect()))
+            .unwrap_or_default()
+    }
+}
+
+/// Options for reading a lookup's source in full: a sheet of `opts`' workbook, or the
+/// lookup's own file options with no row cap unless one was set.
+fn source_options(opts: &OptionSet, lookup: &Lookup) -> Result<OptionSet, GenericError> {
+    let mut source = match &lookup.source {
+        LookupSource::Sheet(name) => {
+            let path = opts.path.as_deref().ok_or(GenericError("no_filepath_specified"))?;
+            let mut source = OptionSet::new(path).sheet_name(name);
+            source.detect_header = opts.detect_header;
+            source
+        }
+        LookupSource::File(source) => source.as_ref().clone(),
+    };
+    source.read_mode = ReadMode::Sync;
+    if source.max.is_none() {
+        source.max = Some(u32::MAX);
+    }
+    Ok(source)
+}
+
+/// Returns a copy of `opts` with every lookup table read and indexed.
+pub async fn load_lookups(opts: &OptionSet) -> Result<OptionSet, GenericError> {
+    let mut loaded = opts.clone();
+    for lookup in loaded.lookups.iter_mut() {
+        let source = source_options(opts, lookup)?;
+        let filepath = source.path.clone().ok_or(GenericError("no_filepath_specified"))?;
+        let path = Path::new(&filepath);
+        if !path.exists() {
+            return Err(GenericError("lookup_file_unavailable"));
+        }
+        let path_data = PathData::new(path);
+        if !path_data.is_valid() {
+            return Err(GenericError("unsupported_format"));
+        }
+        let result = if path_data.use_calamine() {
+            read_workbook_core(&path_data, &source, None, None).await?
+        } else {
+            read_csv_core(&path_data, &source, None, None).await?
+        };
+        lookup.table = Some(Arc::new(LookupTable::new(result.to_vec(), &lookup.foreign_key)));
+    }
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(lookup: Lookup, rows: Value) -> Lookup {
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(rows).unwrap();
+        let foreign_key = lookup.foreign_key.clone();
+        Lookup { table: Some(Arc::new(LookupTable::new(rows, &foreign_key))), ..lookup }
+    }
+
+    fn row(value: Value) -> IndexMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_lookup_embeds_fields_left_and_inner() {
+        let products = json!([{"code": 101, "name": "Bolt", "price": 0.2}, {"code": "P-2", "name": "Nut", "price": 0.1}]);
+        let left = loaded(Lookup::sheet("products", "product", "code").fields(&["name"]), products.clone());
+        assert_eq!(json!(left.apply(row(json!({"product": "101", "qty": 3})))), json!({"product": "101", "qty": 3, "name": "Bolt"}));
+        assert_eq!(json!(left.apply(row(json!({"product": "X9", "qty": 1})))), json!({"product": "X9", "qty": 1, "name": null}));
+        let inner = loaded(Lookup::sheet("products", "product", "code").nest_in("item").inner(), products);
+        assert_eq!(
+            json!(inner.apply(row(json!({"product": "P-2"})))),
+            json!({"product": "P-2", "item": {"code": "P-2", "name": "Nut", "price": 0.1}})
+        );
+        assert!(inner.apply(row(json!({"product": "X9"}))).is_none());
+        assert!(inner.apply(row(json!({"product": "X9"}))).is_none());
+        assert_eq!(inner.unmatched_keys(), vec![json!("X9")]);
+    }
+
+    #[test]
+    fn test_lookup_from_json_rejects_malformed_definitions() {
+        let lookup = Lookup::from_json(&json!({"path": "data/products.xlsx", "sheet": "items", "local_key": "sku"})).unwrap();
+        assert_eq!(lookup.foreign_key, "sku");
+        assert_eq!(lookup.name(), "products.xlsx/items:sku");
+        assert_eq!(Lookup::sheet("products", "product", "code").name(), "products:product");
+        let error = |json: Value| Lookup::from_json(&json).unwrap_err().to_string();
+        assert_eq!(error(json!({"sheet": "products"})), "lookup_missing_local_key");
+        assert_eq!(error(json!({"local_key": "sku"})), "lookup_missing_source");
+        assert_eq!(error(json!({"sheet": "products", "local_key": "sku", "join": "outer"})), "lookup_invalid_join");
+    }
+}
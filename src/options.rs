@@ -8,10 +8,27 @@ use enclose_strings::SimpleExtract;
 
 use is_truthy::TruthyRuleSet;
 use crate::key_segment::KeySegment;
+use crate::filter::RowFilter;
+use crate::derived::DerivedColumn;
+use crate::reshape::{glob_match, GroupRows, Pivot, Unpivot};
+use crate::aggregate::Aggregation;
+use crate::sort::{Dedup, SortSpec};
+use crate::lookup::Lookup;
+use crate::sheets::{SheetFilter, SheetPattern};
+use crate::cell_range::CellRange;
+use crate::tables::RangeSource;
+use crate::segment::TableSegmentation;
+use crate::form::FormExtraction;
+use crate::error::GenericError;
+use crate::detect::DEFAULT_FOOTER_BLANK_ROWS;
 /// default max number of rows in direct single sheet mode without an override via ->max_row_count(max_row_count)
 pub const DEFAULT_MAX_ROWS: usize = 10_000;
 /// default max number of rows multiple sheet preview mode without an override via ->max_row_count(max_row_count)
 pub const DEFAULT_MAX_ROWS_PREVIEW: usize = 1000;
+/// default key added to each row saved in multi-sheet modes, naming the row's sheet -- see ->sheet_tag(key)
+pub const DEFAULT_SHEET_TAG: &str = "_sheet";
+/// default number of data rows sampled by ->infer_column_types() to pick each Auto column's Format
+pub const DEFAULT_INFER_SAMPLE_SIZE: usize = 1000;
 
 /// How a datetime-bearing cell is rendered. `Full` is the ordinary complete ISO datetime;
 /// the other three each discard progressively more of it. Used both as `RowOptionSet`'s
@@ -61,6 +78,13 @@ pub struct RowOptionSet {
   /// genuine `Value::Null` -- an empty string ("") is a different, deliberate value and
   /// is left alone. Off by default: existing output is unchanged unless opted into.
   pub omit_null_values: bool,
+  /// Computed output fields, evaluated in order after each row's cells are converted
+  /// (and before `omit_null_values` and any row filter) -- see `derived.rs`.
+  pub derived: Vec<DerivedColumn>,
+  /// Set on the options `infer_sample` produces: typed columns then hold one type all the
+  /// way down, so explicit text is kept as written and a numeric column falls back to its
+  /// default rather than emitting stray text. Off by default, keeping per-cell typing.
+  pub inferred_types: bool,
 }
 
 impl RowOptionSet {
@@ -71,6 +95,8 @@ impl RowOptionSet {
       decimal_comma: false,
       datetime_mode: DateTimeMode::Full,
       omit_null_values: false,
+      derived: vec![],
+      inferred_types: false,
       columns: cols.to_vec()
     }
   }
@@ -81,6 +107,8 @@ impl RowOptionSet {
       decimal_comma,
       datetime_mode,
       omit_null_values: false,
+      derived: vec![],
+      inferred_types: false,
       columns: cols.to_vec()
     }
   }
@@ -122,6 +150,35 @@ pub struct OptionSet {
   /// captured as headers nor as data -- for spreadsheets that leave a note, blank, or
   /// subtitle row between the header and the first real data row.
   pub data_row_index: Option<usize>,
+  /// Cell range in A1 notation to read instead of the whole sheet, e.g. `C5:K240`, or
+  /// `C5:K` to read to the last row -- see `cell_range::CellRange`. `header_row` and
+  /// `data_row_index` then count from the range's first row. Parsed when reading, so a
+  /// malformed range fails with `invalid_cell_range`.
+  pub cell_range: Option<String>,
+  /// 0-based index of the last data row, counted like `data_row_index`. Rows below it
+  /// are never captured or saved.
+  pub data_end_row: Option<usize>,
+  /// Number of rows to drop from the end of the data, after `data_end_row` or footer
+  /// detection.
+  pub skip_footer: usize,
+  /// Whether to guess where the data ends, dropping total rows, blank padding and notes
+  /// below the table -- see `detect::detect_data_end`. Off by default, like
+  /// `detect_header`, and ignored when `data_end_row` is set.
+  pub detect_footer: bool,
+  /// Consecutive blank rows that end the data under footer detection, 2 by default.
+  pub footer_blank_rows: Option<usize>,
+  /// Whether records run down columns, with field labels in the first column, rather
+  /// than along rows. Transposed tables are turned back into rows before header
+  /// resolution, so `header_row`, `data_row_index` and columns apply to the result.
+  pub orientation: Orientation,
+  /// An Excel table or defined name to read instead of a sheet -- see `tables.rs`.
+  pub range_source: Option<RangeSource>,
+  /// Splits the selected sheet into the tables stacked or placed side by side on it --
+  /// see `segment.rs`.
+  pub segmentation: Option<TableSegmentation>,
+  /// Labelled values to extract from the whole sheet into `ResultSet.form`, alongside
+  /// the table read -- see `form.rs`. Single-sheet and CSV reads only.
+  pub form: Option<FormExtraction>,
   /// Number of consecutive rows, starting at the header row, that together form the
   /// header -- e.g. a spreadsheet with a merged "2015"/"2010" year row followed by a
   /// "Female"/"Male" sub-label row underneath needs a span of 2. Column keys are built
@@ -141,6 +198,48 @@ pub struct OptionSet {
   /// `.detect_header()`. Consumers like `spread-cli` that want it as *their own* default
   /// user experience turn it on unconditionally when building their `OptionSet`.
   pub detect_header: bool,
+  /// Number of leading data rows to sample, when set, to pick one `Format` for every
+  /// `Format::Auto` column before the read (see `profile::ColumnProfile::suggested_format`)
+  /// -- so each column is then typed consistently across all rows instead of cell by
+  /// cell, where a zip code column could otherwise be numbers in most rows but keep a
+  /// leading zero ("02134") only where a cell couldn't be read as a number. Explicit
+  /// column formats are never replaced. `None` (the default) keeps per-cell typing.
+  pub infer_sample: Option<usize>,
+  /// Keeps only the converted rows that match, evaluated before a row is captured or
+  /// passed to a `SaveRowFn` -- see `filter.rs` for the expression syntax. With a filter
+  /// set, `max` counts kept rows only, and the rows dropped are reported in
+  /// `ResultSet.filtered_count`.
+  pub filter: Option<RowFilter>,
+  /// Melts each converted row into one row per value column before the row filter runs
+  /// -- see `reshape::Unpivot`. With it set, `max` counts output rows.
+  pub unpivot: Option<Unpivot>,
+  /// Pivots the full set of converted, filtered rows into one object per index value
+  /// once reading is done -- see `reshape::Pivot`.
+  pub pivot: Option<Pivot>,
+  /// Merges rows sharing key columns into parent documents with a child array once
+  /// reading is done, or while streaming for sorted input -- see `reshape::GroupRows`.
+  pub group: Option<GroupRows>,
+  /// Replaces the rows with one summary row per group once reading is done -- see
+  /// `aggregate::Aggregation`.
+  pub aggregate: Option<Aggregation>,
+  /// Resolves key columns against other sheets or files, embedding the matched rows --
+  /// see `lookup.rs`. Applied to each converted row before any unpivot or filter.
+  pub lookups: Vec<Lookup>,
+  /// Per-sheet column overrides and header settings for multi-sheet modes, keyed by
+  /// snake_case sheet name -- see `SheetOptions`.
+  pub sheet_options: IndexMap<String, SheetOptions>,
+  /// Key added to each row passed to a `SaveRowFn` in multi-sheet modes, holding the
+  /// sheet name. `None` means `DEFAULT_SHEET_TAG`.
+  pub sheet_tag: Option<String>,
+  /// Appends every matching sheet into one flat data set -- see `SheetUnion`.
+  pub union: Option<SheetUnion>,
+  /// Pattern selection, exclusions and visibility rules for picking sheets -- see
+  /// `sheets::select_worksheets`.
+  pub sheet_filter: SheetFilter,
+  /// Orders the output rows -- see `sort::SortSpec`.
+  pub sort: Option<SortSpec>,
+  /// Drops repeated rows before any grouping -- see `sort::Dedup`.
+  pub dedup: Option<Dedup>,
   pub read_mode: ReadMode,
   pub field_mode: FieldNameMode
 }
@@ -158,8 +257,30 @@ impl OptionSet {
         omit_header: false,
         header_row: None,
         data_row_index: None,
+        cell_range: None,
+        data_end_row: None,
+        skip_footer: 0,
+        detect_footer: false,
+        footer_blank_rows: None,
+        orientation: Orientation::Rows,
+        range_source: None,
+        segmentation: None,
+        form: None,
         header_row_span: 1,
         detect_header: false,
+        infer_sample: None,
+        filter: None,
+        unpivot: None,
+        pivot: None,
+        group: None,
+        aggregate: None,
+        lookups: vec![],
+        sheet_options: IndexMap::new(),
+        sheet_tag: None,
+        union: None,
+        sheet_filter: SheetFilter::default(),
+        sort: None,
+        dedup: None,
         read_mode: ReadMode::Sync,
         field_mode: FieldNameMode::AutoA1,
     }
@@ -223,6 +344,98 @@ impl OptionSet {
       self
   }
 
+  /// Reads only the cells in an A1 range such as `C5:K240`, or `C5:K` to the last row.
+  /// Header and data row indices are then relative to the range's first row.
+  pub fn range(mut self, a1: &str) -> Self {
+      self.cell_range = Some(a1.to_string());
+      self
+  }
+
+  /// Sets the 0-based index of the last data row.
+  pub fn data_end_row(mut self, row: usize) -> Self {
+      self.data_end_row = Some(row);
+      self
+  }
+
+  /// Drops the last `rows` rows of data, e.g. a fixed totals and notes block.
+  pub fn skip_footer(mut self, rows: usize) -> Self {
+      self.skip_footer = rows;
+      self
+  }
+
+  /// Guesses where the data ends, stopping at a run of blank rows and dropping trailing
+  /// total, blank and notes rows.
+  pub fn detect_footer(mut self) -> Self {
+      self.detect_footer = true;
+      self
+  }
+
+  /// Sets how many consecutive blank rows end the data under footer detection.
+  pub fn footer_blank_rows(mut self, rows: usize) -> Self {
+      self.footer_blank_rows = Some(rows.max(1));
+      self
+  }
+
+  /// Reads a transposed table, with field labels down the first column and one record
+  /// per column.
+  pub fn transpose(mut self) -> Self {
+      self.orientation = Orientation::Columns;
+      self
+  }
+
+  /// Transposes the table only if its data types run along rows rather than down
+  /// columns -- see `detect::detect_column_records`.
+  pub fn detect_orientation(mut self) -> Self {
+      self.orientation = Orientation::Detect;
+      self
+  }
+
+  /// Whether any footer option is set, so the end of the data must be resolved.
+  pub fn handles_footer(&self) -> bool {
+      self.data_end_row.is_some() || self.skip_footer > 0 || self.detect_footer
+  }
+
+  pub fn footer_blank_run(&self) -> usize {
+      self.footer_blank_rows.unwrap_or(DEFAULT_FOOTER_BLANK_ROWS)
+  }
+
+  /// Reads an Excel table (`Insert > Table`) by name, using its own header row and
+  /// extent. Only xlsx and xlsm workbooks have tables.
+  pub fn table(mut self, name: &str) -> Self {
+      self.range_source = Some(RangeSource::Table(name.to_string()));
+      self
+  }
+
+  /// Reads the range a workbook-level defined name refers to, with its first row as
+  /// the header unless `header_row` is set.
+  pub fn defined_name(mut self, name: &str) -> Self {
+      self.range_source = Some(RangeSource::DefinedName(name.to_string()));
+      self
+  }
+
+  /// Reads every table on the selected sheet as its own data set, splitting on blank rows
+  /// and columns and detecting each table's header.
+  pub fn split_tables(self) -> Self {
+      self.table_segmentation(TableSegmentation::default())
+  }
+
+  pub fn table_segmentation(mut self, segmentation: TableSegmentation) -> Self {
+      self.segmentation = Some(segmentation);
+      self
+  }
+
+  /// Extracts labelled values scattered across the sheet into one object, reported as
+  /// `ResultSet.form` next to the rows read.
+  pub fn form(mut self, form: FormExtraction) -> Self {
+      self.form = Some(form);
+      self
+  }
+
+  /// The parsed `cell_range`, if set.
+  pub fn parsed_cell_range(&self) -> Result<Option<CellRange>, GenericError> {
+      self.cell_range.as_deref().map(CellRange::parse).transpose()
+  }
+
   /// Sets the number of consecutive rows, starting at the header row, that together
   /// form the header -- see `header_row_span`'s field doc for what multi-row headers
   /// this is for.
@@ -246,6 +459,135 @@ impl OptionSet {
       self
   }
 
+  /// Opts into column-consistent typing: samples the first `DEFAULT_INFER_SAMPLE_SIZE`
+  /// data rows and assigns each `Format::Auto` column the format that fits it -- see the
+  /// `infer_sample` field doc.
+  pub fn infer_column_types(mut self) -> Self {
+      self.infer_sample = Some(DEFAULT_INFER_SAMPLE_SIZE);
+      self
+  }
+
+  /// As `infer_column_types`, sampling the first `size` data rows instead.
+  pub fn infer_column_types_sample(mut self, size: usize) -> Self {
+      self.infer_sample = Some(size.max(1));
+      self
+  }
+
+  /// Keeps only rows matching a parsed filter expression, e.g.
+  /// `RowFilter::parse("qty > 0 && region in [\"west\",\"east\"]")?`.
+  pub fn row_filter(mut self, filter: RowFilter) -> Self {
+      self.filter = Some(filter);
+      self
+  }
+
+  /// Keeps only rows for which `predicate` returns `true`.
+  pub fn filter_fn<F>(mut self, predicate: F) -> Self
+  where
+      F: Fn(&IndexMap<String, Value>) -> bool + Send + Sync + 'static,
+  {
+      self.filter = Some(RowFilter::from_fn(predicate));
+      self
+  }
+
+  /// Whether a converted row passes the row filter, if any.
+  pub fn keeps_row(&self, row: &IndexMap<String, Value>) -> bool {
+      self.filter.as_ref().is_none_or(|f| f.matches(row))
+  }
+
+  /// Adds a lookup against another sheet or file -- see `lookup::Lookup`.
+  pub fn lookup(mut self, lookup: Lookup) -> Self {
+      self.lookups.push(lookup);
+      self
+  }
+
+  /// Sets the lookups from JSON definitions -- see `Lookup::from_json`. Fails on the
+  /// first malformed definition.
+  pub fn lookups(mut self, lookups: &[Value]) -> Result<Self, GenericError> {
+      self.lookups = lookups.iter().map(Lookup::from_json).collect::<Result<_, _>>()?;
+      Ok(self)
+  }
+
+  /// Keys without a match per lookup (by `Lookup::name`) in this read so far.
+  pub fn unmatched_lookup_keys(&self) -> IndexMap<String, Vec<Value>> {
+      self.lookups.iter().map(|l| (l.name(), l.unmatched_keys())).collect()
+  }
+
+  /// Melts wide value columns into long rows -- see `reshape::Unpivot`.
+  pub fn unpivot(mut self, unpivot: Unpivot) -> Self {
+      self.unpivot = Some(unpivot);
+      self
+  }
+
+  /// The output rows for one converted source row after any lookups: the row itself,
+  /// its melted rows with `unpivot` set, or none when an inner lookup finds no match.
+  pub fn output_rows(&self, row: IndexMap<String, Value>) -> Vec<IndexMap<String, Value>> {
+      let Some(row) = self.lookups.iter().try_fold(row, |row, lookup| lookup.apply(row)) else {
+          return vec![];
+      };
+      match &self.unpivot {
+          Some(unpivot) => unpivot.apply(&row),
+          None => vec![row],
+      }
+  }
+
+  /// Pivots long rows into wide objects after reading -- see `reshape::Pivot`.
+  pub fn pivot(mut self, pivot: Pivot) -> Self {
+      self.pivot = Some(pivot);
+      self
+  }
+
+  /// Merges rows into parent documents with a child array -- see `reshape::GroupRows`.
+  pub fn group_rows(mut self, group: GroupRows) -> Self {
+      self.group = Some(group);
+      self
+  }
+
+  /// Outputs summary rows per group instead of the rows themselves -- see
+  /// `aggregate::Aggregation`.
+  pub fn aggregate(mut self, aggregation: Aggregation) -> Self {
+      self.aggregate = Some(aggregation);
+      self
+  }
+
+  /// Sorts the output rows -- see `sort::SortSpec`. When streaming to a save callback,
+  /// rows beyond one chunk spill to temporary files and are merged after reading.
+  pub fn sort(mut self, sort: SortSpec) -> Self {
+      self.sort = Some(sort);
+      self
+  }
+
+  /// Sorts by field names, with a leading `-` for descending order, e.g.
+  /// `.sort_by(&["country", "-score"])`.
+  pub fn sort_by(self, fields: &[&str]) -> Result<Self, GenericError> {
+      Ok(self.sort(SortSpec::by(fields)?))
+  }
+
+  /// Drops duplicate rows, or rows with duplicate key columns -- see `sort::Dedup`.
+  pub fn dedup(mut self, dedup: Dedup) -> Self {
+      self.dedup = Some(dedup);
+      self
+  }
+
+  /// Whether any stage reshapes the row set once reading is done (see
+  /// `reshape::transform_rows`).
+  pub fn transforms_row_set(&self) -> bool {
+      self.pivot.is_some() || self.group.is_some() || self.aggregate.is_some() || self.sort.is_some() || self.dedup.is_some()
+  }
+
+  /// Whether rows for a `SaveRowFn` must be buffered until reading is done, rather than
+  /// streamed -- true for pivots and grouping of unsorted input. Sorting and
+  /// de-duplication stream with bounded buffers of their own, and aggregation folds each
+  /// row into its group's accumulators.
+  pub fn buffers_row_set(&self) -> bool {
+      self.pivot.is_some() || self.group.as_ref().is_some_and(|g| !g.sorted)
+  }
+
+  /// Whether `max` caps output rows rather than source rows read, i.e. when rows may be
+  /// dropped by the filter or an inner lookup, or multiplied by `unpivot`.
+  pub fn counts_output_rows(&self) -> bool {
+      self.filter.is_some() || self.unpivot.is_some() || self.lookups.iter().any(|l| l.join == crate::lookup::JoinKind::Inner)
+  }
+
   /// Sets the maximum number of rows to read.
   pub fn max_row_count(mut self, max: u32) -> Self {
       self.max = Some(max);
@@ -266,6 +608,128 @@ impl OptionSet {
     self
 }
 
+  /// Sets the read mode to full multi-sheet extraction: every selected sheet (see
+  /// `sheet_names`/`sheet_indices`, or `all_sheets`) is read in full, each with its own
+  /// header detection and any `SheetOptions`.
+  pub fn read_mode_multiple(mut self) -> Self {
+    self.read_mode = ReadMode::Multiple;
+    self
+  }
+
+  /// Sets the read mode to asynchronous multi-sheet extraction: like
+  /// `read_mode_multiple`, but rows are only passed to the save callback, each tagged
+  /// with its sheet name under `sheet_tag`.
+  pub fn read_mode_async_multiple(mut self) -> Self {
+    self.read_mode = ReadMode::AsyncMultiple;
+    self
+  }
+
+  /// Selects every sheet in the workbook for the multi-sheet modes.
+  pub fn all_sheets(mut self) -> Self {
+    self.selected = None;
+    self.indices = vec![];
+    self
+  }
+
+  /// Whether a multi-sheet read covers every eligible sheet, i.e. no sheet was selected
+  /// by name, pattern or index.
+  pub fn reads_all_sheets(&self) -> bool {
+    self.selected.is_none() && self.indices.is_empty() && self.sheet_filter.include.is_empty()
+  }
+
+  /// Selects sheets whose names match a glob pattern (`*`, `?`, ignoring case), e.g.
+  /// `"Sales*"`, after any selected by name.
+  pub fn sheet_pattern(self, pattern: &str) -> Self {
+    self.select_sheets(SheetPattern::glob(pattern))
+  }
+
+  /// Selects sheets matching a name, glob or regex pattern -- see `SheetPattern`.
+  pub fn select_sheets(mut self, pattern: SheetPattern) -> Self {
+    self.sheet_filter.include.push(pattern);
+    self
+  }
+
+  /// Never selects these sheets. Entries with `*` or `?` are globs, others names.
+  pub fn exclude_sheets(mut self, names: &[&str]) -> Self {
+    for name in names {
+      let pattern = if name.contains(['*', '?']) { SheetPattern::glob(name) } else { SheetPattern::name(name) };
+      self.sheet_filter.exclude.push(pattern);
+    }
+    self
+  }
+
+  /// Never selects sheets matching a pattern, e.g. `SheetPattern::regex("(?i)^draft")?`.
+  pub fn exclude_sheet_pattern(mut self, pattern: SheetPattern) -> Self {
+    self.sheet_filter.exclude.push(pattern);
+    self
+  }
+
+  /// Skips hidden and very hidden sheets.
+  pub fn skip_hidden_sheets(mut self) -> Self {
+    self.sheet_filter.skip_hidden = true;
+    self
+  }
+
+  /// Skips very hidden sheets, which can only be shown again via VBA.
+  pub fn skip_very_hidden_sheets(mut self) -> Self {
+    self.sheet_filter.skip_very_hidden = true;
+    self
+  }
+
+  pub fn skip_chart_sheets(mut self) -> Self {
+    self.sheet_filter.skip_charts = true;
+    self
+  }
+
+  /// Fails with `no_matching_sheets` instead of falling back to the first sheet when
+  /// the requested sheets can't be found.
+  pub fn strict_sheet_match(mut self) -> Self {
+    self.sheet_filter.strict = true;
+    self
+  }
+
+  /// Overrides column and header settings for one sheet in multi-sheet modes.
+  pub fn sheet_options(mut self, sheet_name: &str, sheet_opts: SheetOptions) -> Self {
+    self.sheet_options.insert(sheet_name.to_snake_case(), sheet_opts);
+    self
+  }
+
+  /// Appends every sheet whose name matches a glob pattern (`*`, `?`, ignoring case),
+  /// e.g. `"*"` or `"20??-*"`, into one data set -- see `SheetUnion`.
+  pub fn union_sheets(self, pattern: &str) -> Self {
+    self.sheet_union(SheetUnion::matching(pattern))
+  }
+
+  /// Appends the sheets at these 0-based indices into one data set.
+  pub fn union_sheet_indices(self, indices: &[u32]) -> Self {
+    self.sheet_union(SheetUnion::indices(indices))
+  }
+
+  pub fn sheet_union(mut self, union: SheetUnion) -> Self {
+    self.union = Some(union);
+    self
+  }
+
+  /// Sets the key that names the source sheet in rows saved in multi-sheet modes, and
+  /// in every row of a sheet union.
+  pub fn sheet_tag(mut self, key: &str) -> Self {
+    self.sheet_tag = Some(key.to_string());
+    self
+  }
+
+  pub fn sheet_tag_key(&self) -> &str {
+    self.sheet_tag.as_deref().unwrap_or(DEFAULT_SHEET_TAG)
+  }
+
+  /// Options for reading one sheet: a copy with that sheet's `SheetOptions` applied.
+  pub fn for_sheet(&self, sheet_name: &str) -> OptionSet {
+    let mut sheet_opts = self.clone();
+    if let Some(overrides) = self.sheet_options.get(&sheet_name.to_snake_case()) {
+      overrides.apply(&mut sheet_opts);
+    }
+    sheet_opts
+  }
+
   /// Sets read mode from a range of common key names
   /// async, preview or sync (default) with synonyms such as `a`, `p` and `s`
   /// If the key is unmatched, it will always default to Sync
@@ -306,6 +770,19 @@ impl OptionSet {
     self
   }
 
+  /// Adds computed output fields from JSON definitions -- see `DerivedColumn::from_json`.
+  /// Fails on the first malformed definition.
+  pub fn derived_columns(mut self, cols: &[Value]) -> Result<Self, GenericError> {
+    self.rows.derived = cols.iter().map(DerivedColumn::from_json).collect::<Result<_, _>>()?;
+    Ok(self)
+  }
+
+  /// Appends one computed output field.
+  pub fn derived_column(mut self, col: DerivedColumn) -> Self {
+    self.rows.derived.push(col);
+    self
+  }
+
   /// Sets the column key naming convention.
   pub fn field_name_mode(mut self, system: &str, override_header: bool) -> Self {
       self.field_mode = FieldNameMode::from_key(system, override_header);
@@ -355,7 +832,65 @@ impl OptionSet {
     output.insert("omit_header".to_string(), self.omit_header.into());
     output.insert("header_row".to_string(), self.header_row.into());
     output.insert("data_row_index".to_string(), self.data_row_index.into());
+    if let Some(cell_range) = &self.cell_range {
+      output.insert("range".to_string(), cell_range.clone().into());
+    }
+    if self.orientation != Orientation::Rows {
+      output.insert("orientation".to_string(), self.orientation.to_string().into());
+    }
+    if let Some(source) = &self.range_source {
+      output.insert("source".to_string(), source.to_json());
+    }
+    if let Some(segmentation) = &self.segmentation {
+      output.insert("split_tables".to_string(), segmentation.to_json());
+    }
+    if let Some(form) = &self.form {
+      output.insert("form".to_string(), form.to_json());
+    }
     output.insert("detect_header".to_string(), self.detect_header.into());
+    if self.handles_footer() {
+      output.insert("data_end_row".to_string(), self.data_end_row.into());
+      output.insert("skip_footer".to_string(), self.skip_footer.into());
+      output.insert("detect_footer".to_string(), self.detect_footer.into());
+    }
+    if let Some(sample) = self.infer_sample {
+      output.insert("infer_sample".to_string(), sample.into());
+    }
+    if let Some(filter) = &self.filter {
+      output.insert("filter".to_string(), filter.to_string().into());
+    }
+    if let Some(unpivot) = &self.unpivot {
+      output.insert("unpivot".to_string(), unpivot.to_json());
+    }
+    if let Some(pivot) = &self.pivot {
+      output.insert("pivot".to_string(), pivot.to_json());
+    }
+    if let Some(group) = &self.group {
+      output.insert("group".to_string(), group.to_json());
+    }
+    if let Some(aggregation) = &self.aggregate {
+      output.insert("aggregate".to_string(), aggregation.to_json());
+    }
+    if let Some(union) = &self.union {
+      output.insert("union".to_string(), union.to_json());
+    }
+    if !self.sheet_filter.is_default() {
+      output.insert("sheet_filter".to_string(), self.sheet_filter.to_json());
+    }
+    if !self.sheet_options.is_empty() {
+      let sheets: Vec<&String> = self.sheet_options.keys().collect();
+      output.insert("sheet_options".to_string(), json!(sheets));
+    }
+    if !self.lookups.is_empty() {
+      let lookups: Vec<Value> = self.lookups.iter().map(|l| l.to_json()).collect();
+      output.insert("lookups".to_string(), lookups.into());
+    }
+    if let Some(sort) = &self.sort {
+      output.insert("sort".to_string(), sort.to_json());
+    }
+    if let Some(dedup) = &self.dedup {
+      output.insert("dedup".to_string(), dedup.to_json());
+    }
     output.insert("read_mode".to_string(), self.read_mode.to_string().into());
     output.insert("jsonl".to_string(), self.jsonl.into());
     output.insert("decimal_separator".to_string(), self.rows.decimal_separator().into());
@@ -364,6 +899,10 @@ impl OptionSet {
       let columns: Vec<Value> = self.rows.columns.clone().into_iter().map(|c| c.to_json()).collect();
       output.insert("columns".to_string(), columns.into());
     }
+    if !self.rows.derived.is_empty() {
+      let derived: Vec<Value> = self.rows.derived.iter().map(|d| d.to_json()).collect();
+      output.insert("derived".to_string(), derived.into());
+    }
     json!(output)
   }
 
@@ -403,6 +942,62 @@ impl OptionSet {
       format!("date mode: {}", self.rows.date_mode()),
       format!("column style: {}", self.field_mode.to_string())
     ]);
+    if let Some(cell_range) = &self.cell_range {
+      lines.push(format!("range: {}", cell_range));
+    }
+    if let Some(last) = self.data_end_row {
+      lines.push(format!("data end row: {}", last));
+    } else if self.detect_footer {
+      lines.push("data end row: auto-detect".to_string());
+    }
+    if self.skip_footer > 0 {
+      lines.push(format!("skip footer rows: {}", self.skip_footer));
+    }
+    if self.orientation != Orientation::Rows {
+      lines.push(format!("orientation: {}", self.orientation));
+    }
+    match &self.range_source {
+      Some(RangeSource::Table(name)) => lines.push(format!("table: {}", name)),
+      Some(RangeSource::DefinedName(name)) => lines.push(format!("defined name: {}", name)),
+      None => {}
+    }
+    if self.segmentation.is_some() {
+      lines.push("split tables: yes".to_string());
+    }
+    if let Some(form) = &self.form {
+      lines.push(format!("form fields: {}{}", form.fields.len(), if form.auto_labels { " plus labels ending in ':'" } else { "" }));
+    }
+    if let Some(sample) = self.infer_sample {
+      lines.push(format!("infer column types from: first {} rows", sample));
+    }
+    if let Some(filter) = &self.filter {
+      lines.push(format!("filter: {}", filter));
+    }
+    if let Some(unpivot) = &self.unpivot {
+      lines.push(format!("unpivot: {} into {} / {}", unpivot.value_pattern.clone().unwrap_or_else(|| unpivot.value_columns.join(",")), unpivot.variables.join(","), unpivot.value));
+    }
+    if let Some(pivot) = &self.pivot {
+      lines.push(format!("pivot: {} by {} ({} of {})", pivot.index.join(","), pivot.columns, pivot.aggregate, pivot.values));
+    }
+    if let Some(group) = &self.group {
+      let children = if group.children.is_empty() { "all other columns".to_string() } else { group.children.join(",") };
+      lines.push(format!("group by: {} with {} in {}", group.by.join(","), children, group.container));
+    }
+    if let Some(aggregation) = &self.aggregate {
+      let metrics: Vec<String> = aggregation.metrics.iter().map(|m| m.name.clone()).collect();
+      lines.push(format!("aggregate: {} by {}", metrics.join(","), aggregation.group_by.join(",")));
+    }
+    for lookup in &self.lookups {
+      lines.push(format!("lookup: {} -> {}", lookup.local_key, lookup.foreign_key));
+    }
+    if let Some(sort) = &self.sort {
+      let keys: Vec<String> = sort.keys.iter().map(|k| k.to_string()).collect();
+      lines.push(format!("sort: {}", keys.join(", ")));
+    }
+    if let Some(dedup) = &self.dedup {
+      let by = if dedup.by.is_empty() { "all columns".to_string() } else { dedup.by.join(",") };
+      lines.push(format!("dedup: {} (keep {})", by, if dedup.keep == crate::sort::DedupKeep::Last { "last" } else { "first" }));
+    }
 
     if !self.columns().is_empty() {
       lines.push("columns:".to_string());
@@ -410,6 +1005,12 @@ impl OptionSet {
         lines.push(col.to_line());
       }
     }
+    if !self.rows.derived.is_empty() {
+      lines.push("derived columns:".to_string());
+      for col in &self.rows.derived {
+        lines.push(format!("  {}", col));
+      }
+    }
     lines
   }
 
@@ -459,6 +1060,7 @@ impl OptionSet {
     } else {
       match self.read_mode {
         ReadMode::PreviewMultiple => DEFAULT_MAX_ROWS_PREVIEW,
+        ReadMode::Multiple | ReadMode::AsyncMultiple => usize::MAX,
         _ => DEFAULT_MAX_ROWS
       }
     }
@@ -482,12 +1084,148 @@ impl OptionSet {
 
   // Should rows be captured synchronously
   pub fn capture_rows(&self) -> bool {
-    !matches!(self.read_mode, ReadMode::Async)
+    !self.read_mode.is_async()
   }
 
 }
 
 
+/// Sheets appended into one flat data set (see `OptionSet::sheet_union`). Columns are
+/// aligned by natural key rather than position, and each row gets the name of its sheet
+/// under `OptionSet::sheet_tag_key`. With neither a pattern nor indices, every sheet is
+/// included.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SheetUnion {
+  /// Glob pattern (`*`, `?`) matched against sheet names, ignoring case.
+  pub pattern: Option<String>,
+  /// 0-based sheet indices, included as well as any sheets matching `pattern`.
+  pub indices: Vec<u32>,
+}
+
+impl SheetUnion {
+  pub fn matching(pattern: &str) -> Self {
+    SheetUnion { pattern: Some(pattern.to_string()), indices: vec![] }
+  }
+
+  pub fn indices(indices: &[u32]) -> Self {
+    SheetUnion { pattern: None, indices: indices.to_vec() }
+  }
+
+  pub fn matches(&self, index: usize, name: &str) -> bool {
+    if self.pattern.is_none() && self.indices.is_empty() {
+      return true;
+    }
+    self.indices.contains(&(index as u32))
+      || self.pattern.as_ref().is_some_and(|p| glob_match(&p.to_lowercase(), &name.to_lowercase()))
+  }
+
+  /// Parses `{"pattern": "2025-*", "indices": [0, 2]}`, or a plain pattern string.
+  pub fn from_json(json: &Value) -> Self {
+    match json {
+      Value::String(pattern) => SheetUnion::matching(pattern),
+      _ => SheetUnion {
+        pattern: json.get("pattern").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        indices: json
+          .get("indices")
+          .and_then(|v| v.as_array())
+          .map(|items| items.iter().filter_map(|v| v.as_u64()).map(|n| n as u32).collect())
+          .unwrap_or_default(),
+      },
+    }
+  }
+
+  pub fn to_json(&self) -> Value {
+    json!({"pattern": self.pattern, "indices": self.indices})
+  }
+}
+
+/// Column overrides and header settings that replace the top-level ones for one sheet
+/// in the multi-sheet modes (see `OptionSet::sheet_options`). Unset fields keep the
+/// top-level value.
+#[derive(Debug, Clone, Default)]
+pub struct SheetOptions {
+  pub columns: Option<Vec<Column>>,
+  pub header_row: Option<usize>,
+  pub data_row_index: Option<usize>,
+  pub header_row_span: Option<usize>,
+  pub detect_header: Option<bool>,
+  pub omit_header: Option<bool>,
+}
+
+impl SheetOptions {
+  pub fn new() -> Self {
+    SheetOptions::default()
+  }
+
+  /// Column overrides from JSON, as for `OptionSet::override_columns`.
+  pub fn override_columns(mut self, cols: &[Value]) -> Self {
+    self.columns = Some(cols.iter().map(Column::from_json).collect());
+    self
+  }
+
+  pub fn header_row(mut self, index: usize) -> Self {
+    self.header_row = Some(index);
+    self
+  }
+
+  pub fn data_row_index(mut self, index: usize) -> Self {
+    self.data_row_index = Some(index);
+    self
+  }
+
+  pub fn header_row_span(mut self, span: usize) -> Self {
+    self.header_row_span = Some(span);
+    self
+  }
+
+  pub fn detect_header(mut self, detect: bool) -> Self {
+    self.detect_header = Some(detect);
+    self
+  }
+
+  pub fn omit_header(mut self, omit: bool) -> Self {
+    self.omit_header = Some(omit);
+    self
+  }
+
+  /// Parses `{"columns": [...], "header_row": 2, "data_row_index": 4,
+  /// "header_row_span": 2, "detect_header": true, "omit_header": false}`, all optional.
+  pub fn from_json(json: &Value) -> Self {
+    let get_index = |key: &str| json.get(key).and_then(|v| v.as_u64()).map(|n| n as usize);
+    let get_bool = |key: &str| json.get(key).and_then(|v| v.as_bool());
+    SheetOptions {
+      columns: json.get("columns").and_then(|v| v.as_array()).map(|cols| cols.iter().map(Column::from_json).collect()),
+      header_row: get_index("header_row"),
+      data_row_index: get_index("data_row_index"),
+      header_row_span: get_index("header_row_span"),
+      detect_header: get_bool("detect_header"),
+      omit_header: get_bool("omit_header"),
+    }
+  }
+
+  /// Overwrites the matching settings of `opts`.
+  pub fn apply(&self, opts: &mut OptionSet) {
+    if let Some(columns) = &self.columns {
+      opts.rows.columns = columns.clone();
+    }
+    if self.header_row.is_some() {
+      opts.header_row = self.header_row;
+    }
+    if self.data_row_index.is_some() {
+      opts.data_row_index = self.data_row_index;
+    }
+    if let Some(span) = self.header_row_span {
+      opts.header_row_span = span;
+    }
+    if let Some(detect) = self.detect_header {
+      opts.detect_header = detect;
+    }
+    if let Some(omit) = self.omit_header {
+      opts.omit_header = omit;
+    }
+  }
+}
+
 /// Cell format overrides
 #[derive(Debug, Clone)]
 pub enum Format {
@@ -690,6 +1428,10 @@ pub struct Column {
   /// `RowOptionSet::datetime_mode` default when set to anything other than `Full`.
   pub datetime_mode: DateTimeMode,
   pub decimal_comma: bool, // parse as euro number format
+  /// Validation rule: the field must be present in every output row. Not enforced while
+  /// reading -- it's what `schema::json_schema` lists under `required`, for downstream
+  /// services validating the converted JSON.
+  pub required: bool,
 }
 
 impl Column {
@@ -752,6 +1494,7 @@ impl Column {
     } else {
       Column::from_key_ref_with_format(key_opt, fmt, default, datetime_mode, decimal_comma)
     };
+    col.required = json.get("required").and_then(|v| v.as_bool()).unwrap_or(false);
     // key_opt above only ever handles a plain string (a non-string "key" -- the tagged
     // object form -- silently became Simple("") through it, since as_str() on an object
     // returns None). KeySegment::from_json is the real parser for "key": it handles the
@@ -793,6 +1536,12 @@ impl Column {
     self
   }
 
+  /// Marks the field as required in every output row -- see `Column::required`.
+  pub fn set_required(mut self, val: bool) -> Self {
+    self.required = val;
+    self
+  }
+
   pub fn from_key_ref_with_format(key_opt: Option<&str>, format: Format, default: Option<Value>, datetime_mode: DateTimeMode, decimal_comma: bool) -> Self {
     let key = key_opt.map(|k_str| KeySegment::Simple(Arc::from(k_str)));
     Column {
@@ -801,7 +1550,8 @@ impl Column {
       format,
       default,
       datetime_mode,
-      decimal_comma
+      decimal_comma,
+      required: false
     }
   }
 
@@ -824,7 +1574,8 @@ impl Column {
       "format": self.format.to_string(),
       "default": self.default,
       "datetime_mode": self.datetime_mode.to_string(),
-      "decimal_comma": self.decimal_comma
+      "decimal_comma": self.decimal_comma,
+      "required": self.required
     })
   }
 
@@ -983,7 +1734,11 @@ pub enum ReadMode {
   #[default]
   Sync,
   PreviewMultiple,
-  Async
+  Async,
+  /// Every selected sheet in full, with captured rows per sheet.
+  Multiple,
+  /// Every selected sheet in full, with rows only passed to the save callback.
+  AsyncMultiple
 }
 
 /// either Preview or Async mode
@@ -994,16 +1749,23 @@ impl ReadMode {
     match sample.as_str() {
       "async" | "defer" | "deferred" | "a" => ReadMode::Async,
       "preview" | "p" | "pre" | "multimode" | "multiple" | "previewmultiple" | "previewmulti" | "m" => ReadMode::PreviewMultiple,
+      "full" | "fullmultiple" | "allsheets" | "sheets" | "multisheet" | "f" => ReadMode::Multiple,
+      "asyncmultiple" | "deferredmultiple" | "asyncsheets" | "deferredsheets" | "am" => ReadMode::AsyncMultiple,
       _ => ReadMode::Sync
     }
   }
 
   pub fn is_async(&self) -> bool {
-    matches!(self, Self::Async)
+    matches!(self, Self::Async | Self::AsyncMultiple)
   }
 
   /// not preview or sync mode
   pub fn is_multimode(&self) -> bool {
+    matches!(self, Self::PreviewMultiple | Self::Multiple | Self::AsyncMultiple)
+  }
+
+  /// capped multi-sheet preview, as opposed to full multi-sheet extraction
+  pub fn is_preview(&self) -> bool {
     matches!(self, Self::PreviewMultiple)
   }
 }
@@ -1013,12 +1775,37 @@ impl std::fmt::Display for ReadMode {
     let result = match self {
       Self::Async => "deferred",
       Self::PreviewMultiple => "preview",
+      Self::Multiple => "full multiple",
+      Self::AsyncMultiple => "deferred multiple",
       _ => "direct"
     };
     write!(f, "{}", result)
   }
 }
 
+/// Which way a table's records run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+  /// One record per row, the usual layout.
+  #[default]
+  Rows,
+  /// One record per column, with field labels down the first column.
+  Columns,
+  /// Transposed only when the sample looks like one record per column.
+  Detect,
+}
+
+impl std::fmt::Display for Orientation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let result = match self {
+      Self::Rows => "rows",
+      Self::Columns => "columns",
+      Self::Detect => "auto-detect",
+    };
+    write!(f, "{}", result)
+  }
+}
+
 /// defines the column key naming convention
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum FieldNameMode {
@@ -1261,4 +2048,11 @@ mod tests {
     assert_eq!(ext.to_string(), "xlsm");
   }
 
+  #[test]
+  fn test_read_mode_names_round_trip() {
+    for mode in [ReadMode::Sync, ReadMode::PreviewMultiple, ReadMode::Async, ReadMode::Multiple, ReadMode::AsyncMultiple] {
+      assert_eq!(ReadMode::from_key(&mode.to_string()), mode);
+    }
+  }
+
 }
\ No newline at end of file
@@ -0,0 +1,608 @@
+//! Column profiling: a pass over a sample of a sheet's *raw* cells (before any per-cell
+//! typing) that reports, per column, the dominant type, null/blank counts, an
+//! (approximate, for large samples) distinct count, min/max, sample values and a
+//! suggested `Format` -- emitted as ready-to-use `Column` JSON for `override_columns`.
+//!
+//! Raw cells are profiled rather than converted values because conversion is exactly
+//! what loses the evidence: by the time a CSV zip code "02134" has been through Auto
+//! typing it is the number 2134, indistinguishable from a genuine integer.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::BufReader;
+use std::path::Path;
+
+use calamine::{open_workbook_auto, Data, DataType, Reader, Sheets};
+use indexmap::IndexMap;
+use is_truthy::*;
+use serde_json::{json, Number, Value};
+
+use crate::detect::{resolve_header_and_data_rows, DETECT_SAMPLE_SIZE};
+use crate::error::GenericError;
+use crate::headers::{combine_header_rows, is_not_header_row, natural_column_keys};
+use crate::reader::{
+    csv_sample_lines, guess_date_string, guess_datetime_string, orient_range, parse_bare_time_string, resolve_sample_sheet,
+    restrict_to_cell_range, text_rows,
+};
+use crate::{Format, OptionSet, PathData};
+
+/// Share of a column's non-blank cells the most common type must reach before it is
+/// suggested as the column's `Format`; below this the column is suggested as `text`,
+/// which never loses information.
+pub const DOMINANCE_THRESHOLD: f64 = 0.9;
+
+/// Distinct values are counted exactly up to this many, then estimated.
+const EXACT_DISTINCT_LIMIT: usize = 10_000;
+
+/// Sample values kept per column.
+const MAX_SAMPLES: usize = 5;
+
+/// The type a single raw cell looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellKind {
+    /// No cell at all (calamine `Data::Empty`, or a missing trailing CSV field).
+    Null,
+    /// A present but empty or whitespace-only cell.
+    Blank,
+    Boolean,
+    Integer,
+    Float,
+    Date,
+    DateTime,
+    Time,
+    /// Free text, including numeric-looking codes with a leading zero ("02134").
+    Text,
+}
+
+impl CellKind {
+    fn is_numeric(&self) -> bool {
+        matches!(self, CellKind::Integer | CellKind::Float)
+    }
+}
+
+impl std::fmt::Display for CellKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let result = match self {
+            CellKind::Null => "null",
+            CellKind::Blank => "blank",
+            CellKind::Boolean => "boolean",
+            CellKind::Integer => "integer",
+            CellKind::Float => "float",
+            CellKind::Date => "date",
+            CellKind::DateTime => "datetime",
+            CellKind::Time => "time",
+            CellKind::Text => "text",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+/// A raw cell reduced to what profiling needs: its kind, its text as written and, for
+/// numbers, its value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RawCell {
+    kind: CellKind,
+    text: String,
+    number: Option<f64>,
+    /// A numeric-looking string kept as text only because of its leading zero.
+    leading_zero: bool,
+}
+
+impl RawCell {
+    fn new(kind: CellKind, text: &str, number: Option<f64>) -> Self {
+        RawCell { kind, text: text.to_string(), number, leading_zero: false }
+    }
+
+    fn null() -> Self {
+        RawCell::new(CellKind::Null, "", None)
+    }
+
+    /// Classifies a plain-text cell (every CSV cell, and xlsx/ods string cells).
+    pub(crate) fn from_text(value: &str) -> Self {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return RawCell::new(CellKind::Blank, value, None);
+        }
+        let unsigned = trimmed.strip_prefix('-').unwrap_or(trimmed);
+        let all_digits = !unsigned.is_empty() && unsigned.chars().all(|c| c.is_ascii_digit());
+        if all_digits && unsigned.len() > 1 && unsigned.starts_with('0') {
+            let mut cell = RawCell::new(CellKind::Text, trimmed, None);
+            cell.leading_zero = true;
+            return cell;
+        }
+        if let Ok(i) = trimmed.parse::<i64>() {
+            return RawCell::new(CellKind::Integer, trimmed, Some(i as f64));
+        }
+        if let Ok(f) = trimmed.parse::<f64>() {
+            if f.is_finite() {
+                return RawCell::new(CellKind::Float, trimmed, Some(f));
+            }
+        }
+        if trimmed.is_truthy_standard(false).is_some() {
+            return RawCell::new(CellKind::Boolean, trimmed, None);
+        }
+        // A bare number is never a date here, matching detect.rs's own is_date_like.
+        if !all_digits {
+            let has_date_part = trimmed.contains(['/', '-']);
+            if trimmed.contains(':') {
+                if has_date_part && guess_datetime_string(trimmed).is_some() {
+                    return RawCell::new(CellKind::DateTime, trimmed, None);
+                }
+                if !has_date_part && parse_bare_time_string(trimmed).is_some() {
+                    return RawCell::new(CellKind::Time, trimmed, None);
+                }
+            } else if guess_date_string(trimmed).is_some() {
+                return RawCell::new(CellKind::Date, trimmed, None);
+            }
+        }
+        RawCell::new(CellKind::Text, trimmed, None)
+    }
+
+    /// Classifies a native xlsx/ods/xls cell, trusting its own type where it has one.
+    pub(crate) fn from_data(cell: &Data) -> Self {
+        match cell {
+            Data::Empty => RawCell::null(),
+            Data::Int(i) => RawCell::new(CellKind::Integer, &i.to_string(), Some(*i as f64)),
+            Data::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                RawCell::new(CellKind::Integer, &(*f as i64).to_string(), Some(*f))
+            }
+            Data::Float(f) => RawCell::new(CellKind::Float, &f.to_string(), Some(*f)),
+            Data::Bool(b) => RawCell::new(CellKind::Boolean, &b.to_string(), None),
+            Data::DateTime(d) => {
+                let serial = d.as_f64();
+                let kind = if serial < 1.0 {
+                    CellKind::Time
+                } else if serial.fract() == 0.0 {
+                    CellKind::Date
+                } else {
+                    CellKind::DateTime
+                };
+                let text = cell.as_datetime().map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string()).unwrap_or(cell.to_string());
+                RawCell::new(kind, &text, None)
+            }
+            Data::DateTimeIso(s) => RawCell::from_text(s),
+            Data::DurationIso(s) => RawCell::new(CellKind::Time, s, None),
+            Data::String(s) => RawCell::from_text(s),
+            Data::Error(_) => RawCell::new(CellKind::Text, &cell.to_string(), None),
+        }
+    }
+}
+
+/// Counts distinct values exactly while that stays cheap, then switches to a
+/// HyperLogLog estimate (4,096 registers, roughly 1.6% standard error).
+#[derive(Debug, Clone)]
+struct DistinctCounter {
+    exact: Option<HashSet<u64>>,
+    registers: Vec<u8>,
+}
+
+impl DistinctCounter {
+    const PRECISION: u32 = 12;
+
+    fn new() -> Self {
+        DistinctCounter { exact: Some(HashSet::new()), registers: vec![0; 1 << Self::PRECISION] }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - Self::PRECISION)) as usize;
+        let rank = ((hash << Self::PRECISION) | (1 << (Self::PRECISION - 1))).leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+        if let Some(exact) = self.exact.as_mut() {
+            exact.insert(hash);
+            if exact.len() > EXACT_DISTINCT_LIMIT {
+                self.exact = None;
+            }
+        }
+    }
+
+    fn is_approximate(&self) -> bool {
+        self.exact.is_none()
+    }
+
+    fn count(&self) -> usize {
+        if let Some(exact) = &self.exact {
+            return exact.len();
+        }
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+/// Per-column profile statistics.
+#[derive(Debug, Clone)]
+pub struct ColumnProfile {
+    /// Column position in the sheet.
+    pub index: usize,
+    /// The column's natural (un-overridden) key -- the `source_key` suggestions match on.
+    pub key: String,
+    pub nulls: usize,
+    pub blanks: usize,
+    /// Number of cells of each kind among the non-empty ones, most common first.
+    pub kinds: IndexMap<CellKind, usize>,
+    pub distinct: usize,
+    pub distinct_approximate: bool,
+    pub min: Value,
+    pub max: Value,
+    pub samples: Vec<String>,
+    /// Numeric-looking cells kept as text only because of a leading zero.
+    pub leading_zeros: usize,
+}
+
+impl ColumnProfile {
+    /// The most common kind among non-empty cells, or `Null` for an all-empty column.
+    pub fn dominant_kind(&self) -> CellKind {
+        self.kinds.first().map(|(k, _)| *k).unwrap_or(CellKind::Null)
+    }
+
+    pub fn non_empty(&self) -> usize {
+        self.kinds.values().sum()
+    }
+
+    /// The suggested `Format`: the dominant kind's, when it covers at least
+    /// `DOMINANCE_THRESHOLD` of the non-empty cells (integers and floats counting
+    /// together as numbers), `text` when the column is mixed -- or holds any code with a
+    /// leading zero that a numeric format would strip -- and `auto` when it is empty.
+    pub fn suggested_format(&self) -> Format {
+        let total = self.non_empty();
+        if total == 0 {
+            return Format::Auto;
+        }
+        if self.leading_zeros > 0 {
+            return Format::Text;
+        }
+        let share = |count: usize| count as f64 / total as f64;
+        let numeric: usize = self.kinds.iter().filter(|(k, _)| k.is_numeric()).map(|(_, n)| n).sum();
+        if share(numeric) >= DOMINANCE_THRESHOLD {
+            return if self.kinds.contains_key(&CellKind::Float) { Format::Float } else { Format::Integer };
+        }
+        let dominant = self.dominant_kind();
+        if share(self.kinds[&dominant]) < DOMINANCE_THRESHOLD {
+            return Format::Text;
+        }
+        match dominant {
+            CellKind::Boolean => Format::Truthy,
+            CellKind::Date => Format::Date,
+            CellKind::DateTime => Format::DateTime,
+            CellKind::Time => Format::Time,
+            _ => Format::Text,
+        }
+    }
+
+    /// The suggestion as `Column` JSON, ready for `OptionSet::override_columns`.
+    pub fn suggested_column(&self) -> Value {
+        json!({ "source_key": self.key, "format": self.suggested_format().to_string() })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let kinds: serde_json::Map<String, Value> = self.kinds.iter().map(|(k, n)| (k.to_string(), json!(n))).collect();
+        json!({
+            "index": self.index,
+            "key": self.key,
+            "dominant_type": self.dominant_kind().to_string(),
+            "types": kinds,
+            "nulls": self.nulls,
+            "blanks": self.blanks,
+            "distinct": self.distinct,
+            "distinct_approximate": self.distinct_approximate,
+            "min": self.min,
+            "max": self.max,
+            "samples": self.samples,
+            "suggested_format": self.suggested_format().to_string(),
+            "column": self.suggested_column(),
+        })
+    }
+}
+
+/// Accumulates a `ColumnProfile` one cell at a time.
+struct ColumnProfiler {
+    index: usize,
+    key: String,
+    nulls: usize,
+    blanks: usize,
+    kinds: IndexMap<CellKind, usize>,
+    distinct: DistinctCounter,
+    num_range: Option<(f64, f64)>,
+    text_range: Option<(String, String)>,
+    samples: Vec<String>,
+    leading_zeros: usize,
+}
+
+impl ColumnProfiler {
+    fn new(index: usize, key: &str) -> Self {
+        ColumnProfiler {
+            index,
+            key: key.to_string(),
+            nulls: 0,
+            blanks: 0,
+            kinds: IndexMap::new(),
+            distinct: DistinctCounter::new(),
+            num_range: None,
+            text_range: None,
+            samples: vec![],
+            leading_zeros: 0,
+        }
+    }
+
+    fn observe(&mut self, cell: &RawCell) {
+        match cell.kind {
+            CellKind::Null => self.nulls += 1,
+            CellKind::Blank => self.blanks += 1,
+            kind => {
+                *self.kinds.entry(kind).or_insert(0) += 1;
+                self.distinct.insert(&cell.text);
+                if cell.leading_zero {
+                    self.leading_zeros += 1;
+                }
+                if let Some(n) = cell.number {
+                    self.num_range = Some(self.num_range.map_or((n, n), |(lo, hi)| (lo.min(n), hi.max(n))));
+                } else {
+                    let text = &cell.text;
+                    self.text_range = Some(match self.text_range.take() {
+                        None => (text.clone(), text.clone()),
+                        Some((lo, hi)) => (
+                            if *text < lo { text.clone() } else { lo },
+                            if *text > hi { text.clone() } else { hi },
+                        ),
+                    });
+                }
+                if self.samples.len() < MAX_SAMPLES && !self.samples.contains(&cell.text) {
+                    self.samples.push(cell.text.clone());
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) -> ColumnProfile {
+        self.kinds.sort_by(|_, a, _, b| b.cmp(a));
+        let numeric_dominant = self.kinds.first().is_some_and(|(k, _)| k.is_numeric());
+        let to_number = |n: f64| {
+            if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+                Value::Number(Number::from(n as i64))
+            } else {
+                Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+            }
+        };
+        let (min, max) = match (numeric_dominant, &self.num_range, &self.text_range) {
+            (true, Some((lo, hi)), _) => (to_number(*lo), to_number(*hi)),
+            (_, _, Some((lo, hi))) => (json!(lo), json!(hi)),
+            (_, Some((lo, hi)), None) => (to_number(*lo), to_number(*hi)),
+            _ => (Value::Null, Value::Null),
+        };
+        ColumnProfile {
+            index: self.index,
+            key: self.key,
+            nulls: self.nulls,
+            blanks: self.blanks,
+            kinds: self.kinds,
+            distinct: self.distinct.count(),
+            distinct_approximate: self.distinct.is_approximate(),
+            min,
+            max,
+            samples: self.samples,
+            leading_zeros: self.leading_zeros,
+        }
+    }
+}
+
+/// Profiles `rows` of raw cells against the columns named by `keys`.
+pub(crate) fn profile_raw_rows(keys: &[String], rows: &[Vec<RawCell>]) -> Vec<ColumnProfile> {
+    let null = RawCell::null();
+    let mut profilers: Vec<ColumnProfiler> = keys.iter().enumerate().map(|(i, k)| ColumnProfiler::new(i, k)).collect();
+    for row in rows {
+        for profiler in profilers.iter_mut() {
+            profiler.observe(row.get(profiler.index).unwrap_or(&null));
+        }
+    }
+    profilers.into_iter().map(ColumnProfiler::finish).collect()
+}
+
+/// The result of profiling one sheet.
+#[derive(Debug, Clone)]
+pub struct ProfileSet {
+    pub filename: String,
+    pub sheet: Option<String>,
+    /// Number of data rows profiled.
+    pub num_rows: usize,
+    pub columns: Vec<ColumnProfile>,
+}
+
+impl ProfileSet {
+    /// The suggested `Column` JSON for every column, ready for `override_columns`.
+    pub fn suggested_columns(&self) -> Vec<Value> {
+        self.columns.iter().map(|c| c.suggested_column()).collect()
+    }
+
+    /// The suggested `Format` for each column, by natural key.
+    pub fn suggested_formats(&self) -> IndexMap<String, Format> {
+        self.columns.iter().map(|c| (c.key.clone(), c.suggested_format())).collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "filename": self.filename,
+            "sheet": self.sheet,
+            "num_rows": self.num_rows,
+            "columns": self.columns.iter().map(|c| c.to_json()).collect::<Vec<Value>>(),
+        })
+    }
+}
+
+/// A sheet's natural column keys plus up to `limit` of its data rows as raw cells,
+/// with the header row(s) and any rows above the first data row resolved exactly as a
+/// normal read would (explicit `header_row`/`data_row_index`/`header_row_span`, or
+/// `detect_header`).
+pub(crate) struct RawSample {
+    pub filename: String,
+    pub sheet: Option<String>,
+    pub keys: Vec<String>,
+    pub rows: Vec<Vec<RawCell>>,
+}
+
+pub(crate) fn sample_from_rows(opts: &OptionSet, text_rows: &[Vec<String>], raw_rows: Vec<Vec<RawCell>>, limit: usize) -> (Vec<String>, Vec<Vec<RawCell>>) {
+    let detected = resolve_header_and_data_rows(opts, || text_rows.iter().take(DETECT_SAMPLE_SIZE).cloned().collect());
+    let span = opts.effective_header_row_span();
+    let header_text: Vec<String> = match detected.header_index {
+        Some(index) if span > 1 => combine_header_rows(&text_rows.iter().skip(index).take(span).cloned().collect::<Vec<_>>()),
+        Some(index) => text_rows.get(index).cloned().unwrap_or_default(),
+        None => vec![],
+    };
+    let keys = if detected.header_index.is_some() {
+        natural_column_keys(&header_text, &opts.field_mode)
+    } else {
+        let width = text_rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        natural_column_keys(&vec![String::new(); width], &opts.field_mode.forced_fallback())
+    };
+    let rows = raw_rows
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx >= detected.data_index)
+        .filter(|(idx, _)| detected.header_index.is_none() || is_not_header_row(&text_rows[*idx], *idx, &header_text))
+        .map(|(_, row)| row)
+        .take(limit)
+        .collect();
+    (keys, rows)
+}
+
+/// Rows to read for a sample of `limit` data rows, allowing for the rows above the first
+/// data row that header resolution needs too.
+pub(crate) fn sample_row_limit(opts: &OptionSet, limit: usize) -> usize {
+    limit + opts.header_row_index() + opts.effective_header_row_span() + DETECT_SAMPLE_SIZE
+}
+
+/// Reads a raw sample of up to `limit` data rows from the first selected sheet (or the
+/// CSV/TSV file) named by `opts`, through the same cell range, table source and
+/// orientation a read would use.
+pub(crate) fn sample_raw_rows(opts: &OptionSet, limit: usize) -> Result<RawSample, GenericError> {
+    let Some(filepath) = opts.path.clone() else {
+        return Err(GenericError("no_filepath_specified"));
+    };
+    let path = Path::new(&filepath);
+    if !path.exists() {
+        return Err(GenericError("file_unavailable"));
+    }
+    let path_data = PathData::new(path);
+    if !path_data.is_valid() {
+        return Err(GenericError("unsupported_format"));
+    }
+    let filename = path_data.filename();
+    let row_limit = sample_row_limit(opts, limit);
+    if path_data.use_calamine() {
+        let mut workbook: Sheets<BufReader<File>> = open_workbook_auto(path).map_err(|_| GenericError("cannot_open_workbook"))?;
+        let (sheet_name, opts) = resolve_sample_sheet(&mut workbook, opts)?;
+        let sheet_range = workbook.worksheet_range(&sheet_name)?;
+        let (range, _) = restrict_to_cell_range(&sheet_range, &opts)?;
+        let (range, _) = orient_range(range, &opts);
+        let text_rows: Vec<Vec<String>> = text_rows(&range, 0).take(row_limit).collect();
+        let raw_rows: Vec<Vec<RawCell>> = range.rows().take(row_limit).map(|row| row.iter().map(RawCell::from_data).collect()).collect();
+        let (keys, rows) = sample_from_rows(&opts, &text_rows, raw_rows, limit);
+        Ok(RawSample { filename, sheet: Some(sheet_name), keys, rows })
+    } else {
+        let text_rows = csv_sample_lines(&path_data, opts, row_limit)?;
+        let raw_rows: Vec<Vec<RawCell>> = text_rows.iter().map(|row| row.iter().map(|s| RawCell::from_text(s)).collect()).collect();
+        let (keys, rows) = sample_from_rows(opts, &text_rows, raw_rows, limit);
+        Ok(RawSample { filename, sheet: None, keys, rows })
+    }
+}
+
+/// Profiles up to `opts.max_rows()` data rows of the first selected sheet (or the
+/// CSV/TSV file) named by `opts`. Column overrides in `opts` are ignored -- the point is
+/// to suggest them.
+pub fn profile_spreadsheet(opts: &OptionSet) -> Result<ProfileSet, GenericError> {
+    let sample = sample_raw_rows(opts, opts.max_rows())?;
+    let columns = profile_raw_rows(&sample.keys, &sample.rows);
+    Ok(ProfileSet {
+        filename: sample.filename,
+        sheet: sample.sheet,
+        num_rows: sample.rows.len(),
+        columns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_csv_fixture(filename: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(filename);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_classifies_raw_text_cells() {
+        assert_eq!(RawCell::from_text("42").kind, CellKind::Integer);
+        assert_eq!(RawCell::from_text("-4.5").kind, CellKind::Float);
+        assert_eq!(RawCell::from_text("02134").kind, CellKind::Text);
+        assert_eq!(RawCell::from_text("0").kind, CellKind::Integer);
+        assert_eq!(RawCell::from_text("yes").kind, CellKind::Boolean);
+        assert_eq!(RawCell::from_text("2023-06-15").kind, CellKind::Date);
+        assert_eq!(RawCell::from_text("15/06/23 10:17").kind, CellKind::DateTime);
+        assert_eq!(RawCell::from_text("10:17").kind, CellKind::Time);
+        assert_eq!(RawCell::from_text("  ").kind, CellKind::Blank);
+        assert_eq!(RawCell::from_text("SKU001").kind, CellKind::Text);
+    }
+
+    #[test]
+    fn test_profile_reports_stats_and_suggests_formats() {
+        let path = write_csv_fixture(
+            "profile_mixed.csv",
+            "id,zip,score,active,joined,note\n1,02134,1.5,yes,2023-06-15,a\n2,90210,2,no,2023-06-16,\n3,10001,3.25,yes,2023-07-01,b\n4,30301,,no,2023-07-02,12\n",
+        );
+        let profile = profile_spreadsheet(&OptionSet::new(&path)).unwrap();
+        assert_eq!(profile.num_rows, 4);
+        let formats = profile.suggested_formats();
+        assert_eq!(formats["id"].to_string(), Format::Integer.to_string());
+        assert_eq!(formats["zip"].to_string(), Format::Text.to_string(), "a leading-zero code keeps the whole column as text");
+        assert_eq!(formats["score"].to_string(), Format::Float.to_string());
+        assert_eq!(formats["active"].to_string(), Format::Truthy.to_string());
+        assert_eq!(formats["joined"].to_string(), Format::Date.to_string());
+        assert_eq!(formats["note"].to_string(), Format::Text.to_string(), "mixed text and numbers fall back to text");
+
+        let score = &profile.columns[2];
+        assert_eq!(score.blanks, 1);
+        assert_eq!(score.dominant_kind(), CellKind::Float);
+        assert_eq!((score.min.clone(), score.max.clone()), (serde_json::json!(1.5), serde_json::json!(3.25)));
+        let id = &profile.columns[0];
+        assert_eq!(id.distinct, 4);
+        assert!(!id.distinct_approximate);
+        assert_eq!(id.samples, vec!["1", "2", "3", "4"]);
+        assert_eq!(profile.suggested_columns()[1], serde_json::json!({"source_key": "zip", "format": "text"}));
+    }
+
+    #[test]
+    fn test_suggested_columns_round_trip_through_override_columns() {
+        let opts = OptionSet::new("data/sample-data-1.xlsx");
+        let profile = profile_spreadsheet(&opts).unwrap();
+        let columns = profile.suggested_columns();
+        let result = crate::process_spreadsheet_direct(&opts.clone().override_columns(&columns)).unwrap();
+        assert_eq!(result.columns.len(), profile.columns.len());
+        for (col, prof) in result.columns.iter().zip(profile.columns.iter()) {
+            assert_eq!(col.format.to_string(), prof.suggested_format().to_string());
+        }
+    }
+
+    #[test]
+    fn test_distinct_counter_switches_to_estimate_for_large_samples() {
+        let mut counter = DistinctCounter::new();
+        for i in 0..50_000 {
+            counter.insert(&i.to_string());
+        }
+        assert!(counter.is_approximate());
+        let estimate = counter.count() as f64;
+        assert!((estimate - 50_000.0).abs() / 50_000.0 < 0.05, "estimate {} too far off", estimate);
+    }
+}
@@ -1,16 +1,31 @@
-use calamine::{open_workbook_auto, Data, Reader, Sheets};
+use calamine::{open_workbook_auto, Data, Range, Reader, Sheets};
 use csv::{ReaderBuilder, StringRecord};
 use heck::ToSnakeCase;
 use indexmap::IndexMap;
 use serde_json::{Number, Value};
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 use crate::data_set::*;
-use crate::detect::{resolve_header_and_data_rows, DETECT_SAMPLE_SIZE};
+use crate::detect::{detect_data_end, detect_header_rows, resolve_data_end, resolve_header_and_data_rows, resolve_orientation, HeaderDetection, DETECT_SAMPLE_SIZE};
 use crate::error::GenericError;
+use crate::profile::{profile_raw_rows, sample_from_rows, sample_row_limit, RawCell};
+use crate::derived::insert_derived_columns;
+use crate::aggregate::AggregateStream;
+use crate::reshape::{transform_rows, GroupStream};
+use crate::sort::{DedupStream, ExternalSorter};
+use crate::lookup::load_lookups;
+use crate::sheets::{select_worksheets, MatchedSheets};
+use crate::workbook::{read_defined_names, read_sheet_info, DocumentProperties};
+use crate::cell_range::CellRange;
+use crate::tables::{read_table_info, resolve_range_source};
+use crate::segment::{segment_tables, TableSegmentation};
+use crate::form::{locate_form_values, FormExtraction, FormLocator};
+use crate::options::{ReadMode, DEFAULT_MAX_ROWS};
 use alphanumeric::*;
 use simple_string_patterns::SimpleMatch;
 use crate::headers::*;
@@ -21,6 +36,7 @@ use crate::round_decimal::RoundDecimal;
 use crate::DateTimeMode;
 use crate::Extension;
 use to_segments::ToSegments;
+use crate::Column;
 use crate::Format;
 use crate::OptionSet;
 use crate::PathData;
@@ -30,6 +46,9 @@ use fuzzy_datetime::{fuzzy_to_date_string, fuzzy_to_datetime_string_opts, iso_fu
 /// Callback invoked once per row when saving asynchronously (e.g. --deferred mode)
 pub type SaveRowFn = Box<dyn Fn(IndexMap<String, Value>) -> Result<(), GenericError> + Send + Sync>;
 
+/// The callback behind a `SaveRowFn`, borrowed by per-sheet readers.
+type SaveRowCallback<'a> = dyn Fn(IndexMap<String, Value>) -> Result<(), GenericError> + Send + Sync + 'a;
+
 /// Output the result set with captured rows (up to the maximum allowed) directly.
 /// This is now synchronous and calls the asynchronous function using a runtime.
 pub fn process_spreadsheet_direct(opts: &OptionSet) -> Result<ResultSet, GenericError> {
@@ -76,11 +95,26 @@ pub async fn process_spreadsheet_core(
         }
         let path_data = PathData::new(path);
         if path_data.is_valid() {
-            if path_data.use_calamine() {
-                read_workbook_core(&path_data, opts, save_opt, out_ref).await
+            let joined;
+            let opts = if opts.lookups.is_empty() {
+                opts
             } else {
-                read_csv_core(&path_data, opts, save_opt, out_ref).await
-            }
+                joined = load_lookups(opts).await?;
+                &joined
+            };
+            let result = if opts.transforms_row_set() {
+                read_and_transform_row_set(&path_data, opts, save_opt, out_ref).await?
+            } else if path_data.use_calamine() {
+                read_workbook_core(&path_data, opts, save_opt, out_ref).await?
+            } else {
+                read_csv_core(&path_data, opts, save_opt, out_ref).await?
+            };
+            let result = match &opts.unpivot {
+                // Row-set stages take their keys from the unpivoted rows themselves
+                Some(unpivot) if !opts.transforms_row_set() => result.with_unpivot_keys(unpivot),
+                _ => result,
+            };
+            Ok(result.with_unmatched_keys(opts.unmatched_lookup_keys()))
         } else {
             Err(GenericError("unsupported_format"))
         }
@@ -89,6 +123,228 @@ pub async fn process_spreadsheet_core(
     }
 }
 
+/// Reads with the whole-row-set stages configured on `opts` (see
+/// `OptionSet::transforms_row_set`) applied once reading is done. Captured rows are
+/// transformed in place. Rows for a save callback are buffered, transformed, then saved
+/// when a pivot, aggregation or unsorted grouping needs the whole set. Otherwise they
+/// stream through `RowStream`: de-duplication and sorted grouping pass rows on as they go,
+/// and sorting spills chunks to temporary files.
+async fn read_and_transform_row_set(
+    path_data: &PathData<'_>,
+    opts: &OptionSet,
+    save_opt: Option<SaveRowFn>,
+    out_ref: Option<&str>,
+) -> Result<ResultSet, GenericError> {
+    if opts.aggregate.is_some() && !opts.buffers_row_set() && !opts.multimode() {
+        return read_and_aggregate(path_data, opts, save_opt, out_ref).await;
+    }
+    // Saved rows are tallied so the result can describe them, as it would captured rows
+    let tally: Arc<Mutex<SavedRowTally>> = Arc::default();
+    let save_opt: Option<Arc<SaveRowFn>> = save_opt.map(|save_method| {
+        let tally = tally.clone();
+        let tallied: SaveRowFn = Box::new(move |row| {
+            tally.lock().map_err(|_| GenericError("row_buffer_error"))?.add(&row);
+            save_method(row)
+        });
+        Arc::new(tallied)
+    });
+    let buffer: Arc<Mutex<Vec<IndexMap<String, Value>>>> = Arc::new(Mutex::new(Vec::new()));
+    let stream = (!opts.buffers_row_set()).then(|| Arc::new(Mutex::new(RowStream::new(opts))));
+    let staged_save = save_opt.as_ref().map(|save_method| {
+        let save_method = save_method.clone();
+        let buffer = buffer.clone();
+        let stream = stream.clone();
+        let save: SaveRowFn = Box::new(move |row| match &stream {
+            Some(stream) => stream.lock().map_err(|_| GenericError("row_buffer_error"))?.push(row, &save_method),
+            None => {
+                buffer.lock().map_err(|_| GenericError("row_buffer_error"))?.push(row);
+                Ok(())
+            }
+        });
+        save
+    });
+    let result = if path_data.use_calamine() {
+        read_workbook_core(path_data, opts, staged_save, out_ref).await?
+    } else {
+        read_csv_core(path_data, opts, staged_save, out_ref).await?
+    };
+    if let Some(save_method) = &save_opt {
+        if let Some(stream) = stream {
+            stream.lock().map_err(|_| GenericError("row_buffer_error"))?.finish(save_method)?;
+        } else {
+            let rows = std::mem::take(&mut *buffer.lock().map_err(|_| GenericError("row_buffer_error"))?);
+            for row in transform_rows(opts, rows) {
+                save_method(row)?;
+            }
+        }
+    }
+    let result = result.map_row_set(|rows| transform_rows(opts, rows));
+    if save_opt.is_some() && !opts.capture_rows() {
+        let tally = std::mem::take(&mut *tally.lock().map_err(|_| GenericError("row_buffer_error"))?);
+        return Ok(result.with_saved_row_set(tally.count, tally.keys.into_iter().collect()));
+    }
+    Ok(result)
+}
+
+/// Rows passed to a save callback after the row-set stages, and their top-level keys.
+#[derive(Default)]
+struct SavedRowTally {
+    count: usize,
+    keys: indexmap::IndexSet<String>,
+}
+
+impl SavedRowTally {
+    fn add(&mut self, row: &IndexMap<String, Value>) {
+        self.count += 1;
+        for key in row.keys() {
+            if !self.keys.contains(key) {
+                self.keys.insert(key.clone());
+            }
+        }
+    }
+}
+
+/// Reads a single data set with every row folded into `opts.aggregate` as it is read,
+/// rather than only the rows a capture would keep. Only an explicit `max` caps the rows
+/// read. The summary rows are then captured or saved like any other rows.
+async fn read_and_aggregate(
+    path_data: &PathData<'_>,
+    opts: &OptionSet,
+    save_opt: Option<SaveRowFn>,
+    out_ref: Option<&str>,
+) -> Result<ResultSet, GenericError> {
+    let summary: Arc<Mutex<Vec<IndexMap<String, Value>>>> = Arc::new(Mutex::new(Vec::new()));
+    let collect: Arc<SaveRowFn> = {
+        let summary = summary.clone();
+        Arc::new(Box::new(move |row| {
+            summary.lock().map_err(|_| GenericError("row_buffer_error"))?.push(row);
+            Ok(())
+        }))
+    };
+    let stream = Arc::new(Mutex::new(RowStream::new(opts)));
+    let fold: SaveRowFn = {
+        let stream = stream.clone();
+        let collect = collect.clone();
+        Box::new(move |row| stream.lock().map_err(|_| GenericError("row_buffer_error"))?.push(row, &collect))
+    };
+    let mut read_opts = opts.clone();
+    read_opts.read_mode = ReadMode::Async;
+    read_opts.max = opts.max.or(Some(u32::MAX));
+    let result = if path_data.use_calamine() {
+        read_workbook_core(path_data, &read_opts, Some(fold), out_ref).await?
+    } else {
+        read_csv_core(path_data, &read_opts, Some(fold), out_ref).await?
+    };
+    stream.lock().map_err(|_| GenericError("row_buffer_error"))?.finish(&collect)?;
+    let rows = std::mem::take(&mut *summary.lock().map_err(|_| GenericError("row_buffer_error"))?);
+    if let Some(save_method) = save_opt {
+        for row in rows.iter().cloned() {
+            save_method(row)?;
+        }
+    }
+    Ok(result.with_row_set(rows, opts))
+}
+
+/// The streaming row-set stages in `transform_rows` order: de-duplication, grouping of
+/// sorted input, aggregation, then an external sort in front of the save callback.
+struct RowStream {
+    dedup: Option<DedupStream>,
+    group: Option<GroupStream>,
+    aggregate: Option<AggregateStream>,
+    sorter: Option<ExternalSorter>,
+}
+
+impl RowStream {
+    fn new(opts: &OptionSet) -> Self {
+        RowStream {
+            dedup: opts.dedup.clone().map(DedupStream::new),
+            group: opts.group.clone().map(GroupStream::new),
+            aggregate: opts.aggregate.clone().map(AggregateStream::new),
+            sorter: opts.sort.clone().map(ExternalSorter::new),
+        }
+    }
+
+    fn push(&mut self, row: IndexMap<String, Value>, save_method: &SaveRowFn) -> Result<(), GenericError> {
+        let row = match &mut self.dedup {
+            Some(dedup) => dedup.push(row),
+            None => Some(row),
+        };
+        match row {
+            Some(row) => self.push_deduplicated(row, save_method),
+            None => Ok(()),
+        }
+    }
+
+    fn push_deduplicated(&mut self, row: IndexMap<String, Value>, save_method: &SaveRowFn) -> Result<(), GenericError> {
+        let row = match &mut self.group {
+            Some(group) => group.push(row),
+            None => Some(row),
+        };
+        match row {
+            Some(row) => self.push_grouped(row, save_method),
+            None => Ok(()),
+        }
+    }
+
+    fn push_grouped(&mut self, row: IndexMap<String, Value>, save_method: &SaveRowFn) -> Result<(), GenericError> {
+        if let Some(aggregate) = &mut self.aggregate {
+            aggregate.push(&row);
+            return Ok(());
+        }
+        match &mut self.sorter {
+            Some(sorter) => sorter.push(row),
+            None => save_method(row),
+        }
+    }
+
+    fn finish(&mut self, save_method: &SaveRowFn) -> Result<(), GenericError> {
+        let held = self.dedup.as_mut().map(|dedup| dedup.finish()).unwrap_or_default();
+        for row in held {
+            self.push_deduplicated(row, save_method)?;
+        }
+        if let Some(parent) = self.group.as_mut().and_then(|group| group.finish()) {
+            self.push_grouped(parent, save_method)?;
+        }
+        if let Some(mut aggregate) = self.aggregate.take() {
+            for row in aggregate.finish() {
+                self.push_grouped(row, save_method)?;
+            }
+        }
+        if let Some(sorter) = self.sorter.take() {
+            for row in sorter.finish()? {
+                save_method(row?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns a copy of `opts` whose column overrides assign every `Format::Auto` column the
+/// format profiled from the first `sample_size` data rows (see `OptionSet::infer_sample`)
+/// of `text_rows`/`raw_rows` -- the lines actually being read, after any cell range,
+/// table source and orientation have been applied. The overrides come back resolved to
+/// one column per natural key, in sheet order, so positional and `source_key` overrides
+/// alike keep their explicit formats. Inferred columns default to null, so the odd cell
+/// that doesn't fit the column's format ("n/a" in a numeric column) becomes null rather
+/// than a stray value of another type.
+fn with_inferred_formats(opts: &OptionSet, sample_size: usize, text_rows: &[Vec<String>], raw_rows: Vec<Vec<RawCell>>) -> OptionSet {
+    let (keys, rows) = sample_from_rows(opts, text_rows, raw_rows, sample_size);
+    let profiles = profile_raw_rows(&keys, &rows);
+    let mut columns = resolve_columns(&opts.rows.columns, &keys);
+    for (col, profile) in columns.iter_mut().zip(profiles.iter()) {
+        if matches!(col.format, Format::Auto) {
+            col.format = profile.suggested_format();
+            if col.default.is_none() {
+                col.default = Some(Value::Null);
+            }
+        }
+    }
+    let mut inferred = opts.clone();
+    inferred.rows.columns = columns;
+    inferred.rows.inferred_types = true;
+    inferred
+}
+
 #[deprecated(
     since = "1.0.6",
     note = "This function is a wrapper for the renamed function `process_spreadsheet_core`"
@@ -111,14 +367,45 @@ pub async fn read_workbook_core<'a>(
 ) -> Result<ResultSet, GenericError> {
     if let Ok(mut workbook) = open_workbook_auto(path_data.path()) {
         let max_rows = opts.max_rows();
-        let (selected_names, sheet_names, _sheet_indices) =
-            match_sheet_name_and_index(&mut workbook, opts);
+        if let Some((sheet_name, source_opts)) = resolve_range_source(&mut workbook, opts)? {
+            let info = WorkbookInfo::new(path_data, std::slice::from_ref(&sheet_name), &workbook.sheet_names());
+            return read_single_worksheet(workbook, &sheet_name, &source_opts, &info, save_opt, out_ref).await;
+        }
+        let MatchedSheets { selected: selected_names, sheet_names, eligible, .. } =
+            select_worksheets(&mut workbook, opts)?;
 
         if !selected_names.is_empty() {
             let info = WorkbookInfo::new(path_data, &selected_names, &sheet_names);
+            // Each table becomes its own data set, which multi-sheet and union reads
+            // have no place for
+            if opts.segmentation.is_some() && (opts.union.is_some() || opts.multimode()) {
+                return Err(GenericError("split_tables_with_multiple_sheets"));
+            }
+            // A form is one object per read, taken from a single sheet
+            if opts.form.is_some() && (opts.union.is_some() || opts.multimode()) {
+                return Err(GenericError("form_with_multiple_sheets"));
+            }
 
-            if opts.multimode() {
-                read_multiple_worksheets(&mut workbook, &sheet_names, opts, &info, max_rows).await
+            if let Some(union) = &opts.union {
+                let matched: Vec<String> = sheet_names
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, name)| union.matches(*index, name) && eligible.contains(name))
+                    .map(|(_, name)| name.clone())
+                    .collect();
+                if matched.is_empty() {
+                    return Err(GenericError("no_matching_sheets"));
+                }
+                let info = WorkbookInfo::new(path_data, &matched, &sheet_names);
+                read_sheet_union(&mut workbook, &matched, opts, &info, save_opt, out_ref)
+            } else if opts.read_mode().is_preview() {
+                read_multiple_worksheets(&mut workbook, &eligible, opts, &info, max_rows).await
+            } else if opts.multimode() {
+                let targets = if opts.reads_all_sheets() { &eligible } else { &selected_names };
+                let info = WorkbookInfo::new(path_data, targets, &sheet_names);
+                read_all_worksheets(&mut workbook, targets, opts, &info, save_opt, out_ref)
+            } else if let Some(segmentation) = &opts.segmentation {
+                read_segmented_tables(&mut workbook, &selected_names[0], opts, segmentation, &info, save_opt, out_ref)
             } else {
                 let sheet_ref = &selected_names[0];
                 read_single_worksheet(workbook, sheet_ref, opts, &info, save_opt, out_ref).await
@@ -141,12 +428,19 @@ async fn read_multiple_worksheets(
 ) -> Result<ResultSet, GenericError> {
     let mut sheets: Vec<SheetDataSet> = vec![];
     let capture_rows = opts.capture_rows();
+    // With a row filter or unpivot, the row cap applies to output rows rather than to rows read
+    let count_kept = capture_rows && opts.counts_output_rows();
+    let mut filtered_count: usize = 0;
     for (sheet_index, sheet_ref) in sheet_names.iter().enumerate() {
-        let range = workbook.worksheet_range(&sheet_ref.clone())?;
+        let sheet_range = workbook.worksheet_range(&sheet_ref.clone())?;
+        let (range, _) = restrict_to_cell_range(&sheet_range, opts)?;
+        let first_sheet_row = first_sheet_row(&range);
+        let (range, transposed) = orient_range(range, opts);
+        let first_sheet_row = if transposed { 0 } else { first_sheet_row };
         let mut headers: Vec<String> = vec![];
         let mut has_headers = false;
         let mut rows: Vec<IndexMap<String, Value>> =
-            Vec::with_capacity(if capture_rows { max_rows } else { 0 });
+            Vec::with_capacity(if capture_rows { max_rows.min(DEFAULT_MAX_ROWS) } else { 0 });
         let mut row_index = 0;
         let detected = resolve_header_and_data_rows(opts, || {
             range.rows().take(DETECT_SAMPLE_SIZE)
@@ -183,19 +477,28 @@ async fn read_multiple_worksheets(
             has_headers = true;
         }
         let total = range.get_size().0;
+        let data_end = resolve_data_end(opts, first_data_row_index, total, || {
+            detect_data_end(text_rows(&range, first_data_row_index), first_data_row_index, opts.footer_blank_run())
+        })
+        .unwrap_or(total);
         if capture_rows || match_header_row_below {
             let max_row_count = if capture_rows {
                 max_rows
             } else {
                 header_row_index + 2
             };
-            let max_take = if total < max_row_count {
+            let max_take = if total < max_row_count || count_kept {
                 total
             } else {
                 max_row_count + 1
             };
             for row in range.rows().take(max_take) {
-                if row_index > max_row_count {
+                let limit_reached = if count_kept {
+                    rows.len() >= max_rows
+                } else {
+                    row_index > max_row_count
+                } || row_index >= data_end;
+                if limit_reached {
                     break;
                 }
                 if match_header_row_below && row_index == header_row_index {
@@ -217,16 +520,236 @@ async fn read_multiple_worksheets(
                         true
                     };
                     if is_real_data {
-                        let row_map = workbook_row_to_map(row, &resolved_row_opts, &headers);
-                        rows.push(row_map);
+                        let row_map = workbook_row_to_map(row, &resolved_row_opts, &headers, first_sheet_row + row_index, sheet_ref);
+                        for row_map in opts.output_rows(row_map) {
+                            if count_kept && rows.len() >= max_rows {
+                                break;
+                            }
+                            if opts.keeps_row(&row_map) {
+                                rows.push(row_map);
+                            } else {
+                                filtered_count += 1;
+                            }
+                        }
                     }
                 }
                 row_index += 1;
             }
         }
-        sheets.push(SheetDataSet::new(sheet_ref, &headers, &rows, total));
+        sheets.push(SheetDataSet::new(sheet_ref, &headers, &rows, total).with_indices(detected.header_index, first_data_row_index));
+    }
+    Ok(ResultSet::from_multiple(&sheets, info, opts).with_filtered_count(filtered_count))
+}
+
+/// Reads each of `sheet_names` in full for the multi-sheet modes, with its own
+/// `SheetOptions` and header detection. Rows passed to `save_opt` are tagged with their
+/// sheet name under `OptionSet::sheet_tag_key`.
+fn read_all_worksheets(
+    workbook: &mut Sheets<BufReader<File>>,
+    sheet_names: &[String],
+    opts: &OptionSet,
+    info: &WorkbookInfo,
+    save_opt: Option<SaveRowFn>,
+    out_ref: Option<&str>,
+) -> Result<ResultSet, GenericError> {
+    let mut sheets: Vec<SheetDataSet> = vec![];
+    let mut filtered_count: usize = 0;
+    let tag = opts.sheet_tag_key();
+    for sheet_ref in sheet_names {
+        let sheet_opts = opts.for_sheet(sheet_ref);
+        let tagged_save = save_opt.as_ref().map(|save_method| {
+            move |mut row: IndexMap<String, Value>| {
+                row.shift_insert(0, tag.to_string(), Value::String(sheet_ref.clone()));
+                save_method(row)
+            }
+        });
+        let sheet = read_worksheet(
+            workbook,
+            sheet_ref,
+            &sheet_opts,
+            tagged_save.as_ref().map(|save| save as &SaveRowCallback<'_>),
+        )?;
+        filtered_count += sheet.filtered_count;
+        sheets.push(
+            SheetDataSet::new(sheet_ref, &sheet.headers, &sheet.rows, sheet.total)
+                .with_indices(sheet.header_row_index, sheet.body_start_index),
+        );
+    }
+    let mut result = ResultSet::from_multiple(&sheets, info, opts).with_filtered_count(filtered_count);
+    result.selected = Some(sheet_names.to_vec());
+    result.out_ref = out_ref.map(|s| s.to_string());
+    Ok(result)
+}
+
+/// Reads every table found on one worksheet (see `segment.rs`) as its own data set, named
+/// after its title or position. Each table is read within its own range with the header
+/// found during segmentation. Rows passed to `save_opt` are tagged with the table name
+/// under `OptionSet::sheet_tag_key`.
+fn read_segmented_tables(
+    workbook: &mut Sheets<BufReader<File>>,
+    sheet_ref: &str,
+    opts: &OptionSet,
+    segmentation: &TableSegmentation,
+    info: &WorkbookInfo,
+    save_opt: Option<SaveRowFn>,
+    out_ref: Option<&str>,
+) -> Result<ResultSet, GenericError> {
+    let sheet_range = workbook.worksheet_range(sheet_ref)?;
+    let (range, _) = restrict_to_cell_range(&sheet_range, opts)?;
+    let text_rows: Vec<Vec<String>> = range
+        .rows()
+        .map(|row| row.iter().map(|c| c.to_string()).collect())
+        .collect();
+    let tables = segment_tables(&text_rows, range.start().unwrap_or((0, 0)), segmentation);
+    let mut sheets: Vec<SheetDataSet> = vec![];
+    let mut filtered_count: usize = 0;
+    let tag = opts.sheet_tag_key();
+    for (index, table) in tables.iter().enumerate() {
+        let name = table.name(sheet_ref, index, segmentation);
+        let mut table_opts = opts.clone();
+        table_opts.segmentation = None;
+        // As in multi-sheet reads, positional column overrides only fit the first table.
+        // Overrides naming a `source_key` apply wherever that column is found.
+        if index > 0 {
+            table_opts.rows.columns.retain(|col| col.source_key.is_some());
+        }
+        table_opts.cell_range = Some(table.range.to_string());
+        table_opts.detect_header = false;
+        table_opts.header_row_span = 1;
+        table_opts.header_row = table.header_row_index;
+        table_opts.omit_header = table.header_row_index.is_none();
+        table_opts.data_row_index = Some(table.data_row_index);
+        let tagged_save = save_opt.as_ref().map(|save_method| {
+            let name = name.clone();
+            move |mut row: IndexMap<String, Value>| {
+                row.shift_insert(0, tag.to_string(), Value::String(name.clone()));
+                save_method(row)
+            }
+        });
+        let read = read_worksheet_range(
+            &sheet_range,
+            sheet_ref,
+            &table_opts,
+            tagged_save.as_ref().map(|save| save as &SaveRowCallback<'_>),
+        )?;
+        filtered_count += read.filtered_count;
+        sheets.push(
+            SheetDataSet::new(&name, &read.headers, &read.rows, read.total)
+                .with_indices(read.header_row_index, read.body_start_index),
+        );
     }
-    Ok(ResultSet::from_multiple(&sheets, info, opts))
+    let mut result = ResultSet::from_multiple(&sheets, info, opts).with_filtered_count(filtered_count);
+    result.selected = Some(vec![sheet_ref.to_string()]);
+    result.out_ref = out_ref.map(|s| s.to_string());
+    Ok(result)
+}
+
+/// Appends `sheet_names` into one flat data set (see `SheetUnion`). Positional column
+/// overrides are pinned to the first sheet's natural keys, so they follow a column to
+/// wherever it sits in later sheets. Captured rows are aligned to the union of all keys,
+/// with nulls for missing fields; rows saved asynchronously keep their own sheet's keys.
+/// `max_row_count` caps the combined rows.
+fn read_sheet_union(
+    workbook: &mut Sheets<BufReader<File>>,
+    sheet_names: &[String],
+    opts: &OptionSet,
+    info: &WorkbookInfo,
+    save_opt: Option<SaveRowFn>,
+    out_ref: Option<&str>,
+) -> Result<ResultSet, GenericError> {
+    let tag = opts.sheet_tag_key();
+    let max_rows = opts.max_rows();
+    let mut rows: Vec<IndexMap<String, Value>> = vec![];
+    let mut keys: IndexMap<String, ()> = IndexMap::new();
+    keys.insert(tag.to_string(), ());
+    let mut anchored: Option<Vec<Column>> = None;
+    let mut first: Option<(String, Vec<String>, Option<usize>, usize)> = None;
+    let mut warnings: Vec<String> = vec![];
+    let (mut total, mut saved, mut filtered_count) = (0, 0, 0);
+    for sheet_ref in sheet_names {
+        let remaining = max_rows.saturating_sub(if opts.capture_rows() { rows.len() } else { saved });
+        if remaining == 0 {
+            break;
+        }
+        let mut sheet_opts = opts.for_sheet(sheet_ref);
+        sheet_opts.max = Some(remaining.min(u32::MAX as usize) as u32);
+        let own_columns = opts.sheet_options.get(&sheet_ref.to_snake_case()).is_some_and(|o| o.columns.is_some());
+        if let (Some(columns), false) = (&anchored, own_columns) {
+            sheet_opts.rows.columns = columns.clone();
+        }
+        let tagged_save = save_opt.as_ref().map(|save_method| {
+            move |mut row: IndexMap<String, Value>| {
+                row.shift_insert(0, tag.to_string(), Value::String(sheet_ref.clone()));
+                save_method(row)
+            }
+        });
+        let sheet = read_worksheet(workbook, sheet_ref, &sheet_opts, tagged_save.as_ref().map(|save| save as &SaveRowCallback<'_>))?;
+        if anchored.is_none() {
+            anchored = Some(anchor_columns(&opts.rows.columns, &sheet.natural_keys));
+        }
+        match &first {
+            None => first = Some((sheet_ref.clone(), sheet.natural_keys.clone(), sheet.header_row_index, sheet.body_start_index)),
+            Some((first_name, first_keys, ..)) => {
+                if let Some(warning) = describe_key_mismatch(first_name, first_keys, sheet_ref, &sheet.natural_keys) {
+                    warnings.push(warning);
+                }
+            }
+        }
+        total += sheet.total;
+        saved += sheet.saved_count;
+        filtered_count += sheet.filtered_count;
+        for mut row in sheet.rows {
+            row.shift_insert(0, tag.to_string(), Value::String(sheet_ref.clone()));
+            for key in row.keys() {
+                keys.entry(key.clone()).or_default();
+            }
+            rows.push(row);
+        }
+    }
+    let keys: Vec<String> = keys.into_keys().collect();
+    let rows: Vec<IndexMap<String, Value>> = rows
+        .into_iter()
+        .map(|mut row| keys.iter().map(|k| (k.clone(), row.swap_remove(k).unwrap_or(Value::Null))).collect())
+        .collect();
+    let (header_row_index, body_start_index) = first.map(|(_, _, h, b)| (h, b)).unwrap_or((None, 0));
+    let ds = DataSet::from_count_and_rows(total, rows, opts);
+    Ok(ResultSet::new(info, &keys, ds, opts, out_ref, header_row_index, body_start_index)
+        .with_filtered_count(filtered_count)
+        .with_warnings(warnings))
+}
+
+/// Gives each positional column override (one with no `source_key`) the natural key of
+/// the column it lands on in the first sheet, so it matches by name from then on.
+fn anchor_columns(columns: &[Column], natural_keys: &[String]) -> Vec<Column> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(index, col)| match (&col.source_key, natural_keys.get(index)) {
+            (None, Some(key)) => {
+                let mut col = col.clone();
+                col.source_key = Some(Arc::from(key.as_str()));
+                col
+            }
+            _ => col.clone(),
+        })
+        .collect()
+}
+
+/// Describes how a sheet's natural keys differ from the first sheet's, if at all.
+fn describe_key_mismatch(first_name: &str, first_keys: &[String], sheet_name: &str, keys: &[String]) -> Option<String> {
+    let missing: Vec<&str> = first_keys.iter().filter(|k| !keys.contains(k)).map(|k| k.as_str()).collect();
+    let extra: Vec<&str> = keys.iter().filter(|k| !first_keys.contains(k)).map(|k| k.as_str()).collect();
+    if missing.is_empty() && extra.is_empty() {
+        return None;
+    }
+    let mut parts = vec![];
+    if !missing.is_empty() {
+        parts.push(format!("missing {}", missing.join(", ")));
+    }
+    if !extra.is_empty() {
+        parts.push(format!("extra {}", extra.join(", ")));
+    }
+    Some(format!("sheet `{}` columns differ from `{}`: {}", sheet_name, first_name, parts.join("; ")))
 }
 
 /// Read a single worksheet from a workbook in immediate (sync) or asycnhronous modes
@@ -238,15 +761,178 @@ pub async fn read_single_worksheet(
     save_opt: Option<SaveRowFn>,
     out_ref: Option<&str>,
 ) -> Result<ResultSet, GenericError> {
+    let sheet = read_worksheet(&mut workbook, sheet_ref, opts, save_opt.as_deref())?;
+    let ds = DataSet::from_count_and_rows(sheet.total, sheet.rows, opts);
+    Ok(ResultSet::new(info, &sheet.headers, ds, opts, out_ref, sheet.header_row_index, sheet.body_start_index)
+        .with_columns(&sheet.columns)
+        .with_filtered_count(sheet.filtered_count)
+        .with_cell_range(sheet.cell_range)
+        .with_body_end_index(sheet.body_end_index)
+        .with_transposed(sheet.transposed)
+        .with_form(sheet.form))
+}
+
+/// Transposes a range when `opts.orientation` calls for it, so each record becomes a
+/// row, also returning whether it did.
+pub(crate) fn orient_range<'r>(range: Cow<'r, Range<Data>>, opts: &OptionSet) -> (Cow<'r, Range<Data>>, bool) {
+    if !resolve_orientation(opts, || text_rows(&range, 0).take(DETECT_SAMPLE_SIZE).collect()) {
+        return (range, false);
+    }
+    (Cow::Owned(transpose_range(&range)), true)
+}
+
+/// Swaps rows and columns, starting the result at `A1`.
+fn transpose_range(range: &Range<Data>) -> Range<Data> {
+    let (rows, cols) = range.get_size();
+    if rows == 0 || cols == 0 {
+        return Range::empty();
+    }
+    let mut transposed = Range::new((0, 0), (cols as u32 - 1, rows as u32 - 1));
+    for (row, col, cell) in range.used_cells() {
+        transposed.set_value((col as u32, row as u32), cell.clone());
+    }
+    transposed
+}
+
+/// 0-based sheet row of a range's first row, as calamine ranges start at the first
+/// used cell rather than at `A1`.
+fn first_sheet_row(range: &Range<Data>) -> usize {
+    range.start().map_or(0, |(row, _)| row as usize)
+}
+
+/// Cell text of each row from `start`, for detection passes.
+pub(crate) fn text_rows(range: &Range<Data>, start: usize) -> impl Iterator<Item = Vec<String>> + '_ {
+    range.rows().skip(start).map(|row| row.iter().map(|c| c.to_string()).collect())
+}
+
+/// Restricts a worksheet to `OptionSet.cell_range`, if set, also returning the range
+/// actually read with an open end resolved to the sheet's last row.
+pub(crate) fn restrict_to_cell_range<'r>(
+    range: &'r Range<Data>,
+    opts: &OptionSet,
+) -> Result<(Cow<'r, Range<Data>>, Option<CellRange>), GenericError> {
+    let Some(cell_range) = opts.parsed_cell_range()? else {
+        return Ok((Cow::Borrowed(range), None));
+    };
+    let restricted = cell_range.restrict(range);
+    let end_row = restricted.end().map(|(row, _)| row).or(cell_range.end_row);
+    Ok((Cow::Owned(restricted), Some(CellRange { end_row, ..cell_range })))
+}
+
+/// Rows and resolved header settings of one worksheet, before they are wrapped in a
+/// `ResultSet` (single-sheet reads) or a `SheetDataSet` (multi-sheet reads).
+struct WorksheetRead {
+    headers: Vec<String>,
+    rows: Vec<IndexMap<String, Value>>,
+    total: usize,
+    header_row_index: Option<usize>,
+    body_start_index: usize,
+    columns: Vec<Column>,
+    /// Header keys before any column overrides -- see `natural_column_keys`.
+    natural_keys: Vec<String>,
+    filtered_count: usize,
+    /// Rows passed to the save callback.
+    saved_count: usize,
+    cell_range: Option<CellRange>,
+    /// See `ResultSet.body_end_index`.
+    body_end_index: Option<usize>,
+    transposed: bool,
+    /// See `ResultSet.form`. Only set by `read_worksheet`, which has the whole sheet.
+    form: Option<IndexMap<String, Value>>,
+}
+
+/// Reads one worksheet with `opts`, capturing rows unless reading asynchronously and
+/// passing them to `save_opt` if set.
+fn read_worksheet(
+    workbook: &mut Sheets<BufReader<File>>,
+    sheet_ref: &str,
+    opts: &OptionSet,
+    save_opt: Option<&SaveRowCallback<'_>>,
+) -> Result<WorksheetRead, GenericError> {
     let range = workbook.worksheet_range(sheet_ref)?;
+    let mut sheet = read_worksheet_range(&range, sheet_ref, opts, save_opt)?;
+    sheet.form = opts.form.as_ref().map(|form| extract_workbook_form(&range, form, &opts.rows));
+    Ok(sheet)
+}
+
+/// Converts each form value like a cell in a column of the field's format.
+fn extract_workbook_form(range: &Range<Data>, form: &FormExtraction, row_opts: &RowOptionSet) -> IndexMap<String, Value> {
+    let cells: Vec<Vec<String>> = text_rows(range, 0).collect();
+    locate_form_values(&cells, form)
+        .into_iter()
+        .map(|found| {
+            let value = found.value_cell.and_then(|(row, col)| range.get((row, col))).map_or(Value::Null, |cell| {
+                workbook_cell_to_value(cell, &form_value_opts(row_opts, &found.format), 0)
+            });
+            (found.key, value)
+        })
+        .collect()
+}
+
+/// As `extract_workbook_form`, over the text of a CSV file's lines. Lines are only read
+/// until every field's value has been seen, or to the end with automatic labels.
+fn extract_csv_form(lines: impl Iterator<Item = Vec<String>>, form: &FormExtraction, row_opts: &RowOptionSet) -> IndexMap<String, Value> {
+    let mut locator = FormLocator::new(form);
+    let mut scanned = vec![];
+    for line in lines {
+        locator.scan_row(&line);
+        scanned.push(line);
+        if locator.is_complete() {
+            break;
+        }
+    }
+    locator
+        .finish()
+        .into_iter()
+        .map(|found| {
+            let value = found.value_cell.and_then(|(row, col)| scanned.get(row)?.get(col)).map_or(Value::Null, |cell| {
+                csv_cell_to_json_value(cell, &form_value_opts(row_opts, &found.format), 0)
+            });
+            (found.key, value)
+        })
+        .collect()
+}
+
+/// Row options with a single column of `format`, for converting one form value.
+fn form_value_opts(row_opts: &RowOptionSet, format: &Format) -> RowOptionSet {
+    let column = Column::from_key_ref_with_format(None, format.clone(), None, row_opts.datetime_mode, row_opts.decimal_comma);
+    RowOptionSet { columns: vec![column], ..row_opts.clone() }
+}
+
+/// Reads an already loaded worksheet range, as for `read_worksheet`.
+fn read_worksheet_range(
+    range: &Range<Data>,
+    sheet_ref: &str,
+    opts: &OptionSet,
+    save_opt: Option<&SaveRowCallback<'_>>,
+) -> Result<WorksheetRead, GenericError> {
+    let (range, cell_range) = restrict_to_cell_range(range, opts)?;
+    let first_sheet_row = first_sheet_row(&range);
+    let (range, transposed) = orient_range(range, opts);
+    // Transposed records are source columns, so they're numbered from the first one read
+    let first_sheet_row = if transposed { 0 } else { first_sheet_row };
+    let inferred;
+    let opts = match opts.infer_sample {
+        Some(sample_size) => {
+            let limit = sample_row_limit(opts, sample_size);
+            let text: Vec<Vec<String>> = text_rows(&range, 0).take(limit).collect();
+            let raw = range.rows().take(limit).map(|row| row.iter().map(RawCell::from_data).collect()).collect();
+            inferred = with_inferred_formats(opts, sample_size, &text, raw);
+            &inferred
+        }
+        None => opts,
+    };
     let capture_rows = opts.capture_rows();
     let columns = opts.rows.columns.clone();
     let max_rows = opts.max_rows();
+    // With a row filter or unpivot, the row cap applies to output rows rather than to rows read
+    let count_kept = capture_rows && opts.counts_output_rows();
+    let mut filtered_count: usize = 0;
     let mut headers: Vec<String> = vec![];
     let mut col_keys: Vec<String> = vec![];
     let mut has_headers = false;
     let mut rows: Vec<IndexMap<String, Value>> =
-        Vec::with_capacity(if capture_rows { max_rows } else { 0 });
+        Vec::with_capacity(if capture_rows { max_rows.min(DEFAULT_MAX_ROWS) } else { 0 });
     let mut row_index = 0;
     let detected = resolve_header_and_data_rows(opts, || {
         range.rows().take(DETECT_SAMPLE_SIZE)
@@ -265,6 +951,7 @@ pub async fn read_single_worksheet(
     // legacy per-row detection never needs to (and mustn't) run for it.
     let match_header_row_below = capture_headers && header_row_index > 0 && header_row_span <= 1;
     let mut resolved_row_opts = opts.rows.clone();
+    let mut natural: Vec<String> = vec![];
 
     if capture_headers && header_row_span > 1 {
         // Multi-row header: read `header_row_span` consecutive rows starting at
@@ -279,6 +966,7 @@ pub async fn read_single_worksheet(
             .collect();
         let combined = combine_header_rows(&raw_header_rows);
         let natural_keys = natural_column_keys(&combined, &opts.field_mode);
+        natural = natural_keys.clone();
         let resolved_columns = resolve_columns(&columns, &natural_keys);
         headers = build_header_keys(&combined, &resolved_columns, &opts.field_mode);
         resolved_row_opts.columns = resolved_columns;
@@ -287,6 +975,7 @@ pub async fn read_single_worksheet(
     } else if capture_headers {
         if let Some(first_row) = range.headers() {
             let natural_keys = natural_column_keys(&first_row, &opts.field_mode);
+            natural = natural_keys.clone();
             let resolved_columns = resolve_columns(&columns, &natural_keys);
             headers = build_header_keys(&first_row, &resolved_columns, &opts.field_mode);
             resolved_row_opts.columns = resolved_columns;
@@ -297,25 +986,35 @@ pub async fn read_single_worksheet(
         let num_cols = range.get_size().1;
         let blank = vec![String::new(); num_cols];
         let natural_keys = natural_column_keys(&blank, &opts.field_mode);
+        natural = natural_keys.clone();
         let resolved_columns = resolve_columns(&columns, &natural_keys);
         headers = build_header_keys(&blank, &resolved_columns, &opts.field_mode.forced_fallback());
         resolved_row_opts.columns = resolved_columns;
         has_headers = true;
     }
     let total = range.get_size().0;
+    let body_end_index = resolve_data_end(opts, first_data_row_index, total, || {
+        detect_data_end(text_rows(&range, first_data_row_index), first_data_row_index, opts.footer_blank_run())
+    });
+    let data_end = body_end_index.unwrap_or(total);
     if capture_rows || match_header_row_below {
         let max_row_count = if capture_rows {
             max_rows
         } else {
             header_row_index + 2
         };
-        let max_take = if total < max_row_count {
+        let max_take = if total < max_row_count || count_kept {
             total
         } else {
             max_row_count + 1
         };
         for row in range.rows().take(max_take) {
-            if row_index > max_row_count {
+            let limit_reached = if count_kept {
+                rows.len() >= max_rows
+            } else {
+                row_index > max_row_count
+            } || row_index >= data_end;
+            if limit_reached {
                 break;
             }
             if match_header_row_below && row_index == header_row_index {
@@ -324,6 +1023,7 @@ pub async fn read_single_worksheet(
                     .map(|c| c.to_string().to_snake_case())
                     .collect::<Vec<String>>();
                 let natural_keys = natural_column_keys(&h_row, &opts.field_mode);
+                natural = natural_keys.clone();
                 let resolved_columns = resolve_columns(&columns, &natural_keys);
                 headers = build_header_keys(&h_row, &resolved_columns, &opts.field_mode);
                 resolved_row_opts.columns = resolved_columns;
@@ -340,21 +1040,30 @@ pub async fn read_single_worksheet(
                     true
                 };
                 if is_real_data {
-                    let row_map = workbook_row_to_map(row, &resolved_row_opts, &headers);
-                    rows.push(row_map);
+                    let row_map = workbook_row_to_map(row, &resolved_row_opts, &headers, first_sheet_row + row_index, sheet_ref);
+                    for row_map in opts.output_rows(row_map) {
+                        if count_kept && rows.len() >= max_rows {
+                            break;
+                        }
+                        if opts.keeps_row(&row_map) {
+                            rows.push(row_map);
+                        } else {
+                            filtered_count += 1;
+                        }
+                    }
                 }
             }
             row_index += 1;
         }
     }
+    let mut save_count: usize = 0;
     if let Some(save_method) = save_opt {
         // Skip everything before first_data_row_index (the header row itself, and any
         // title/notes/gap rows above it) -- this used to just stream from the true start
         // of the sheet regardless of header_row_index/data_row_index, silently exporting
         // notes rows as bogus data records.
-        let mut save_count: usize = 0;
         for (idx, row) in range.rows().enumerate() {
-            if save_count >= max_rows {
+            if save_count >= max_rows || idx >= data_end {
                 break;
             }
             if idx < first_data_row_index {
@@ -367,15 +1076,38 @@ pub async fn read_single_worksheet(
                 true
             };
             if is_real_data {
-                let row_map = workbook_row_to_map(row, &resolved_row_opts, &headers);
-                save_method(row_map)?;
-                save_count += 1;
+                let row_map = workbook_row_to_map(row, &resolved_row_opts, &headers, first_sheet_row + idx, sheet_ref);
+                for row_map in opts.output_rows(row_map) {
+                    if save_count >= max_rows {
+                        break;
+                    }
+                    if opts.keeps_row(&row_map) {
+                        save_method(row_map)?;
+                        save_count += 1;
+                    } else if !capture_rows {
+                        // already counted above when the same rows were also captured
+                        filtered_count += 1;
+                    }
+                }
             }
         }
     }
 
-    let ds = DataSet::from_count_and_rows(total, rows, opts);
-    Ok(ResultSet::new(info, &headers, ds, opts, out_ref, detected.header_index, first_data_row_index))
+    Ok(WorksheetRead {
+        headers,
+        rows,
+        total,
+        header_row_index: detected.header_index,
+        body_start_index: first_data_row_index,
+        columns: resolved_row_opts.columns,
+        natural_keys: natural,
+        filtered_count,
+        saved_count: save_count,
+        cell_range,
+        body_end_index,
+        transposed,
+        form: None,
+    })
 }
 
 /// Process a CSV/TSV file asynchronously with an optional row save method
@@ -393,10 +1125,8 @@ pub async fn read_csv_core<'a>(
     save_opt: Option<SaveRowFn>,
     out_ref: Option<&str>,
 ) -> Result<ResultSet, GenericError> {
-    let separator = match path_data.mode() {
-        Extension::Tsv => b't',
-        _ => b',',
-    };
+    let separator = csv_separator(path_data);
+    let cell_range = opts.parsed_cell_range()?;
     if let Ok(mut rdr) = ReaderBuilder::new()
         .delimiter(separator)
         .has_headers(false)
@@ -409,10 +1139,42 @@ pub async fn read_csv_core<'a>(
     {
         let capture_rows = opts.capture_rows();
         let max_line_usize = opts.max_rows();
+        // A transposed file is held in memory, as no record is complete until the last
+        // line has been read. `source_lines` is how many lines of the file that took.
+        let mut source_lines = 0;
+        let transposed: Option<Vec<StringRecord>> = if resolve_orientation(opts, || {
+            csv_text_rows(path_data.path(), separator, cell_range.as_ref()).take(DETECT_SAMPLE_SIZE).collect()
+        }) {
+            let lines: Vec<Vec<String>> = csv_text_rows(path_data.path(), separator, cell_range.as_ref()).collect();
+            source_lines = lines.len();
+            Some(transpose_text_rows(&lines).into_iter().map(StringRecord::from).collect())
+        } else {
+            None
+        };
+        let is_transposed = transposed.is_some();
+        let text_lines = || -> Box<dyn Iterator<Item = Vec<String>> + '_> {
+            match &transposed {
+                Some(records) => Box::new(records.iter().map(|r| r.iter().map(|s| s.to_string()).collect())),
+                None => Box::new(csv_text_rows(path_data.path(), separator, cell_range.as_ref())),
+            }
+        };
+        let inferred;
+        let opts = match opts.infer_sample {
+            Some(sample_size) => {
+                let text: Vec<Vec<String>> = text_lines().take(sample_row_limit(opts, sample_size)).collect();
+                let raw = text.iter().map(|row| row.iter().map(|s| RawCell::from_text(s)).collect()).collect();
+                inferred = with_inferred_formats(opts, sample_size, &text, raw);
+                &inferred
+            }
+            None => opts,
+        };
         // Sampling (when actually needed for detection) opens a fresh, short-lived reader
         // rather than reusing `rdr` -- csv::Reader is a moving cursor, so peeking ahead on
         // the same reader would consume records the main pass below still needs.
         let detected = resolve_header_and_data_rows(opts, || {
+            if is_transposed {
+                return text_lines().take(DETECT_SAMPLE_SIZE).collect();
+            }
             let mut sample_rows = Vec::new();
             if let Ok(mut sample_rdr) = ReaderBuilder::new()
                 .delimiter(separator)
@@ -420,13 +1182,26 @@ pub async fn read_csv_core<'a>(
                 .flexible(true)
                 .from_path(path_data.path())
             {
-                for record in sample_rdr.records().take(DETECT_SAMPLE_SIZE).flatten() {
-                    sample_rows.push(record.iter().map(|s| s.to_string()).collect());
+                for (_, record) in csv_lines_in_range(sample_rdr.records(), cell_range.as_ref()).take(DETECT_SAMPLE_SIZE) {
+                    if let Ok(record) = record {
+                        sample_rows.push(restrict_record(record, cell_range.as_ref()).iter().map(|s| s.to_string()).collect());
+                    }
                 }
             }
             sample_rows
         });
         let first_data_row_index = detected.data_index;
+        // Footer options need the line count, and detection the rows below the header, so
+        // like sampling above this takes its own pass over the file
+        let body_end_index = if opts.handles_footer() {
+            let line_total = text_lines().count();
+            resolve_data_end(opts, first_data_row_index, line_total, || {
+                detect_data_end(text_lines().skip(first_data_row_index), first_data_row_index, opts.footer_blank_run())
+            })
+        } else {
+            None
+        };
+        let data_end = body_end_index.unwrap_or(usize::MAX);
         // No line is a header source for --omit-header, *or* when detection found no
         // confident header row at all (see DetectedRows::header_index) -- both fall
         // back to lazily-built A1/C01-style names below.
@@ -435,7 +1210,7 @@ pub async fn read_csv_core<'a>(
         let header_row_span = opts.effective_header_row_span();
 
         let mut rows: Vec<IndexMap<String, Value>> =
-            Vec::with_capacity(if capture_rows { max_line_usize } else { 0 });
+            Vec::with_capacity(if capture_rows { max_line_usize.min(DEFAULT_MAX_ROWS) } else { 0 });
         let mut headers: Vec<String> = vec![];
         let mut resolved_row_opts = opts.rows.clone();
         let mut header_row_buffer: Vec<Vec<String>> = Vec::new();
@@ -443,15 +1218,35 @@ pub async fn read_csv_core<'a>(
         // derived once, lazily, from the first eligible data row's column count.
         let mut fallback_keys_built = false;
 
+        // CSV files have no worksheets, so derived `sheet_name()` columns get the file stem
+        let sheet_name = path_data.path().file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
         let mut total: usize = 0;
         let mut line_count: usize = 0;
+        let mut filtered_count: usize = 0;
         let mut row_index: usize = 0;
+        let mut last_line: Option<usize> = None;
+        // Transposed records are already restricted to the range
+        let record_range = if is_transposed { None } else { cell_range };
+        let lines: Box<dyn Iterator<Item = (usize, csv::Result<StringRecord>)>> = match transposed {
+            Some(records) => {
+                let first_line = cell_range.map_or(0, |cr| cr.start.0 as usize);
+                last_line = source_lines.checked_sub(1).map(|last| first_line + last);
+                Box::new(records.into_iter().map(Ok).enumerate())
+            }
+            None => Box::new(csv_lines_in_range(rdr.records(), cell_range.as_ref())),
+        };
 
-        for result in rdr.records() {
+        for (line, result) in lines {
+            if !is_transposed {
+                last_line = Some(line);
+            }
             let Ok(record) = result else {
                 row_index += 1;
                 continue;
             };
+            let record = restrict_record(record, record_range.as_ref());
+            // Transposed records are source columns, so they're numbered in reading order
+            let source_line = if is_transposed { row_index } else { line };
             // "total"/num_rows is a structural line count for the whole file, matching
             // the calamine path's range.get_size().0 -- it includes the header row (and
             // any skipped gap rows), not just rows that end up classified as data.
@@ -481,7 +1276,8 @@ pub async fn read_csv_core<'a>(
                 continue;
             }
 
-            if row_index < first_data_row_index {
+            // Footer lines still count towards `total`, like the rest of the file
+            if row_index < first_data_row_index || row_index >= data_end {
                 row_index += 1;
                 continue;
             }
@@ -497,28 +1293,45 @@ pub async fn read_csv_core<'a>(
             if capture_rows {
                 if line_count < max_line_usize {
                     if let Some(row) = csv_row_result_to_values(Ok(record), &resolved_row_opts) {
-                        let mut row_map = to_index_map(&row, &headers, Some(&resolved_row_opts.columns));
-                        if resolved_row_opts.omit_null_values {
-                            omit_null_values(&mut row_map);
+                        let row_map = values_to_row_map(&row, &resolved_row_opts, &headers, source_line, &sheet_name);
+                        for row_map in opts.output_rows(row_map) {
+                            if line_count >= max_line_usize {
+                                break;
+                            }
+                            if opts.keeps_row(&row_map) {
+                                rows.push(row_map);
+                                line_count += 1;
+                            } else {
+                                filtered_count += 1;
+                            }
                         }
-                        rows.push(row_map);
-                        line_count += 1;
                     }
                 }
             } else if let Some(save_method) = save_opt.as_ref() {
                 if let Some(row) = csv_row_result_to_values(Ok(record), &resolved_row_opts) {
-                    let mut row_map = to_index_map(&row, &headers, Some(&resolved_row_opts.columns));
-                    if resolved_row_opts.omit_null_values {
-                        omit_null_values(&mut row_map);
+                    let row_map = values_to_row_map(&row, &resolved_row_opts, &headers, source_line, &sheet_name);
+                    for row_map in opts.output_rows(row_map) {
+                        if opts.keeps_row(&row_map) {
+                            save_method(row_map)?;
+                        } else {
+                            filtered_count += 1;
+                        }
                     }
-                    save_method(row_map)?;
                 }
             }
             row_index += 1;
         }
+        let form = opts.form.as_ref().map(|form| extract_csv_form(csv_text_rows(path_data.path(), separator, None), form, &opts.rows));
         let info = WorkbookInfo::simple(path_data);
         let ds = DataSet::from_count_and_rows(total, rows, opts);
-        Ok(ResultSet::new(&info, &headers, ds, opts, out_ref, detected.header_index, first_data_row_index))
+        let cell_range = cell_range.map(|cr| CellRange { end_row: last_line.map(|line| line as u32).or(cr.end_row), ..cr });
+        Ok(ResultSet::new(&info, &headers, ds, opts, out_ref, detected.header_index, first_data_row_index)
+            .with_columns(&resolved_row_opts.columns)
+            .with_filtered_count(filtered_count)
+            .with_cell_range(cell_range)
+            .with_body_end_index(body_end_index)
+            .with_transposed(is_transposed)
+            .with_form(form))
     } else {
         let error_msg = match path_data.ext() {
             Extension::Tsv => "unreadable_tsv_file",
@@ -528,13 +1341,79 @@ pub async fn read_csv_core<'a>(
     }
 }
 
+/// CSV records with their 0-based line index, limited to the rows of `cell_range` if set.
+fn csv_lines_in_range<'r>(
+    records: impl Iterator<Item = csv::Result<StringRecord>> + 'r,
+    cell_range: Option<&CellRange>,
+) -> impl Iterator<Item = (usize, csv::Result<StringRecord>)> + 'r {
+    let cell_range = cell_range.copied();
+    records
+        .enumerate()
+        .skip(cell_range.map_or(0, |cr| cr.start.0 as usize))
+        .take_while(move |(line, _)| cell_range.is_none_or(|cr| cr.contains_row(*line)))
+}
+
+fn csv_separator(path_data: &PathData) -> u8 {
+    match path_data.mode() {
+        Extension::Tsv => b'\t',
+        _ => b',',
+    }
+}
+
+/// Swaps the lines and fields of a CSV file's text, padding short lines with blanks.
+fn transpose_text_rows(lines: &[Vec<String>]) -> Vec<Vec<String>> {
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    (0..width)
+        .map(|col| lines.iter().map(|line| line.get(col).cloned().unwrap_or_default()).collect())
+        .collect()
+}
+
+/// Each CSV line's fields as text from a fresh reader, restricted to `cell_range`, for
+/// passes that must not move the main reader. Malformed lines come through empty so
+/// positions still line up with the main pass.
+fn csv_text_rows(path: &Path, separator: u8, cell_range: Option<&CellRange>) -> impl Iterator<Item = Vec<String>> {
+    let cell_range = cell_range.copied();
+    let reader = ReaderBuilder::new().delimiter(separator).has_headers(false).flexible(true).from_path(path).ok();
+    reader.into_iter().flat_map(move |rdr| {
+        csv_lines_in_range(rdr.into_records(), cell_range.as_ref()).map(move |(_, record)| match record {
+            Ok(record) => restrict_record(record, cell_range.as_ref()).iter().map(|s| s.to_string()).collect(),
+            Err(_) => vec![],
+        })
+    })
+}
+
+/// The fields of a CSV record inside `cell_range`'s columns, if set.
+fn restrict_record(record: StringRecord, cell_range: Option<&CellRange>) -> StringRecord {
+    match cell_range {
+        Some(cr) => StringRecord::from(cr.slice_fields(record.iter())),
+        None => record,
+    }
+}
+
 // Convert an array of row data to an IndexMap of serde_json::Value objects
 fn workbook_row_to_map(
     row: &[Data],
     opts: &RowOptionSet,
     headers: &[String],
+    row_index: usize,
+    sheet_name: &str,
 ) -> IndexMap<String, Value> {
-    let mut row_map = to_index_map(&workbook_row_to_values(row, opts), headers, Some(&opts.columns));
+    values_to_row_map(&workbook_row_to_values(row, opts), opts, headers, row_index, sheet_name)
+}
+
+/// Builds the output row from converted cell values: places each value at its column's
+/// key, adds any derived columns, then drops nulls if requested. `row_index` is the
+/// 0-based row in the source sheet (or line in the file), not counting from the range
+/// read, and is reported 1-based by `row_number()` in derived expressions.
+fn values_to_row_map(
+    values: &[Value],
+    opts: &RowOptionSet,
+    headers: &[String],
+    row_index: usize,
+    sheet_name: &str,
+) -> IndexMap<String, Value> {
+    let row_map = to_index_map(values, headers, Some(&opts.columns));
+    let mut row_map = insert_derived_columns(row_map, &opts.derived, values, headers, row_index + 1, sheet_name);
     if opts.omit_null_values {
         omit_null_values(&mut row_map);
     }
@@ -689,7 +1568,7 @@ fn simplify_datetime_string(full_datetime_str: &str) -> String {
 /// even reaches order-guessing, since an explicit splitter was already given). The output
 /// shape is always the same canonical "YYYY-MM-DD" regardless of the input's order or
 /// separator -- only what's *accepted* as input is more flexible, not what's produced.
-fn guess_date_string(value: &str) -> Option<String> {
+pub(crate) fn guess_date_string(value: &str) -> Option<String> {
     fuzzy_to_date_string(value, None)
 }
 
@@ -697,7 +1576,7 @@ fn guess_date_string(value: &str) -> Option<String> {
 /// canonical "YYYY-MM-DDTHH:MM:SS.mmmZ" shape `iso_fuzzy_to_datetime_string` also
 /// produces, just with flexible input-side order/separator guessing instead of a forced
 /// YMD + '-' assumption.
-fn guess_datetime_string(value: &str) -> Option<String> {
+pub(crate) fn guess_datetime_string(value: &str) -> Option<String> {
     fuzzy_to_datetime_string_opts(value, 'T', None, Some(':'), true)
 }
 
@@ -740,7 +1619,7 @@ fn guess_datetime_string(value: &str) -> Option<String> {
 /// outright before ever calling to_numbers(). `to_numbers()` silently discards non-digit
 /// characters, so without this guard "2h45m" would extract as [2, 45] and format as
 /// "02:45" -- not a rejection, a *wrong* answer indistinguishable from a correct one.
-fn parse_bare_time_string(value: &str) -> Option<String> {
+pub(crate) fn parse_bare_time_string(value: &str) -> Option<String> {
     let has_am_suffix = value.ends_with_ci("am");
     let has_pm_suffix = !has_am_suffix && value.ends_with_ci("pm");
     let is_12_hour = has_am_suffix || has_pm_suffix;
@@ -968,6 +1847,9 @@ fn csv_cell_to_json_value(cell: &str, opts: &RowOptionSet, index: usize) -> Valu
         Format::Array(ref element_format, ref separator) => {
             return process_array_value(cell, element_format, separator)
         }
+        // Inferred text is kept exactly as written -- a code like "02134" would
+        // otherwise be sniffed as numeric below and lose its leading zero.
+        Format::Text if opts.inferred_types => return Value::String(cell.to_string()),
         _ => {}
     }
     let has_number = cell.to_first_number::<f64>().is_some();
@@ -1017,6 +1899,14 @@ fn csv_cell_to_json_value(cell: &str, opts: &RowOptionSet, index: usize) -> Valu
                     new_cell = Value::Number(float_val);
                 }
             }
+        } else if opts.inferred_types {
+            // Numeric-looking but not a valid JSON number -- in practice a code with a
+            // leading zero ("02134"). Only a numeric format reads it as a number;
+            // otherwise it's kept as written rather than nulled.
+            new_cell = match fmt {
+                Format::Integer | Format::Float | Format::Decimal(_) => process_string_value(&num_cell, fmt, def_val),
+                _ => Value::String(cell.to_string()),
+            };
         }
     } else if let Some(is_true) = cell.is_truthy_core(false) {
         new_cell = Value::Bool(is_true);
@@ -1029,12 +1919,19 @@ fn csv_cell_to_json_value(cell: &str, opts: &RowOptionSet, index: usize) -> Valu
                     Value::Null
                 }
             }
+            // Non-numeric text under a numeric format passes through as-is, unless the
+            // column was inferred (see with_inferred_formats) and so falls back to its
+            // default, keeping its type consistent down the column.
+            Format::Integer | Format::Float | Format::Decimal(_) if opts.inferred_types => process_string_value(cell, fmt, def_val),
             _ => Value::String(cell.to_string()),
         };
     }
     new_cell
 }
 
+/// Row counts per worksheet, from the declared dimensions where the format has them.
+/// Row counts per worksheet, counted over each sheet's loaded range rather than the
+/// dimensions it declares -- see `read_workbook_info` for those.
 pub async fn read_workbook_sheet_info<'a>(
     path_data: &PathData<'a>,
 ) -> Result<IndexMap<String, usize>, GenericError> {
@@ -1051,10 +1948,82 @@ pub async fn read_workbook_sheet_info<'a>(
     }
 }
 
+/// Header detection for the sheet or CSV file `opts` selects, with each candidate row
+/// scored and a confidence level, so a caller can confirm an uncertain choice before
+/// reading. Samples the same rows a read with `detect_header` would, within `range`,
+/// `table` or `defined_name` if set.
+pub async fn detect_headers(opts: &OptionSet) -> Result<HeaderDetection, GenericError> {
+    let filepath = opts.path.clone().ok_or(GenericError("no_filepath_specified"))?;
+    let path = Path::new(&filepath);
+    if !path.exists() {
+        return Err(GenericError("file_unavailable"));
+    }
+    let path_data = PathData::new(path);
+    if !path_data.is_valid() {
+        return Err(GenericError("unsupported_format"));
+    }
+    let sample: Vec<Vec<String>> = if path_data.use_calamine() {
+        let mut workbook = open_workbook_auto(path).map_err(|_| GenericError("cannot_open_workbook"))?;
+        let (sheet_name, opts) = resolve_sample_sheet(&mut workbook, opts)?;
+        let sheet_range = workbook.worksheet_range(&sheet_name)?;
+        let (range, _) = restrict_to_cell_range(&sheet_range, &opts)?;
+        let (range, _) = orient_range(range, &opts);
+        text_rows(&range, 0).take(DETECT_SAMPLE_SIZE).collect()
+    } else {
+        csv_sample_lines(&path_data, opts, DETECT_SAMPLE_SIZE)?
+    };
+    Ok(detect_header_rows(&sample))
+}
+
+/// The sheet a sampling pass reads -- the `table` or `defined_name` source, otherwise the
+/// first selected sheet -- with the options that apply to it.
+pub(crate) fn resolve_sample_sheet(
+    workbook: &mut Sheets<BufReader<File>>,
+    opts: &OptionSet,
+) -> Result<(String, OptionSet), GenericError> {
+    if let Some(resolved) = resolve_range_source(workbook, opts)? {
+        return Ok(resolved);
+    }
+    let matched = select_worksheets(workbook, opts)?;
+    let sheet_name = matched.selected.first().cloned().ok_or(GenericError("workbook_with_no_sheets"))?;
+    let sheet_opts = opts.for_sheet(&sheet_name);
+    Ok((sheet_name, sheet_opts))
+}
+
+/// The first `limit` lines of a CSV/TSV file as a read sees them: within `range` if set,
+/// and transposed when `orientation` calls for it.
+pub(crate) fn csv_sample_lines(path_data: &PathData, opts: &OptionSet, limit: usize) -> Result<Vec<Vec<String>>, GenericError> {
+    let cell_range = opts.parsed_cell_range()?;
+    let separator = csv_separator(path_data);
+    let lines = || csv_text_rows(path_data.path(), separator, cell_range.as_ref());
+    Ok(if resolve_orientation(opts, || lines().take(DETECT_SAMPLE_SIZE).collect()) {
+        transpose_text_rows(&lines().collect::<Vec<_>>()).into_iter().take(limit).collect()
+    } else {
+        lines().take(limit).collect()
+    })
+}
+
+/// Workbook metadata without reading rows: dimensions, used range, visibility and type
+/// of every sheet, plus defined names and document properties.
+pub async fn read_workbook_info<'a>(path_data: &PathData<'a>) -> Result<WorkbookInfo, GenericError> {
+    if let Ok(mut workbook) = open_workbook_auto(path_data.path()) {
+        let sheet_names = workbook.sheet_names();
+        let mut info = WorkbookInfo::new(path_data, &[], &sheet_names);
+        info.selected = None;
+        info.sheet_info = read_sheet_info(&mut workbook);
+        info.defined_names = read_defined_names(&workbook);
+        info.tables = read_table_info(&mut workbook);
+        info.properties = DocumentProperties::from_path(path_data.path());
+        Ok(info)
+    } else {
+        Err(GenericError("cannot_open_workbook"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{helpers::*, Column, KeySegment};
+    use crate::{helpers::*, Column, DetectionConfidence, FormField, KeySegment};
     use serde_json::json;
     use std::path;
     use std::sync::Arc;
@@ -2050,6 +3019,13 @@ mod tests {
         path.to_string_lossy().to_string()
     }
 
+    #[test]
+    fn test_tsv_files_split_on_tabs() {
+        let path = write_csv_fixture("tab_separated.tsv", "region\tteam size\nwest, north\t12\n");
+        let rows = process_spreadsheet_direct(&OptionSet::new(&path)).unwrap().to_vec();
+        assert_eq!(json!(rows), json!([{"region": "west, north", "team_size": 12}]));
+    }
+
     #[test]
     fn test_csv_header_row_and_data_row_index_skip_a_gap() {
         // Row 0 title, row 1 notes, row 2 header, row 3 blank, rows 4-5 data --
@@ -2074,6 +3050,51 @@ mod tests {
         assert_eq!(rows[1].get("sku"), Some(&json!("SKU002")));
     }
 
+    #[test]
+    fn test_infer_column_types_keeps_codes_as_text_and_numbers_consistent() {
+        let path = write_csv_fixture(
+            "infer_column_types.csv",
+            "id,zip,qty\n1,02134,5\n2,90210,7\n3,10001,n/a\n4,30301,9\n5,60601,11\n6,73301,12\n7,94105,3\n8,33101,4\n9,98101,8\n10,80202,6\n",
+        );
+        // per-cell typing: most zips become numbers and the leading-zero one isn't read at all
+        let rows = process_spreadsheet_direct(&OptionSet::new(&path)).unwrap().to_vec();
+        assert_eq!(rows[0].get("zip"), Some(&Value::Null));
+        assert_eq!(rows[1].get("zip"), Some(&json!(90210)));
+        assert_eq!(rows[2].get("qty"), Some(&json!("n/a")));
+
+        let result = process_spreadsheet_direct(&OptionSet::new(&path).infer_column_types()).unwrap();
+        let rows = result.to_vec();
+        assert_eq!(rows[0].get("zip"), Some(&json!("02134")));
+        assert_eq!(rows[1].get("zip"), Some(&json!("90210")), "the whole column is text, not just the leading-zero cell");
+        assert_eq!(rows[0].get("id"), Some(&json!(1)));
+        assert_eq!(rows[2].get("qty"), Some(&Value::Null), "a mostly-numeric column no longer emits a stray string");
+        assert_eq!(result.columns[1].format.to_string(), "text");
+    }
+
+    #[test]
+    fn test_infer_column_types_never_replaces_explicit_formats() {
+        let path = write_csv_fixture("infer_column_types_override.csv", "id,zip\n1,02134\n2,90210\n");
+        let opts = OptionSet::new(&path)
+            .override_columns(&[json!({"source_key": "zip", "format": "integer"})])
+            .infer_column_types();
+        let rows = process_spreadsheet_direct(&opts).unwrap().to_vec();
+        assert_eq!(rows[0].get("zip"), Some(&json!(2134)));
+        assert_eq!(rows[1].get("zip"), Some(&json!(90210)));
+    }
+
+    #[test]
+    fn test_infer_column_types_samples_the_transposed_range_being_read() {
+        let path = write_csv_fixture(
+            "infer_column_types_transposed.csv",
+            "Export notes,,,\nsku,A1,B2,C3\nzip,02134,90210,10001\nqty,5,n/a,7\n",
+        );
+        let opts = OptionSet::new(&path).range("A2:D4").transpose().infer_column_types();
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let rows = result.to_vec();
+        assert_eq!(rows[1].get("zip"), Some(&json!("90210")), "inferred on the record keys, not the raw first line");
+        assert_eq!(result.columns[1].format.to_string(), "text");
+    }
+
     #[test]
     fn test_csv_header_row_span_combines_a_merged_year_row_with_a_region_row() {
         // Same shape as the user's original example, as plain CSV -- the merge-shadow
@@ -2215,6 +3236,770 @@ mod tests {
         assert_eq!(result.get(3).unwrap(), true);
     }
 
+    #[test]
+    fn test_row_filter_keeps_matching_rows_and_reports_the_rest_csv() {
+        let filter = crate::RowFilter::parse(r#"country in ["United States", "France"] && age > 30"#).unwrap();
+        let opts = OptionSet::new("data/sample-data-1.csv").row_filter(filter);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let rows = result.data.first_sheet();
+        assert_eq!(rows.len(), 106);
+        assert_eq!(result.filtered_count, 294);
+        assert!(rows.iter().all(|r| r["age"].as_i64().unwrap() > 30));
+        assert_eq!(result.to_json()["filtered_count"], 294);
+    }
+
+    #[test]
+    fn test_row_filter_max_row_count_counts_only_kept_rows_xlsx() {
+        let filter = crate::RowFilter::parse("country == 'United States'").unwrap();
+        let opts = OptionSet::new("data/sample-data-1.xlsx").row_filter(filter).max_row_count(20);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let rows = result.data.first_sheet();
+        assert_eq!(rows.len(), 20);
+        assert!(rows.iter().all(|r| r["country"] == "United States"));
+        assert!(result.filtered_count > 0);
+    }
+
+    #[tokio::test]
+    async fn test_row_filter_closure_applies_before_save_fn() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let saved = Arc::new(AtomicUsize::new(0));
+        let counter = saved.clone();
+        let save: SaveRowFn = Box::new(move |row| {
+            assert_eq!(row["country"], "United States");
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        let opts = OptionSet::new("data/sample-data-1.csv")
+            .read_mode_async()
+            .filter_fn(|row| row.get("country").and_then(|v| v.as_str()) == Some("United States"));
+        let result = process_spreadsheet_async(&opts, save, None).await.unwrap();
+        assert_eq!(saved.load(Ordering::SeqCst), 100);
+        assert_eq!(result.filtered_count, 300);
+    }
+
+    #[test]
+    fn test_derived_columns_are_added_after_conversion_csv() {
+        let opts = OptionSet::new("data/sample-data-1.csv")
+            .max_row_count(2)
+            .derived_columns(&[
+                serde_json::json!({"key": "full_name", "expr": "first_name + \" \" + last_name"}),
+                serde_json::json!({"key": "bmi", "expr": "weight / (height / 100 * height / 100)"}),
+                serde_json::json!({"key": {"type": "object", "key": "source", "next": "row"}, "expr": "row_number()"}),
+                serde_json::json!({"key": {"type": "object", "key": "source", "next": "file"}, "expr": "sheet_name()"}),
+                serde_json::json!({"key": "import_batch", "value": "2026-10"}),
+            ])
+            .unwrap();
+        let rows = process_spreadsheet_direct(&opts).unwrap().data.first_sheet();
+        assert_eq!(rows[0]["full_name"], "Dulce Abril");
+        assert_eq!(rows[0]["source"], serde_json::json!({"row": 2, "file": "sample-data-1"}));
+        assert_eq!(rows[1]["source"]["row"], 3);
+        assert_eq!(rows[1]["import_batch"], "2026-10");
+        let bmi = rows[0]["bmi"].as_f64().unwrap();
+        assert!((bmi - 103.0 / (1.64 * 1.64)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_row_number_counts_sheet_rows_above_a_cell_range_xlsx_and_csv() {
+        for path in ["data/sample-data-1.xlsx", "data/sample-data-1.csv"] {
+            let opts = OptionSet::new(path)
+                .range("A5:J8")
+                .derived_column(crate::DerivedColumn::from_expr("row", "row_number()").unwrap());
+            let rows = process_spreadsheet_direct(&opts).unwrap().data.first_sheet();
+            assert_eq!(rows[0]["row"], 6, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_derived_columns_can_be_filtered_on_xlsx() {
+        let opts = OptionSet::new("data/sample-data-1.xlsx")
+            .derived_column(crate::DerivedColumn::from_expr("sheet", "sheet_name()").unwrap())
+            .derived_column(crate::DerivedColumn::from_expr("score_per_year", "score / age").unwrap())
+            .row_filter(crate::RowFilter::parse("score_per_year > 60").unwrap());
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let rows = result.data.first_sheet();
+        assert!(!rows.is_empty());
+        assert!(result.filtered_count > 0);
+        assert!(rows.iter().all(|r| r["score_per_year"].as_f64().unwrap() > 60.0));
+        assert_eq!(rows[0]["sheet"], serde_json::json!(result.sheets[0]));
+    }
+
+    #[test]
+    fn test_unpivot_splits_a_multi_row_header_into_variable_fields_xlsx() {
+        let path = gen_multi_row_header_fixture("unpivot_multi_row_header.xlsx");
+        let opts = OptionSet::new(&path)
+            .header_row(0)
+            .header_row_span(2)
+            .unpivot(crate::reshape::Unpivot::matching(&[], "20*").variable_names(&["year", "region"]).value_name("amount"))
+            .row_filter(crate::RowFilter::parse("amount > 150").unwrap());
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(json!(result.to_vec()), json!([
+            {"year": 2025, "region": "north", "amount": 200.0},
+            {"year": 2025, "region": "midlands", "amount": 201.0},
+            {"year": 2025, "region": "south", "amount": 202.0},
+        ]));
+        assert_eq!(result.filtered_count, 3);
+        assert_eq!(result.keys, vec!["year", "region", "amount"]);
+        assert!(result.columns.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unpivot_max_row_count_counts_output_rows_and_streams_csv() {
+        let unpivot = crate::reshape::Unpivot::new(&["id"], &["score", "weight", "height"]).variable_names(&["measure"]);
+        let opts = OptionSet::new("data/sample-data-1.csv").unpivot(unpivot.clone()).max_row_count(5);
+        let rows = process_spreadsheet_core(&opts, None, None).await.unwrap().to_vec();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(json!(rows[3]), json!({"id": 2, "measure": "score", "value": 1582}));
+
+        let saved = std::sync::Arc::new(std::sync::Mutex::new(0usize));
+        let counter = saved.clone();
+        let save: SaveRowFn = Box::new(move |_row| {
+            *counter.lock().unwrap() += 1;
+            Ok(())
+        });
+        let opts = OptionSet::new("data/sample-data-1.csv").unpivot(unpivot).read_mode_async();
+        let result = process_spreadsheet_async(&opts, save, None).await.unwrap();
+        assert_eq!(*saved.lock().unwrap(), 1200);
+        assert_eq!(result.keys, vec!["id", "measure", "value"]);
+    }
+
+    #[test]
+    fn test_pivot_reverses_an_unpivot_into_one_object_per_region_xlsx() {
+        let path = gen_multi_row_header_fixture("pivot_multi_row_header.xlsx");
+        let opts = OptionSet::new(&path)
+            .header_row(0)
+            .header_row_span(2)
+            .unpivot(crate::Unpivot::matching(&[], "20*").variable_names(&["year", "region"]).value_name("amount"))
+            .pivot(crate::Pivot::new(&["region"], "year", "amount").nest_in("years"));
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(result.keys, vec!["region", "years"]);
+        assert!(result.columns.is_empty());
+        assert_eq!(json!(result.to_vec()[0]), json!({
+            "region": "north",
+            "years": [{"year": 2015, "amount": 100.0}, {"year": 2025, "amount": 200.0}],
+        }));
+        assert_eq!(result.to_vec().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pivot_buffers_rows_for_the_save_fn_csv() {
+        let saved = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = saved.clone();
+        let save: SaveRowFn = Box::new(move |row| {
+            sink.lock().unwrap().push(row);
+            Ok(())
+        });
+        let opts = OptionSet::new("data/sample-data-1.csv")
+            .read_mode_async()
+            .pivot(crate::Pivot::new(&["country"], "gender", "score").aggregate(crate::PivotAggregate::Sum));
+        let result = process_spreadsheet_async(&opts, save, None).await.unwrap();
+        let rows = saved.lock().unwrap();
+        assert_eq!(rows.len(), 58);
+        assert_eq!(result.num_rows, 58);
+        assert_eq!(result.keys[..3], ["country", "Female", "Male"]);
+        assert_eq!(rows[0]["country"], "United States");
+        let france = rows.iter().find(|r| r["country"] == "France").unwrap();
+        assert_eq!(france["Female"], 327125);
+    }
+
+    const ORDER_LINES_CSV: &str = "order_id,customer,date,sku,qty\n\
+        1001,Ada,2026-10-01,A1,2\n\
+        1001,Ada,2026-10-01,B2,1\n\
+        1002,Bo,2026-10-02,A1,5\n\
+        1003,Cy,2026-10-02,C3,1\n\
+        1003,Cy,2026-10-02,A1,4\n";
+
+    #[test]
+    fn test_group_rows_nests_line_items_under_each_order_csv() {
+        let path = write_csv_fixture("group_order_lines.csv", ORDER_LINES_CSV);
+        let opts = OptionSet::new(&path).group_rows(crate::GroupRows::new(&["order_id"], &["sku", "qty"]));
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let rows = result.to_vec();
+        assert_eq!(result.keys, vec!["order_id", "customer", "date", "items"]);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(json!(rows[0]), json!({
+            "order_id": 1001, "customer": "Ada", "date": "2026-10-01",
+            "items": [{"sku": "A1", "qty": 2}, {"sku": "B2", "qty": 1}],
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_group_rows_streams_sorted_input_to_the_save_fn_csv() {
+        let path = write_csv_fixture("group_order_lines_stream.csv", ORDER_LINES_CSV);
+        let saved = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = saved.clone();
+        let save: SaveRowFn = Box::new(move |row| {
+            sink.lock().unwrap().push(row);
+            Ok(())
+        });
+        let opts = OptionSet::new(&path)
+            .read_mode_async()
+            .group_rows(crate::GroupRows::new(&["order_id"], &["sku", "qty"]).container("lines").sorted());
+        process_spreadsheet_async(&opts, save, None).await.unwrap();
+        let rows = saved.lock().unwrap();
+        let counts: Vec<usize> = rows.iter().map(|r| r["lines"].as_array().unwrap().len()).collect();
+        assert_eq!(counts, vec![2, 1, 2]);
+        assert_eq!(rows[2]["customer"], "Cy");
+    }
+
+    #[test]
+    fn test_aggregate_counts_rows_and_averages_age_by_country_csv() {
+        let aggregation = crate::Aggregation::from_json(&json!({
+            "group_by": ["country"],
+            "metrics": [{"op": "count"}, {"op": "mean", "field": "age"}, {"op": "max", "field": "age", "as": "oldest"}]
+        })).unwrap();
+        let opts = OptionSet::new("data/sample-data-1.csv").aggregate(aggregation);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let rows = result.to_vec();
+        assert_eq!(result.keys, vec!["country", "count", "mean_age", "oldest"]);
+        assert_eq!(rows.len(), 58);
+        assert_eq!(rows[0]["country"], "United States");
+        assert_eq!(rows[0]["count"], 100);
+        assert_eq!(rows.iter().map(|r| r["count"].as_u64().unwrap()).sum::<u64>(), 400);
+    }
+
+    #[test]
+    fn test_sort_and_dedup_by_country_csv() {
+        let opts = OptionSet::new("data/sample-data-1.csv")
+            .dedup(crate::Dedup::by(&["country"]))
+            .sort(crate::SortSpec::new(vec![crate::SortKey::desc("country").collation(crate::Collation::CaseInsensitive)]));
+        let rows = process_spreadsheet_direct(&opts).unwrap().to_vec();
+        assert_eq!(rows.len(), 58);
+        let countries: Vec<&str> = rows.iter().map(|r| r["country"].as_str().unwrap()).collect();
+        let mut expected = countries.clone();
+        expected.sort_by_key(|c| std::cmp::Reverse(c.to_lowercase()));
+        assert_eq!(countries, expected);
+    }
+
+    #[tokio::test]
+    async fn test_sort_streams_through_spilled_chunks_to_the_save_fn_csv() {
+        let saved = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = saved.clone();
+        let save: SaveRowFn = Box::new(move |row| {
+            sink.lock().unwrap().push(row);
+            Ok(())
+        });
+        let opts = OptionSet::new("data/sample-data-1.csv")
+            .read_mode_async()
+            .sort(crate::SortSpec::by(&["-age", "id"]).unwrap().chunk_size(64));
+        process_spreadsheet_async(&opts, save, None).await.unwrap();
+        let rows = saved.lock().unwrap();
+        assert_eq!(rows.len(), 400);
+        let keys: Vec<(i64, i64)> = rows.iter().map(|r| (r["age"].as_i64().unwrap(), r["id"].as_i64().unwrap())).collect();
+        let mut expected = keys.clone();
+        expected.sort_by_key(|(age, id)| (std::cmp::Reverse(*age), *id));
+        assert_eq!(keys, expected);
+    }
+
+    /// An `orders` sheet referencing a `products` sheet by code, with one unknown code.
+    fn write_orders_workbook(filename: &str) -> String {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let orders = workbook.add_worksheet().set_name("orders").unwrap();
+        orders.write_row(0, 0, ["order_id", "product_code", "qty"]).unwrap();
+        for (index, (code, qty)) in [("P1", 2.0), ("P2", 1.0), ("ZZ", 4.0), ("P1", 5.0)].iter().enumerate() {
+            let row = index as u32 + 1;
+            orders.write_number(row, 0, 1000.0 + row as f64).unwrap();
+            orders.write_string(row, 1, *code).unwrap();
+            orders.write_number(row, 2, *qty).unwrap();
+        }
+        let products = workbook.add_worksheet().set_name("products").unwrap();
+        products.write_row(0, 0, ["code", "name", "price"]).unwrap();
+        products.write_string(1, 0, "P1").unwrap();
+        products.write_string(1, 1, "Bolt").unwrap();
+        products.write_number(1, 2, 0.25).unwrap();
+        products.write_string(2, 0, "P2").unwrap();
+        products.write_string(2, 1, "Nut").unwrap();
+        products.write_number(2, 2, 0.1).unwrap();
+        let path = std::env::temp_dir().join(filename);
+        workbook.save(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_lookup_joins_products_sheet_left_and_inner_xlsx() {
+        let path = write_orders_workbook("lookup_orders.xlsx");
+        let left = OptionSet::new(&path)
+            .sheet_name("orders")
+            .lookup(crate::Lookup::sheet("products", "product_code", "code").fields(&["name", "price"]));
+        let result = process_spreadsheet_direct(&left).unwrap();
+        let rows = result.to_vec();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(json!(rows[0]), json!({"order_id": 1001.0, "product_code": "P1", "qty": 2.0, "name": "Bolt", "price": 0.25}));
+        assert_eq!(rows[2]["name"], Value::Null);
+        assert_eq!(result.unmatched_keys["products:product_code"], vec![json!("ZZ")]);
+        assert_eq!(result.to_json()["unmatched_keys"], json!({"products:product_code": ["ZZ"]}));
+
+        let inner = OptionSet::new(&path).sheet_name("orders").lookups(&[json!({
+            "sheet": "products", "local_key": "product_code", "foreign_key": "code",
+            "fields": ["name"], "target": "product", "join": "inner"
+        })]).unwrap();
+        let rows = process_spreadsheet_direct(&inner).unwrap().to_vec();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(json!(rows[1]), json!({"order_id": 1002.0, "product_code": "P2", "qty": 1.0, "product": {"name": "Nut"}}));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_against_another_file_while_streaming_csv() {
+        let products = write_csv_fixture("lookup_products.csv", "code,name\nP1,Bolt\nP2,Nut\n");
+        let orders = write_csv_fixture("lookup_order_lines.csv", "order_id,product_code\n1,P2\n2,P9\n3,P1\n");
+        let saved = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = saved.clone();
+        let save: SaveRowFn = Box::new(move |row| {
+            sink.lock().unwrap().push(row);
+            Ok(())
+        });
+        let opts = OptionSet::new(&orders)
+            .read_mode_async()
+            .lookup(crate::Lookup::file(OptionSet::new(&products), "product_code", "code").nest_in("product").inner());
+        let result = process_spreadsheet_async(&opts, save, None).await.unwrap();
+        let rows = saved.lock().unwrap();
+        let names: Vec<&Value> = rows.iter().map(|r| &r["product"]["name"]).collect();
+        assert_eq!(names, vec![&json!("Nut"), &json!("Bolt")]);
+        assert_eq!(result.unmatched_keys["lookup_products.csv:product_code"], vec![json!("P9")]);
+    }
+
+    /// Two regional sheets: `north` with its header on the first row, `south` below a
+    /// title and a blank row, plus an unrelated `notes` sheet.
+    fn write_regional_workbook(filename: &str) -> String {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let north = workbook.add_worksheet().set_name("north").unwrap();
+        north.write_row(0, 0, ["store", "revenue"]).unwrap();
+        north.write_row(1, 0, ["Leeds", "1200"]).unwrap();
+        north.write_row(2, 0, ["York", "800"]).unwrap();
+        let south = workbook.add_worksheet().set_name("south").unwrap();
+        south.write_string(0, 0, "Southern stores").unwrap();
+        south.write_row(2, 0, ["store", "revenue"]).unwrap();
+        south.write_row(3, 0, ["Bath", "950"]).unwrap();
+        let notes = workbook.add_worksheet().set_name("notes").unwrap();
+        notes.write_string(0, 0, "Figures are provisional").unwrap();
+        let path = std::env::temp_dir().join(filename);
+        workbook.save(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_multiple_mode_reads_selected_sheets_with_their_own_header_rows_xlsx() {
+        let path = write_regional_workbook("multi_sheet_regions.xlsx");
+        let opts = OptionSet::new(&path)
+            .read_mode_multiple()
+            .sheet_names(&["north".to_string(), "south".to_string()])
+            .sheet_options("south", crate::SheetOptions::new().header_row(2).data_row_index(3))
+            .sheet_options("north", crate::SheetOptions::new().override_columns(&[json!({"key": "branch"})]));
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let sheets = result.data.sheets();
+        assert_eq!(result.sheets, vec!["north", "south"]);
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(json!(sheets[0].rows), json!([{"branch": "Leeds", "revenue": "1200"}, {"branch": "York", "revenue": "800"}]));
+        assert_eq!((sheets[0].header_row_index, sheets[0].body_start_index), (Some(0), 1));
+        assert_eq!(json!(sheets[1].rows), json!([{"store": "Bath", "revenue": "950"}]));
+        assert_eq!((sheets[1].header_row_index, sheets[1].body_start_index), (Some(2), 3));
+        let south = &result.to_json()["data"][1];
+        assert_eq!((&south["header_row_index"], &south["body_start_index"]), (&json!(2), &json!(3)));
+    }
+
+    #[tokio::test]
+    async fn test_async_multiple_mode_tags_saved_rows_with_their_sheet_xlsx() {
+        let path = write_regional_workbook("multi_sheet_regions_async.xlsx");
+        let saved = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = saved.clone();
+        let save: SaveRowFn = Box::new(move |row| {
+            sink.lock().unwrap().push(row);
+            Ok(())
+        });
+        let opts = OptionSet::new(&path)
+            .read_mode_async_multiple()
+            .all_sheets()
+            .sheet_options("south", crate::SheetOptions::from_json(&json!({"header_row": 2, "data_row_index": 3})))
+            .sheet_options("notes", crate::SheetOptions::new().omit_header(true))
+            .sheet_tag("region");
+        let result = process_spreadsheet_async(&opts, save, None).await.unwrap();
+        assert_eq!(result.sheets, vec!["north", "south", "notes"]);
+        assert!(result.data.sheets().iter().all(|sheet| sheet.rows.is_empty()));
+        let rows = saved.lock().unwrap();
+        let tags: Vec<&str> = rows.iter().map(|r| r["region"].as_str().unwrap()).collect();
+        assert_eq!(tags, vec!["north", "north", "south", "notes"]);
+        assert_eq!(rows[2].keys().collect::<Vec<_>>(), vec!["region", "store", "revenue"]);
+    }
+
+    #[test]
+    fn test_union_appends_matching_sheets_aligned_by_natural_key_xlsx() {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let jan = workbook.add_worksheet().set_name("2026-01").unwrap();
+        jan.write_row(0, 0, ["store", "revenue"]).unwrap();
+        jan.write_string(1, 0, "Leeds").unwrap();
+        jan.write_number(1, 1, 100.0).unwrap();
+        // same columns in a different order
+        let feb = workbook.add_worksheet().set_name("2026-02").unwrap();
+        feb.write_row(0, 0, ["revenue", "store"]).unwrap();
+        feb.write_number(1, 0, 120.0).unwrap();
+        feb.write_string(1, 1, "York").unwrap();
+        let mar = workbook.add_worksheet().set_name("2026-03").unwrap();
+        mar.write_row(0, 0, ["store", "revenue", "notes"]).unwrap();
+        mar.write_string(1, 0, "Bath").unwrap();
+        mar.write_number(1, 1, 90.0).unwrap();
+        mar.write_string(1, 2, "refit").unwrap();
+        let summary = workbook.add_worksheet().set_name("Summary").unwrap();
+        summary.write_row(0, 0, ["total", "310"]).unwrap();
+        let path = std::env::temp_dir().join("union_months.xlsx");
+        workbook.save(&path).unwrap();
+
+        let opts = OptionSet::new(path.to_str().unwrap())
+            .union_sheets("2026-*")
+            .override_columns(&[json!({}), json!({"key": "amount", "format": "integer"})]);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(result.keys, vec!["_sheet", "store", "amount", "notes"]);
+        assert_eq!(json!(result.to_vec()), json!([
+            {"_sheet": "2026-01", "store": "Leeds", "amount": 100, "notes": null},
+            {"_sheet": "2026-02", "store": "York", "amount": 120, "notes": null},
+            {"_sheet": "2026-03", "store": "Bath", "amount": 90, "notes": "refit"},
+        ]));
+        assert_eq!(result.warnings, vec!["sheet `2026-03` columns differ from `2026-01`: extra notes"]);
+
+        let opts = OptionSet::new(path.to_str().unwrap()).union_sheet_indices(&[0, 2]).sheet_tag("month").max_row_count(1);
+        let rows = process_spreadsheet_direct(&opts).unwrap().to_vec();
+        assert_eq!(json!(rows), json!([{"month": "2026-01", "store": "Leeds", "revenue": 100.0}]));
+    }
+
+    #[test]
+    fn test_sheet_patterns_skip_hidden_sheets_and_strict_matching_xlsx() {
+        use rust_xlsxwriter::{Chart, ChartType, Workbook};
+        let mut workbook = Workbook::new();
+        for (name, revenue) in [("Sales 2025", 800.0), ("Sales 2026", 950.0), ("Sales draft", 10.0)] {
+            let sheet = workbook.add_worksheet().set_name(name).unwrap();
+            sheet.write_row(0, 0, ["store", "revenue"]).unwrap();
+            sheet.write_string(1, 0, "Leeds").unwrap();
+            sheet.write_number(1, 1, revenue).unwrap();
+        }
+        let archive = workbook.add_worksheet().set_name("Sales archive").unwrap();
+        archive.write_row(0, 0, ["store", "revenue"]).unwrap();
+        archive.set_hidden(true);
+        let lookup = workbook.add_worksheet().set_name("Sales codes").unwrap();
+        lookup.write_row(0, 0, ["code", "label"]).unwrap();
+        lookup.set_very_hidden(true);
+        let mut chart = Chart::new(ChartType::Column);
+        chart.add_series().set_values("='Sales 2026'!$B$2:$B$2");
+        workbook.add_chartsheet().set_name("Sales chart").unwrap().insert_chart(0, 0, &chart).unwrap();
+        let path = std::env::temp_dir().join("sheet_patterns.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let opts = OptionSet::new(path)
+            .read_mode_multiple()
+            .sheet_pattern("sales*")
+            .exclude_sheets(&["*draft"])
+            .skip_hidden_sheets()
+            .skip_chart_sheets();
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(result.sheets, vec!["Sales 2025", "Sales 2026"]);
+
+        let opts = OptionSet::new(path)
+            .read_mode_multiple()
+            .select_sheets(crate::SheetPattern::parse("/^Sales (\\d{4}|archive|codes)$/").unwrap())
+            .skip_very_hidden_sheets()
+            .skip_chart_sheets();
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(result.sheets, vec!["Sales 2025", "Sales 2026", "Sales archive"]);
+
+        // without strict mode an unknown sheet falls back to the first one
+        let opts = OptionSet::new(path).sheet_name("Forecast");
+        let rows = process_spreadsheet_direct(&opts).unwrap().to_vec();
+        assert_eq!(json!(rows), json!([{"store": "Leeds", "revenue": 800.0}]));
+        let opts = OptionSet::new(path).sheet_name("Forecast").strict_sheet_match();
+        assert_eq!(process_spreadsheet_direct(&opts).unwrap_err().to_string(), "no_matching_sheets");
+    }
+
+    #[test]
+    fn test_cell_range_restricts_reading_before_header_detection_xlsx_and_csv() {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Report").unwrap();
+        sheet.write_string(0, 0, "Quarterly report").unwrap();
+        sheet.write_string(3, 2, "Stores").unwrap();
+        sheet.write_row(4, 2, ["store", "qty", "region"]).unwrap();
+        sheet.write_row(5, 2, ["Leeds", "12", "north"]).unwrap();
+        sheet.write_row(6, 2, ["Bath", "9", "south"]).unwrap();
+        sheet.write_row(7, 2, ["York", "4", "north"]).unwrap();
+        sheet.write_string(5, 6, "see note").unwrap();
+        let path = std::env::temp_dir().join("cell_range_report.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let result = process_spreadsheet_direct(&OptionSet::new(path).range("C5:E7")).unwrap();
+        assert_eq!(json!(result.to_vec()), json!([
+            {"store": "Leeds", "qty": "12", "region": "north"},
+            {"store": "Bath", "qty": "9", "region": "south"},
+        ]));
+        assert_eq!(result.cell_range.unwrap().to_string(), "C5:E7");
+
+        // header and data rows count from the first row of the range
+        let opts = OptionSet::new(path).range("c4:d").header_row(1).data_row_index(3);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(json!(result.to_vec()), json!([{"store": "Bath", "qty": "9"}, {"store": "York", "qty": "4"}]));
+        assert_eq!((result.header_row_index, result.body_start_index), (Some(1), 3));
+        assert_eq!(result.cell_range.unwrap().to_string(), "C4:D8");
+        assert_eq!(result.to_json()["range"], json!("C4:D8"));
+
+        let csv_path = std::env::temp_dir().join("cell_range_report.csv");
+        std::fs::write(&csv_path, "Quarterly report,,,,\n,,,,\n,,store,qty,note\n,,Leeds,12,a\n,,Bath,9,b\ntotal,,,21,\n").unwrap();
+        let opts = OptionSet::new(csv_path.to_str().unwrap()).range("C3:D5");
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(json!(result.to_vec()), json!([{"store": "Leeds", "qty": 12}, {"store": "Bath", "qty": 9}]));
+        assert_eq!(result.cell_range.unwrap().to_string(), "C3:D5");
+
+        let opts = OptionSet::new(path).range("E5:C9");
+        assert_eq!(process_spreadsheet_direct(&opts).unwrap_err().to_string(), "invalid_cell_range");
+    }
+
+    #[tokio::test]
+    async fn test_detect_headers_scores_rows_of_the_selected_source() {
+        let csv_path = std::env::temp_dir().join("detect_headers_sales.csv");
+        std::fs::write(&csv_path, "Sales 2025,,\n,,\nregion,revenue,updated\nnorth,1200,2025-03-01\nsouth,950,2025-03-02\n").unwrap();
+        let csv_path = csv_path.to_str().unwrap();
+        let detection = detect_headers(&OptionSet::new(csv_path)).await.unwrap();
+        assert_eq!((detection.header_index, detection.data_index), (Some(2), 3));
+        assert_eq!(detection.confidence, DetectionConfidence::High);
+        assert_eq!(detection.candidates[0].row_index, 2);
+
+        // within a range, indices count from its first row
+        let detection = detect_headers(&OptionSet::new(csv_path).range("A3:C5")).await.unwrap();
+        assert_eq!((detection.header_index, detection.data_index), (Some(0), 1));
+        assert_eq!(detect_headers(&OptionSet::new("missing.csv")).await.unwrap_err().to_string(), "file_unavailable");
+    }
+
+    #[test]
+    fn test_transposed_records_read_with_column_overrides_xlsx_and_csv() {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Suppliers").unwrap();
+        sheet.write_row(0, 0, ["Supplier", "Acme Ltd", "Brook & Co"]).unwrap();
+        sheet.write_string(1, 0, "Lead time").unwrap();
+        sheet.write_row(1, 1, [12.0, 9.5]).unwrap();
+        sheet.write_row(2, 0, ["Approved", "yes", "no"]).unwrap();
+        sheet.write_row(3, 0, ["Since", "2021-04-01", "2023-11-15"]).unwrap();
+        let path = std::env::temp_dir().join("transposed_suppliers.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+        let columns = [
+            json!({"source_key": "lead_time", "format": "integer", "key": {"type": "object", "key": "terms", "next": "days"}}),
+            json!({"source_key": "approved", "format": "truthy"}),
+        ];
+
+        let result = process_spreadsheet_direct(&OptionSet::new(path).transpose().override_columns(&columns)).unwrap();
+        assert_eq!(result.keys, vec!["supplier", "terms", "approved", "since"]);
+        assert_eq!(json!(result.to_vec()), json!([
+            {"supplier": "Acme Ltd", "terms": {"days": 12}, "approved": true, "since": "2021-04-01"},
+            {"supplier": "Brook & Co", "terms": {"days": 9}, "approved": false, "since": "2023-11-15"},
+        ]));
+        assert!(result.transposed);
+        assert_eq!(result.to_json()["transposed"], json!(true));
+
+        let detected = process_spreadsheet_direct(&OptionSet::new(path).detect_orientation()).unwrap();
+        assert!(detected.transposed);
+        assert_eq!(detected.to_vec().len(), 2);
+
+        // an ordinary table is left as it is
+        let csv_path = std::env::temp_dir().join("transposed_plain.csv");
+        std::fs::write(&csv_path, "supplier,lead_time,since\nAcme Ltd,12,2021-04-01\nBrook & Co,9,2023-11-15\n").unwrap();
+        let result = process_spreadsheet_direct(&OptionSet::new(csv_path.to_str().unwrap()).detect_orientation()).unwrap();
+        assert!(!result.transposed);
+        assert_eq!(result.to_vec().len(), 2);
+
+        let csv_path = std::env::temp_dir().join("transposed_suppliers.csv");
+        std::fs::write(&csv_path, "Notes,,,\nSupplier,Acme Ltd,Brook & Co,\nLead time,12,9,\nSince,2021-04-01,2023-11-15,\n").unwrap();
+        let opts = OptionSet::new(csv_path.to_str().unwrap()).range("A2:C4").detect_orientation();
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert!(result.transposed);
+        assert_eq!(json!(result.to_vec()), json!([
+            {"supplier": "Acme Ltd", "lead_time": 12, "since": "2021-04-01"},
+            {"supplier": "Brook & Co", "lead_time": 9, "since": "2023-11-15"},
+        ]));
+        assert_eq!(result.cell_range.unwrap().to_string(), "A2:C4");
+    }
+
+    #[test]
+    fn test_form_values_are_read_with_a_table_from_the_same_sheet_xlsx_and_csv() {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Intake").unwrap();
+        sheet.write_row(0, 0, ["Customer:", "Ada Byron", "", "Order no:"]).unwrap();
+        sheet.write_number(0, 4, 1042.0).unwrap();
+        sheet.write_string(1, 0, "Express").unwrap();
+        sheet.write_string(1, 1, "yes").unwrap();
+        sheet.write_string(2, 3, "Delivery date").unwrap();
+        sheet.write_string(3, 3, "2026-05-01").unwrap();
+        sheet.write_row(5, 0, ["item", "qty"]).unwrap();
+        sheet.write_row(6, 0, ["Desk", "2"]).unwrap();
+        sheet.write_row(7, 0, ["Chair", "4"]).unwrap();
+        let path = std::env::temp_dir().join("form_intake.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let form = FormExtraction::new()
+            .field(FormField::new("express").unwrap().format(Format::Truthy))
+            .field(FormField::new("delivery*").unwrap().key("delivery").offset(1, 0))
+            .field(FormField::new("Phone").unwrap())
+            .auto_labels();
+        let result = process_spreadsheet_direct(&OptionSet::new(path).range("A6:B8").form(form)).unwrap();
+        assert_eq!(json!(result.form), json!({
+            "express": true,
+            "delivery": "2026-05-01",
+            "phone": null,
+            "customer": "Ada Byron",
+            "order_no": 1042.0,
+        }));
+        assert_eq!(json!(result.to_vec()), json!([{"item": "Desk", "qty": "2"}, {"item": "Chair", "qty": "4"}]));
+        assert_eq!(result.to_json()["form"]["customer"], json!("Ada Byron"));
+
+        let csv_path = std::env::temp_dir().join("form_intake.csv");
+        std::fs::write(&csv_path, "Customer:,Ada Byron,,Order no:,1042\n,,,,\nitem,qty,,,\nDesk,2,,,\n").unwrap();
+        let form = FormExtraction::from_labels(&["customer", "order no"]).unwrap();
+        let result = process_spreadsheet_direct(&OptionSet::new(csv_path.to_str().unwrap()).range("A3:B4").form(form)).unwrap();
+        assert_eq!(json!(result.form), json!({"customer": "Ada Byron", "order_no": 1042}));
+        assert_eq!(json!(result.to_vec()), json!([{"item": "Desk", "qty": 2}]));
+
+        let opts = OptionSet::new(path).all_sheets().read_mode_multiple().form(FormExtraction::new().auto_labels());
+        assert_eq!(process_spreadsheet_direct(&opts).unwrap_err(), GenericError("form_with_multiple_sheets"));
+    }
+
+    #[test]
+    fn test_footer_rows_are_detected_or_cut_explicitly_xlsx_and_csv() {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Sales").unwrap();
+        sheet.write_row(0, 0, ["store", "qty", "region"]).unwrap();
+        sheet.write_row(1, 0, ["Leeds", "12", "north"]).unwrap();
+        sheet.write_row(2, 0, ["Bath", "9", "south"]).unwrap();
+        sheet.write_row(3, 0, ["York", "4", "north"]).unwrap();
+        sheet.write_row(4, 0, ["Total", "25"]).unwrap();
+        sheet.write_string(7, 0, "Generated by the stock system").unwrap();
+        let path = std::env::temp_dir().join("footer_sales.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+        let stores = |result: &ResultSet| result.to_vec().iter().map(|row| row["store"].clone()).collect::<Vec<Value>>();
+
+        let result = process_spreadsheet_direct(&OptionSet::new(path)).unwrap();
+        assert_eq!(result.to_vec().len(), 7);
+        assert_eq!(result.body_end_index, None);
+
+        let result = process_spreadsheet_direct(&OptionSet::new(path).detect_footer()).unwrap();
+        assert_eq!(stores(&result), vec![json!("Leeds"), json!("Bath"), json!("York")]);
+        assert_eq!(result.body_end_index, Some(4));
+        assert_eq!(result.to_json()["body_end_index"], json!(4));
+
+        let result = process_spreadsheet_direct(&OptionSet::new(path).data_end_row(2)).unwrap();
+        assert_eq!(stores(&result), vec![json!("Leeds"), json!("Bath")]);
+        let result = process_spreadsheet_direct(&OptionSet::new(path).data_end_row(4).skip_footer(2)).unwrap();
+        assert_eq!(stores(&result), vec![json!("Leeds"), json!("Bath")]);
+
+        let csv_path = std::env::temp_dir().join("footer_sales.csv");
+        std::fs::write(&csv_path, "store,qty,region\nLeeds,12,north\nBath,9,south\n,,\nYork,4,north\nTotal:,25,\n,,\n,,\nGenerated by the stock system,,\n").unwrap();
+        let csv_path = csv_path.to_str().unwrap();
+        let result = process_spreadsheet_direct(&OptionSet::new(csv_path).detect_footer()).unwrap();
+        assert_eq!(stores(&result), vec![json!("Leeds"), json!("Bath"), json!(""), json!("York")]);
+        assert_eq!((result.body_end_index, result.num_rows), (Some(5), 9));
+        let result = process_spreadsheet_direct(&OptionSet::new(csv_path).skip_footer(4)).unwrap();
+        assert_eq!(result.to_vec().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_tables_and_defined_names_as_sources_xlsx() {
+        use rust_xlsxwriter::{Table, TableColumn, TableFunction, Workbook};
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Finance").unwrap();
+        sheet.write_string(0, 0, "Budget 2026 -- provisional").unwrap();
+        sheet.write_row(2, 1, ["Cost centre", "Budget"]).unwrap();
+        sheet.write_string(3, 1, "IT").unwrap();
+        sheet.write_number(3, 2, 1200.0).unwrap();
+        sheet.write_string(4, 1, "HR").unwrap();
+        sheet.write_number(4, 2, 800.0).unwrap();
+        let columns = [
+            TableColumn::new().set_header("Cost centre").set_total_label("Total"),
+            TableColumn::new().set_header("Budget").set_total_function(TableFunction::Sum),
+        ];
+        sheet.add_table(2, 1, 5, 2, &Table::new().set_name("Budgets").set_columns(&columns).set_total_row(true)).unwrap();
+        sheet.write_row(8, 4, ["region", "manager"]).unwrap();
+        sheet.write_row(9, 4, ["north", "Ana"]).unwrap();
+        sheet.write_row(10, 4, ["south", "Raj"]).unwrap();
+        workbook.define_name("Managers", "=Finance!$E$9:$F$11").unwrap();
+        let sheet = workbook.worksheet_from_name("Finance").unwrap();
+        sheet.write_row(13, 1, ["Ana", "A"]).unwrap();
+        sheet.write_row(14, 1, ["Raj", "B"]).unwrap();
+        let columns = [TableColumn::new().set_header("Name"), TableColumn::new().set_header("Grade")];
+        sheet.add_table(13, 1, 14, 2, &Table::new().set_name("Staff").set_header_row(false).set_columns(&columns)).unwrap();
+        let path = std::env::temp_dir().join("tables_and_names.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let opts = OptionSet::new(path).table("budgets").detect_header();
+        let result = process_spreadsheet_core(&opts, None, None).await.unwrap();
+        assert_eq!(json!(result.to_vec()), json!([{"cost_centre": "IT", "budget": 1200.0}, {"cost_centre": "HR", "budget": 800.0}]));
+        assert_eq!(result.cell_range.unwrap().to_string(), "B3:C5");
+        assert_eq!(result.selected, Some(vec!["Finance".to_string()]));
+
+        let opts = OptionSet::new(path).defined_name("Managers");
+        let rows = process_spreadsheet_core(&opts, None, None).await.unwrap().to_vec();
+        assert_eq!(json!(rows), json!([{"region": "north", "manager": "Ana"}, {"region": "south", "manager": "Raj"}]));
+
+        let opts = OptionSet::new(path).table("Forecast");
+        assert_eq!(process_spreadsheet_core(&opts, None, None).await.unwrap_err().to_string(), "table_not_found");
+
+        // Without a header row, the table's column names become the keys
+        let rows = process_spreadsheet_core(&OptionSet::new(path).table("Staff"), None, None).await.unwrap().to_vec();
+        assert_eq!(json!(rows[1]), json!({"name": "Raj", "grade": "B"}));
+
+        let info = read_workbook_info(&PathData::new(path::Path::new(path))).await.unwrap();
+        assert_eq!(info.tables.len(), 2);
+        assert_eq!(info.tables[1].to_json()["range"], "B14:C15");
+        assert_eq!(info.tables[0].to_json(), json!({
+            "name": "Budgets", "sheet": "Finance", "range": "B3:C5",
+            "columns": ["Cost centre", "Budget"], "header_row": true,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_split_tables_reads_each_stacked_table_with_its_own_keys_xlsx() {
+        use rust_xlsxwriter::Workbook;
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Report").unwrap();
+        sheet.write_string(0, 0, "Revenue by region").unwrap();
+        sheet.write_row(1, 0, ["region", "revenue"]).unwrap();
+        sheet.write_string(2, 0, "north").unwrap();
+        sheet.write_number(2, 1, 1200.0).unwrap();
+        sheet.write_string(3, 0, "south").unwrap();
+        sheet.write_number(3, 1, 950.0).unwrap();
+        sheet.write_string(6, 0, "Headcount").unwrap();
+        sheet.write_row(8, 0, ["team", "staff", "open roles"]).unwrap();
+        sheet.write_string(9, 0, "IT").unwrap();
+        sheet.write_number(9, 1, 12.0).unwrap();
+        sheet.write_number(9, 2, 2.0).unwrap();
+        let path = std::env::temp_dir().join("stacked_tables.xlsx");
+        workbook.save(&path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let opts = OptionSet::new(path).table_segmentation(crate::TableSegmentation::new().title_names());
+        let result = process_spreadsheet_core(&opts, None, None).await.unwrap();
+        assert_eq!(result.sheets, vec!["Revenue by region", "Headcount"]);
+        let tables = result.data.sheets();
+        assert_eq!(tables[0].keys, vec!["region", "revenue"]);
+        assert_eq!(json!(tables[0].rows), json!([{"region": "north", "revenue": 1200.0}, {"region": "south", "revenue": 950.0}]));
+        assert_eq!(json!(tables[1].rows), json!([{"team": "IT", "staff": 12.0, "open_roles": 2.0}]));
+
+        let result = process_spreadsheet_core(&OptionSet::new(path).split_tables(), None, None).await.unwrap();
+        assert_eq!(result.sheets, vec!["Report table 1", "Report table 2"]);
+
+        // A positional override only renames the first table's column
+        let opts = OptionSet::new(path).split_tables().override_columns(&[json!({"key": "area"})]);
+        let tables = process_spreadsheet_core(&opts, None, None).await.unwrap().data.sheets();
+        assert_eq!(tables[0].keys, vec!["area", "revenue"]);
+        assert_eq!(tables[1].keys, vec!["team", "staff", "open_roles"]);
+
+        let opts = OptionSet::new(path).split_tables().read_mode_multiple();
+        assert_eq!(process_spreadsheet_core(&opts, None, None).await.unwrap_err(), GenericError("split_tables_with_multiple_sheets"));
+    }
+
     #[tokio::test]
     async fn test_read_workbook_info() {
         let sample_path = "data/sample-data-1.xlsx";
@@ -2223,6 +4008,38 @@ mod tests {
         assert!(info.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_read_workbook_info_reports_dimensions_names_and_properties_xlsx() {
+        use rust_xlsxwriter::{DocProperties, Workbook};
+        let mut workbook = Workbook::new();
+        workbook.set_properties(&DocProperties::new().set_title("Regional sales").set_author("Finance"));
+        let data = workbook.add_worksheet().set_name("Data").unwrap();
+        data.write_row(1, 1, ["store", "revenue", "region"]).unwrap();
+        data.write_row(2, 1, ["Leeds", "1200", "north"]).unwrap();
+        data.write_row(3, 1, ["Bath", "950", "south"]).unwrap();
+        let archive = workbook.add_worksheet().set_name("Archive").unwrap();
+        archive.write_string(0, 0, "old").unwrap();
+        archive.set_hidden(true);
+        workbook.define_name("Stores", "=Data!$B$3:$B$4").unwrap();
+        let path = std::env::temp_dir().join("workbook_info.xlsx");
+        workbook.save(&path).unwrap();
+
+        let path_data = PathData::new(path.as_path());
+        let info = read_workbook_info(&path_data).await.unwrap();
+        let data = &info.sheet_info[0];
+        assert_eq!((data.rows, data.cols, data.start), (3, 3, Some((1, 1))));
+        assert_eq!(data.range_ref().unwrap(), "B2:D4");
+        assert!(info.sheet_info[1].is_hidden());
+        assert_eq!(info.defined_names[0].name, "Stores");
+        assert_eq!(info.defined_names[0].refers_to, "Data!$B$3:$B$4");
+        let properties = info.properties.unwrap();
+        assert_eq!(properties.title.as_deref(), Some("Regional sales"));
+        assert_eq!(properties.author.as_deref(), Some("Finance"));
+        assert!(properties.created.is_some());
+        let counts = read_workbook_sheet_info(&path_data).await.unwrap();
+        assert_eq!(counts.get("Data"), Some(&3));
+    }
+
     #[tokio::test]
     async fn test_large_csv_file() {
         let sample_path = "data/large-datasheet.csv";
@@ -2237,6 +4054,14 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_aggregate_counts_every_row_beyond_the_default_row_limit() {
+        let opts = OptionSet::new("data/medium-spreadsheet-50_000.xlsx").aggregate(crate::Aggregation::new(&[]).metric(crate::Metric::count()));
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        assert_eq!(result.to_vec(), vec![IndexMap::from([("count".to_string(), json!(50_000))])]);
+        assert_eq!(result.num_rows, 1);
+    }
+
     #[tokio::test]
     async fn test_medium_excel_file() {
         let sample_path = "data/medium-spreadsheet-50_000.xlsx";
@@ -0,0 +1,730 @@
+//! Reshaping converted rows between wide and long layouts.
+//!
+//! `Unpivot` (melt) turns one wide row, such as `region, 2020, 2021, 2022`, into one long
+//! row per value column: `{region, year: 2020, value}`, `{region, year: 2021, value}`...
+//! It works row by row, so it runs inside the read loop and streams to a `SaveRowFn`
+//! like any other row.
+//!
+//! `Pivot` is the inverse: rows sharing the same index columns become one object, with
+//! one field (or one `KeySegment::Array`-style item) per distinct value of a column-name
+//! source. It needs the whole row set, so it runs once reading is done -- see
+//! `transform_rows`.
+//!
+//! `GroupRows` merges rows sharing one or more key columns into a parent document, with
+//! designated columns collected as objects into a child array -- the one-to-many shape
+//! of an order export that repeats the order fields on every line item. For input
+//! already sorted by the key it only ever holds one group at a time (`GroupStream`), so
+//! it can stream to a `SaveRowFn`; otherwise it groups through a hash map.
+use std::sync::Arc;
+
+use serde_json::{json, Map, Value};
+use indexmap::IndexMap;
+
+use crate::helpers::{float_value, integer_value};
+use crate::key_segment::{insert_key_segment, Identifier, KeySegment};
+use crate::OptionSet;
+
+pub const DEFAULT_VARIABLE_NAME: &str = "variable";
+pub const DEFAULT_VALUE_NAME: &str = "value";
+pub const DEFAULT_VARIABLE_SEPARATOR: &str = "_";
+
+/// Options for melting wide value columns into long rows. Columns are matched by output
+/// key, after header resolution and derived columns, so a multi-row header combined into
+/// `2015_female` (see `OptionSet::header_row_span`) can be split across two variable
+/// fields: `variables: ["year", "sex"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unpivot {
+    /// Copied onto every output row. Empty means every column that isn't a value column.
+    pub id_columns: Vec<String>,
+    /// Columns to melt, in output order.
+    pub value_columns: Vec<String>,
+    /// Glob-style pattern (`*` and `?`) selecting value columns, in addition to
+    /// `value_columns`. With neither set, every non-id column is melted.
+    pub value_pattern: Option<String>,
+    /// Field name(s) for the melted column key. With more than one, the key is split on
+    /// `separator` into at most that many parts, the last part keeping any remainder.
+    pub variables: Vec<String>,
+    pub value: String,
+    pub separator: String,
+    /// Skips output rows whose value is null or an empty string.
+    pub skip_empty: bool,
+}
+
+impl Default for Unpivot {
+    fn default() -> Self {
+        Unpivot {
+            id_columns: vec![],
+            value_columns: vec![],
+            value_pattern: None,
+            variables: vec![DEFAULT_VARIABLE_NAME.to_string()],
+            value: DEFAULT_VALUE_NAME.to_string(),
+            separator: DEFAULT_VARIABLE_SEPARATOR.to_string(),
+            skip_empty: false,
+        }
+    }
+}
+
+impl Unpivot {
+    /// Melts the given value columns, keeping `id_columns` on every row.
+    pub fn new(id_columns: &[&str], value_columns: &[&str]) -> Self {
+        Unpivot {
+            id_columns: id_columns.iter().map(|s| s.to_string()).collect(),
+            value_columns: value_columns.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Melts every column whose key matches `pattern`, e.g. `"20*"`.
+    pub fn matching(id_columns: &[&str], pattern: &str) -> Self {
+        Unpivot {
+            id_columns: id_columns.iter().map(|s| s.to_string()).collect(),
+            value_pattern: Some(pattern.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn variable_names(mut self, names: &[&str]) -> Self {
+        if !names.is_empty() {
+            self.variables = names.iter().map(|s| s.to_string()).collect();
+        }
+        self
+    }
+
+    pub fn value_name(mut self, name: &str) -> Self {
+        self.value = name.to_string();
+        self
+    }
+
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    pub fn skip_empty(mut self) -> Self {
+        self.skip_empty = true;
+        self
+    }
+
+    /// Parses unpivot options from JSON:
+    ///
+    /// - `"id_columns"`, `"value_columns"` -- arrays of output keys
+    /// - `"value_pattern"` -- glob pattern for value columns
+    /// - `"variable"` -- a field name, or an array of names to split the key across
+    /// - `"value"`, `"separator"` -- strings
+    /// - `"skip_empty"` -- boolean
+    pub fn from_json(json: &Value) -> Self {
+        let strings = |field: &str| -> Vec<String> {
+            match json.get(field) {
+                Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+                Some(Value::String(s)) => vec![s.clone()],
+                _ => vec![],
+            }
+        };
+        let defaults = Unpivot::default();
+        let variables = strings("variable");
+        Unpivot {
+            id_columns: strings("id_columns"),
+            value_columns: strings("value_columns"),
+            value_pattern: json.get("value_pattern").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            variables: if variables.is_empty() { defaults.variables } else { variables },
+            value: json.get("value").and_then(|v| v.as_str()).map(|s| s.to_string()).unwrap_or(defaults.value),
+            separator: json.get("separator").and_then(|v| v.as_str()).map(|s| s.to_string()).unwrap_or(defaults.separator),
+            skip_empty: json.get("skip_empty").and_then(|v| v.as_bool()).unwrap_or(false),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id_columns": self.id_columns,
+            "value_columns": self.value_columns,
+            "value_pattern": self.value_pattern,
+            "variable": self.variables,
+            "value": self.value,
+            "separator": self.separator,
+            "skip_empty": self.skip_empty,
+        })
+    }
+
+    fn is_value_column(&self, key: &str) -> bool {
+        if self.value_columns.iter().any(|k| k == key) {
+            return true;
+        }
+        match &self.value_pattern {
+            Some(pattern) => glob_match(pattern, key),
+            None => self.value_columns.is_empty() && !self.id_columns.iter().any(|k| k == key),
+        }
+    }
+
+    /// Splits a melted column key into one value per variable field. Purely numeric
+    /// parts without a leading zero, such as years, become integers.
+    fn variable_values(&self, key: &str) -> Vec<Value> {
+        let parts: Vec<&str> = if self.variables.len() > 1 && !self.separator.is_empty() {
+            key.splitn(self.variables.len(), self.separator.as_str()).collect()
+        } else {
+            vec![key]
+        };
+        self.variables
+            .iter()
+            .enumerate()
+            .map(|(index, _)| match parts.get(index) {
+                Some(part) => variable_value(part),
+                None => Value::Null,
+            })
+            .collect()
+    }
+
+    /// The keys of the long rows melted from rows with `keys`: the id columns, then the
+    /// variable fields and the value field.
+    pub fn output_keys(&self, keys: &[String]) -> Vec<String> {
+        let mut output: Vec<String> = if self.id_columns.is_empty() {
+            keys.iter().filter(|k| !self.is_value_column(k)).cloned().collect()
+        } else {
+            self.id_columns.iter().filter(|k| keys.contains(k)).cloned().collect()
+        };
+        output.extend(self.variables.iter().cloned());
+        output.push(self.value.clone());
+        output
+    }
+
+    /// Melts one converted row into long rows.
+    pub fn apply(&self, row: &IndexMap<String, Value>) -> Vec<IndexMap<String, Value>> {
+        let ids: Vec<(&String, &Value)> = if self.id_columns.is_empty() {
+            row.iter().filter(|(k, _)| !self.is_value_column(k)).collect()
+        } else {
+            self.id_columns.iter().filter_map(|k| row.get_key_value(k)).collect()
+        };
+        let melted: Vec<(&String, &Value)> = if self.value_columns.is_empty() {
+            row.iter().filter(|(k, _)| self.is_value_column(k)).collect()
+        } else {
+            let mut melted: Vec<(&String, &Value)> = self.value_columns.iter().filter_map(|k| row.get_key_value(k)).collect();
+            if self.value_pattern.is_some() {
+                melted.extend(row.iter().filter(|(k, _)| !self.value_columns.contains(k) && self.is_value_column(k)));
+            }
+            melted
+        };
+        let mut rows = Vec::with_capacity(melted.len());
+        for (key, value) in melted {
+            if self.skip_empty && (value.is_null() || value.as_str() == Some("")) {
+                continue;
+            }
+            let mut out: IndexMap<String, Value> = ids.iter().map(|(k, v)| ((*k).clone(), (*v).clone())).collect();
+            for (name, part) in self.variables.iter().zip(self.variable_values(key)) {
+                out.insert(name.clone(), part);
+            }
+            out.insert(self.value.clone(), value.clone());
+            rows.push(out);
+        }
+        rows
+    }
+}
+
+/// How values landing in the same pivoted cell are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PivotAggregate {
+    #[default]
+    First,
+    Last,
+    /// Numeric sum; non-numeric values are ignored and a cell with none is null.
+    Sum,
+    /// Every value, in row order, as an array.
+    Collect,
+}
+
+impl PivotAggregate {
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.trim().to_lowercase().as_str() {
+            "first" => Some(PivotAggregate::First),
+            "last" => Some(PivotAggregate::Last),
+            "sum" => Some(PivotAggregate::Sum),
+            "collect" | "array" | "list" => Some(PivotAggregate::Collect),
+            _ => None,
+        }
+    }
+
+    fn combine(&self, values: Vec<Value>) -> Value {
+        match self {
+            PivotAggregate::First => values.into_iter().next().unwrap_or(Value::Null),
+            PivotAggregate::Last => values.into_iter().last().unwrap_or(Value::Null),
+            PivotAggregate::Collect => Value::Array(values),
+            PivotAggregate::Sum => sum_values(&values),
+        }
+    }
+}
+
+impl std::fmt::Display for PivotAggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            PivotAggregate::First => "first",
+            PivotAggregate::Last => "last",
+            PivotAggregate::Sum => "sum",
+            PivotAggregate::Collect => "collect",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+/// Sums the numeric values, as an integer while every value is one.
+pub(crate) fn sum_values(values: &[Value]) -> Value {
+    let numbers: Vec<&serde_json::Number> = values.iter().filter_map(|v| v.as_number()).collect();
+    if numbers.is_empty() {
+        return Value::Null;
+    }
+    let integer_sum = numbers.iter().try_fold(0i64, |acc, n| n.as_i64().and_then(|i| acc.checked_add(i)));
+    match integer_sum {
+        Some(sum) => integer_value(sum),
+        None => float_value(numbers.iter().filter_map(|n| n.as_f64()).sum()),
+    }
+}
+
+/// Where pivoted values land in each output object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PivotLayout {
+    /// One top-level field per distinct column-name value: `{"region": "west", "2020": 1.5}`.
+    Fields,
+    /// Items in the named array, one per column-name value, keyed by the column-name
+    /// field as in `KeySegment::Array`: `{"region": "west", "years": [{"year": 2020, "amount": 1.5}]}`.
+    Array(String),
+}
+
+/// Options for pivoting long rows into wide objects, see the module doc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pivot {
+    /// Output keys identifying one output object. Other columns are dropped.
+    pub index: Vec<String>,
+    /// Key whose values name the new fields (or array items).
+    pub columns: String,
+    /// Key whose values fill them.
+    pub values: String,
+    pub aggregate: PivotAggregate,
+    pub layout: PivotLayout,
+}
+
+impl Pivot {
+    pub fn new(index: &[&str], columns: &str, values: &str) -> Self {
+        Pivot {
+            index: index.iter().map(|s| s.to_string()).collect(),
+            columns: columns.to_string(),
+            values: values.to_string(),
+            aggregate: PivotAggregate::First,
+            layout: PivotLayout::Fields,
+        }
+    }
+
+    pub fn aggregate(mut self, aggregate: PivotAggregate) -> Self {
+        self.aggregate = aggregate;
+        self
+    }
+
+    /// Nests pivoted values as items of the named array instead of top-level fields.
+    pub fn nest_in(mut self, container: &str) -> Self {
+        self.layout = PivotLayout::Array(container.to_string());
+        self
+    }
+
+    /// Parses pivot options from JSON: `"index"` (array of keys), `"columns"`, `"values"`,
+    /// `"aggregate"` (`first`, `last`, `sum` or `collect`) and, for the nested layout,
+    /// `"container"`. Returns `None` without `columns` and `values`.
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let index: Vec<String> = match json.get("index") {
+            Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+            Some(Value::String(s)) => vec![s.clone()],
+            _ => vec![],
+        };
+        Some(Pivot {
+            index,
+            columns: json.get("columns")?.as_str()?.to_string(),
+            values: json.get("values")?.as_str()?.to_string(),
+            aggregate: json.get("aggregate").and_then(|v| v.as_str()).and_then(PivotAggregate::from_key).unwrap_or_default(),
+            layout: match json.get("container").and_then(|v| v.as_str()) {
+                Some(container) => PivotLayout::Array(container.to_string()),
+                None => PivotLayout::Fields,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut output = json!({
+            "index": self.index,
+            "columns": self.columns,
+            "values": self.values,
+            "aggregate": self.aggregate.to_string(),
+        });
+        if let PivotLayout::Array(container) = &self.layout {
+            output["container"] = json!(container);
+        }
+        output
+    }
+
+    /// Pivots a full row set. Output objects follow the order each index value was first
+    /// seen, and their fields the order each column-name value was first seen within it.
+    /// Rows without the column-name field are skipped.
+    pub fn apply(&self, rows: &[IndexMap<String, Value>]) -> Vec<IndexMap<String, Value>> {
+        let mut groups: IndexMap<String, (Vec<Value>, PivotCells)> = IndexMap::new();
+        for row in rows {
+            let Some(column_value) = row.get(&self.columns).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            let index_values: Vec<Value> = self.index.iter().map(|k| row.get(k).cloned().unwrap_or(Value::Null)).collect();
+            let group_key = Value::Array(index_values.clone()).to_string();
+            let (_, cells) = groups.entry(group_key).or_insert_with(|| (index_values, IndexMap::new()));
+            let cell = cells.entry(column_value.to_string()).or_insert_with(|| (column_value.clone(), vec![]));
+            cell.1.push(row.get(&self.values).cloned().unwrap_or(Value::Null));
+        }
+        groups
+            .into_values()
+            .map(|(index_values, cells)| {
+                let mut map: Map<String, Value> = self.index.iter().cloned().zip(index_values).collect();
+                for (column_value, values) in cells.into_values() {
+                    let segment = self.cell_segment(&column_value);
+                    insert_key_segment(&mut map, &segment, self.aggregate.combine(values));
+                }
+                map.into_iter().collect()
+            })
+            .collect()
+    }
+
+    fn cell_segment(&self, column_value: &Value) -> KeySegment {
+        match &self.layout {
+            PivotLayout::Fields => KeySegment::Simple(Arc::from(value_to_key(column_value).as_str())),
+            PivotLayout::Array(container) => {
+                let id = match column_value.as_i64() {
+                    Some(n) => Identifier::from_int(n),
+                    None => Identifier::from_string(&value_to_key(column_value)),
+                };
+                KeySegment::Array(
+                    Arc::from(container.as_str()),
+                    id,
+                    Arc::from(self.columns.as_str()),
+                    Arc::new(KeySegment::Simple(Arc::from(self.values.as_str()))),
+                )
+            }
+        }
+    }
+}
+
+/// A pivoted object's cells under construction: each column-name value (keyed by its
+/// JSON text) with the values collected for it.
+type PivotCells = IndexMap<String, (Value, Vec<Value>)>;
+
+fn value_to_key(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub const DEFAULT_CHILD_CONTAINER: &str = "items";
+
+/// Options for merging rows into parent documents with a child array, see the module doc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupRows {
+    /// Output keys identifying a parent document.
+    pub by: Vec<String>,
+    /// Columns moved from each row into its child object, in this order. Every other
+    /// column stays on the parent, taken from the group's first row. Empty means every
+    /// column not in `by`.
+    pub children: Vec<String>,
+    pub container: String,
+    /// Whether input is already sorted by `by`: only consecutive rows are merged, which
+    /// keeps one group in memory at a time and lets grouped rows stream to a `SaveRowFn`.
+    pub sorted: bool,
+}
+
+impl GroupRows {
+    pub fn new(by: &[&str], children: &[&str]) -> Self {
+        GroupRows {
+            by: by.iter().map(|s| s.to_string()).collect(),
+            children: children.iter().map(|s| s.to_string()).collect(),
+            container: DEFAULT_CHILD_CONTAINER.to_string(),
+            sorted: false,
+        }
+    }
+
+    pub fn container(mut self, container: &str) -> Self {
+        self.container = container.to_string();
+        self
+    }
+
+    /// Declares the input sorted by the group key -- see the `sorted` field doc.
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+
+    /// Parses grouping options from JSON: `"by"` and `"children"` (arrays of output
+    /// keys, `"children"` may be left out to nest every other column), `"container"`
+    /// (defaults to `items`) and `"sorted"`. Returns `None` without any `by` keys.
+    pub fn from_json(json: &Value) -> Option<Self> {
+        let strings = |field: &str| -> Vec<String> {
+            match json.get(field) {
+                Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+                Some(Value::String(s)) => vec![s.clone()],
+                _ => vec![],
+            }
+        };
+        let by = strings("by");
+        if by.is_empty() {
+            return None;
+        }
+        Some(GroupRows {
+            by,
+            children: strings("children"),
+            container: json.get("container").and_then(|v| v.as_str()).unwrap_or(DEFAULT_CHILD_CONTAINER).to_string(),
+            sorted: json.get("sorted").and_then(|v| v.as_bool()).unwrap_or(false),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "by": self.by,
+            "children": self.children,
+            "container": self.container,
+            "sorted": self.sorted,
+        })
+    }
+
+    fn group_key(&self, row: &IndexMap<String, Value>) -> String {
+        let values: Vec<&Value> = self.by.iter().map(|k| row.get(k).unwrap_or(&Value::Null)).collect();
+        json!(values).to_string()
+    }
+
+    fn is_child_column(&self, key: &str) -> bool {
+        match self.children.is_empty() {
+            true => !self.by.iter().any(|k| k == key),
+            false => self.children.iter().any(|k| k == key),
+        }
+    }
+
+    /// The row's child object, or `None` when every child column is null or missing
+    /// (a parent row with no line item of its own).
+    fn child(&self, row: &IndexMap<String, Value>) -> Option<Value> {
+        let child: Map<String, Value> = match self.children.is_empty() {
+            true => row.iter().filter(|(k, _)| self.is_child_column(k)).map(|(k, v)| (k.clone(), v.clone())).collect(),
+            false => self.children.iter().filter_map(|k| row.get(k).map(|v| (k.clone(), v.clone()))).collect(),
+        };
+        if child.values().all(|v| v.is_null()) {
+            None
+        } else {
+            Some(Value::Object(child))
+        }
+    }
+
+    /// Starts a parent document from a group's first row.
+    fn parent(&self, row: IndexMap<String, Value>) -> IndexMap<String, Value> {
+        let child = self.child(&row);
+        let mut parent: IndexMap<String, Value> = row.into_iter().filter(|(k, _)| !self.is_child_column(k)).collect();
+        parent.insert(self.container.clone(), Value::Array(child.into_iter().collect()));
+        parent
+    }
+
+    fn add_child(&self, parent: &mut IndexMap<String, Value>, row: &IndexMap<String, Value>) {
+        if let (Some(child), Some(Value::Array(items))) = (self.child(row), parent.get_mut(&self.container)) {
+            items.push(child);
+        }
+    }
+
+    /// Groups a full row set, parents in the order their key was first seen.
+    pub fn apply(&self, rows: Vec<IndexMap<String, Value>>) -> Vec<IndexMap<String, Value>> {
+        if self.sorted {
+            let mut stream = GroupStream::new(self.clone());
+            let mut grouped: Vec<IndexMap<String, Value>> = rows.into_iter().filter_map(|row| stream.push(row)).collect();
+            grouped.extend(stream.finish());
+            return grouped;
+        }
+        let mut parents: IndexMap<String, IndexMap<String, Value>> = IndexMap::new();
+        for row in rows {
+            let key = self.group_key(&row);
+            match parents.get_mut(&key) {
+                Some(parent) => self.add_child(parent, &row),
+                None => {
+                    parents.insert(key, self.parent(row));
+                }
+            }
+        }
+        parents.into_values().collect()
+    }
+}
+
+/// Incremental grouping for sorted input: each pushed row either joins the current
+/// parent or completes it.
+#[derive(Debug, Clone)]
+pub struct GroupStream {
+    group: GroupRows,
+    current: Option<(String, IndexMap<String, Value>)>,
+}
+
+impl GroupStream {
+    pub fn new(group: GroupRows) -> Self {
+        GroupStream { group, current: None }
+    }
+
+    /// Adds a row, returning the previous parent once a row with a new key arrives.
+    pub fn push(&mut self, row: IndexMap<String, Value>) -> Option<IndexMap<String, Value>> {
+        let key = self.group.group_key(&row);
+        match &mut self.current {
+            Some((current_key, parent)) if *current_key == key => {
+                self.group.add_child(parent, &row);
+                None
+            }
+            _ => {
+                let parent = self.group.parent(row);
+                self.current.replace((key, parent)).map(|(_, done)| done)
+            }
+        }
+    }
+
+    /// The last parent, once input is exhausted.
+    pub fn finish(&mut self) -> Option<IndexMap<String, Value>> {
+        self.current.take().map(|(_, parent)| parent)
+    }
+}
+
+/// Applies the whole-row-set stages configured on `opts` -- `dedup`, `group`, `pivot`,
+/// `aggregate`, then `sort` -- to rows that have already been read, converted and
+/// filtered.
+pub fn transform_rows(opts: &OptionSet, rows: Vec<IndexMap<String, Value>>) -> Vec<IndexMap<String, Value>> {
+    let rows = match &opts.dedup {
+        Some(dedup) => dedup.apply(rows),
+        None => rows,
+    };
+    let rows = match &opts.group {
+        Some(group) => group.apply(rows),
+        None => rows,
+    };
+    let rows = match &opts.pivot {
+        Some(pivot) => pivot.apply(&rows),
+        None => rows,
+    };
+    let rows = match &opts.aggregate {
+        Some(aggregation) => aggregation.apply(&rows),
+        None => rows,
+    };
+    match &opts.sort {
+        Some(sort) => sort.apply(rows),
+        None => rows,
+    }
+}
+
+fn variable_value(part: &str) -> Value {
+    let is_plain_integer = !part.is_empty()
+        && part.chars().all(|c| c.is_ascii_digit())
+        && (part.len() == 1 || !part.starts_with('0'));
+    match part.parse::<i64>() {
+        Ok(n) if is_plain_integer => json!(n),
+        _ => json!(part),
+    }
+}
+
+/// Matches `text` against a glob pattern with `*` (any run) and `?` (any one character).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(value: Value) -> IndexMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_unpivot_melts_year_columns() {
+        let unpivot = Unpivot::matching(&["region"], "20*").variable_names(&["year"]);
+        let rows = unpivot.apply(&row(json!({"region": "west", "note": "x", "2020": 1.5, "2021": null})));
+        assert_eq!(json!(rows), json!([
+            {"region": "west", "year": 2020, "value": 1.5},
+            {"region": "west", "year": 2021, "value": null},
+        ]));
+        let rows = unpivot.skip_empty().apply(&row(json!({"region": "west", "2020": 1.5, "2021": null})));
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn test_unpivot_splits_combined_keys_and_defaults_to_non_id_columns() {
+        let unpivot = Unpivot::from_json(&json!({"id_columns": ["region"], "variable": ["year", "sex"], "value": "population"}));
+        let rows = unpivot.apply(&row(json!({"region": "north", "2015_female": 10, "2015_male": 9, "total_all_ages": 19})));
+        assert_eq!(json!(rows), json!([
+            {"region": "north", "year": 2015, "sex": "female", "population": 10},
+            {"region": "north", "year": 2015, "sex": "male", "population": 9},
+            {"region": "north", "year": "total", "sex": "all_ages", "population": 19},
+        ]));
+    }
+
+    #[test]
+    fn test_pivot_to_fields_and_nested_arrays() {
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"region": "west", "year": 2020, "amount": 1, "note": "x"},
+            {"region": "east", "year": 2020, "amount": 4},
+            {"region": "west", "year": 2021, "amount": 2},
+            {"region": "west", "year": 2020, "amount": 3.5},
+        ])).unwrap();
+        let pivot = Pivot::new(&["region"], "year", "amount");
+        assert_eq!(json!(pivot.apply(&rows)), json!([
+            {"region": "west", "2020": 1, "2021": 2},
+            {"region": "east", "2020": 4},
+        ]));
+        let summed = pivot.clone().aggregate(PivotAggregate::Sum).apply(&rows);
+        assert_eq!(summed[0]["2020"], json!(4.5));
+        let collected = pivot.clone().aggregate(PivotAggregate::Collect).apply(&rows);
+        assert_eq!(collected[0]["2020"], json!([1, 3.5]));
+
+        let nested = Pivot::from_json(&json!({"index": ["region"], "columns": "year", "values": "amount", "aggregate": "last", "container": "years"})).unwrap();
+        assert_eq!(json!(nested.apply(&rows)[0]), json!({
+            "region": "west",
+            "years": [{"year": 2020, "amount": 3.5}, {"year": 2021, "amount": 2}],
+        }));
+    }
+
+    #[test]
+    fn test_group_rows_merges_line_items_hashed_and_sorted() {
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"order_id": 1, "customer": "Ada", "sku": "A", "qty": 2},
+            {"order_id": 2, "customer": "Bo", "sku": "B", "qty": 1},
+            {"order_id": 1, "customer": "Ada", "sku": "C", "qty": 5},
+            {"order_id": 3, "customer": "Cy", "sku": null, "qty": null},
+        ])).unwrap();
+        let group = GroupRows::new(&["order_id"], &["sku", "qty"]);
+        assert_eq!(json!(group.apply(rows.clone())), json!([
+            {"order_id": 1, "customer": "Ada", "items": [{"sku": "A", "qty": 2}, {"sku": "C", "qty": 5}]},
+            {"order_id": 2, "customer": "Bo", "items": [{"sku": "B", "qty": 1}]},
+            {"order_id": 3, "customer": "Cy", "items": []},
+        ]));
+        // sorted mode only merges neighbours, so the unsorted order 1 splits in two
+        let sorted = GroupRows::from_json(&json!({"by": ["order_id"], "children": ["sku", "qty"], "container": "lines", "sorted": true})).unwrap();
+        let grouped = sorted.apply(rows.clone());
+        assert_eq!(grouped.len(), 4);
+        assert_eq!(grouped[2]["lines"], json!([{"sku": "C", "qty": 5}]));
+        // Without children, every column outside the key goes into the child
+        let grouped = GroupRows::new(&["order_id"], &[]).apply(rows);
+        assert_eq!(json!(grouped[1]), json!({"order_id": 2, "items": [{"customer": "Bo", "sku": "B", "qty": 1}]}));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("20*", "2021"));
+        assert!(glob_match("q?_*", "q1_sales"));
+        assert!(glob_match("*_female", "2015_female"));
+        assert!(!glob_match("20*", "region"));
+        assert!(!glob_match("q?", "q10"));
+    }
+}
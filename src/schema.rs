@@ -0,0 +1,350 @@
+//! JSON Schema (Draft 2020-12) generation for the converted output, for downstream
+//! services that validate the JSON this crate produces.
+//!
+//! The schema's shape follows each resolved `Column`'s `KeySegment` (nested objects,
+//! arrays of discriminated items, plain arrays) and its leaf types and formats follow the
+//! column's `Format`. What the columns can't say comes from the rows themselves: the
+//! types of `Format::Auto` columns, and whether a field is ever null. `required` lists
+//! the fields whose column is marked `required`, plus each array item's `key_field`
+//! discriminator, which is always set.
+use indexmap::IndexMap;
+use serde_json::{json, Map, Value};
+
+use crate::error::GenericError;
+use crate::key_segment::{Identifier, KeySegment};
+use crate::{process_spreadsheet_direct, Column, Format, OptionSet, ReadMode, ResultSet};
+
+pub const JSON_SCHEMA_DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// `Format::Time`/`Format::Hm` output (`HH:MM` or `HH:MM:SS`, no offset), which the
+/// RFC 3339 `time` format doesn't accept.
+const TIME_PATTERN: &str = "^[0-9]{2,}:[0-9]{2}(:[0-9]{2})?$";
+
+/// One node of the schema tree, built up from declared column structure and observed
+/// values before being rendered as JSON.
+#[derive(Debug, Clone, Default)]
+struct SchemaNode {
+    /// The JSON type an explicit `Format` (or the `KeySegment` structure) pins this node to.
+    declared: Option<&'static str>,
+    format: Option<&'static str>,
+    pattern: Option<&'static str>,
+    /// JSON types seen in the data, excluding null.
+    observed: Vec<&'static str>,
+    nullable: bool,
+    /// Allowed values, for `Array`/`InnerObject` discriminator fields.
+    enum_values: Vec<Value>,
+    properties: IndexMap<String, SchemaNode>,
+    required: Vec<String>,
+    items: Option<Box<SchemaNode>>,
+}
+
+impl SchemaNode {
+    fn declare_format(&mut self, format: &Format) {
+        let (json_type, json_format) = match format {
+            Format::Auto => return,
+            Format::Integer => ("integer", None),
+            Format::Decimal(_) | Format::Float => ("number", None),
+            Format::Boolean | Format::Truthy | Format::TruthyCustom(_) => ("boolean", None),
+            Format::Date => ("string", Some("date")),
+            Format::DateTime => ("string", Some("date-time")),
+            Format::Time | Format::Hm => {
+                self.declared = Some("string");
+                self.pattern = Some(TIME_PATTERN);
+                return;
+            }
+            // `DateTimeSimple` has no UTC offset, which RFC 3339's date-time requires
+            Format::Text | Format::DateTimeSimple | Format::DateTimeCustom(_) => ("string", None),
+            Format::Array(element, _) => {
+                self.declared = Some("array");
+                self.items.get_or_insert_with(Default::default).declare_format(element);
+                return;
+            }
+        };
+        self.declared = Some(json_type);
+        self.format = json_format;
+    }
+
+    fn declare_identifier(&mut self, id: &Identifier) {
+        self.declared = Some(match id {
+            Identifier::String(_) => "string",
+            Identifier::Integer(_) => "integer",
+        });
+        let value = id.to_value();
+        if !self.enum_values.contains(&value) {
+            self.enum_values.push(value);
+        }
+    }
+
+    fn require(&mut self, key: &str) {
+        if !self.required.iter().any(|k| k == key) {
+            self.required.push(key.to_string());
+        }
+    }
+
+    fn observe(&mut self, value: &Value) {
+        let json_type = match value {
+            Value::Null => {
+                self.nullable = true;
+                return;
+            }
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(items) => {
+                let item_node = self.items.get_or_insert_with(Default::default);
+                for item in items {
+                    item_node.observe(item);
+                }
+                "array"
+            }
+            Value::Object(map) => {
+                for (key, item) in map {
+                    self.properties.entry(key.clone()).or_default().observe(item);
+                }
+                "object"
+            }
+        };
+        if !self.observed.contains(&json_type) {
+            self.observed.push(json_type);
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut schema = Map::new();
+        // Values a column's format couldn't convert are kept as they were read, so the
+        // declared type is widened by whatever else was seen
+        let mut types: Vec<&str> = self.declared.into_iter().collect();
+        for observed in &self.observed {
+            if !types.contains(observed) {
+                types.push(observed);
+            }
+        }
+        if types.contains(&"number") && types.contains(&"integer") {
+            types.retain(|t| *t != "number");
+            for t in types.iter_mut().filter(|t| **t == "integer") {
+                *t = "number";
+            }
+        }
+        if !types.is_empty() && self.nullable {
+            types.push("null");
+        }
+        match types.len() {
+            0 => {}
+            1 => {
+                schema.insert("type".to_string(), json!(types[0]));
+            }
+            _ => {
+                schema.insert("type".to_string(), json!(types));
+            }
+        }
+        if let Some(format) = self.format {
+            schema.insert("format".to_string(), json!(format));
+        }
+        if let Some(pattern) = self.pattern {
+            schema.insert("pattern".to_string(), json!(pattern));
+        }
+        if !self.enum_values.is_empty() {
+            schema.insert("enum".to_string(), json!(self.enum_values));
+        }
+        if types.contains(&"object") {
+            let properties: Map<String, Value> = self.properties.iter().map(|(k, node)| (k.clone(), node.to_json())).collect();
+            schema.insert("properties".to_string(), Value::Object(properties));
+            if !self.required.is_empty() {
+                schema.insert("required".to_string(), json!(self.required));
+            }
+        }
+        if types.contains(&"array") {
+            if let Some(items) = &self.items {
+                schema.insert("items".to_string(), items.to_json());
+            }
+        }
+        Value::Object(schema)
+    }
+}
+
+/// Declares the path `segment` describes within `parent`, the schema-side mirror of
+/// `insert_key_segment`. A required column also marks each container on its path as
+/// required within its parent.
+fn declare_segment(parent: &mut SchemaNode, segment: &KeySegment, column: Option<&Column>) {
+    let required = column.is_some_and(|c| c.required);
+    match segment {
+        KeySegment::Excluded => {}
+        KeySegment::Simple(key) => {
+            let node = parent.properties.entry(key.to_string()).or_default();
+            if let Some(col) = column {
+                node.declare_format(&col.format);
+            }
+            if required {
+                parent.require(key);
+            }
+        }
+        KeySegment::Object(key, next) => {
+            let node = parent.properties.entry(key.to_string()).or_default();
+            node.declared = Some("object");
+            declare_segment(node, next, column);
+            if required {
+                parent.require(key);
+            }
+        }
+        KeySegment::Array(container, id, key_field, next) => {
+            let node = parent.properties.entry(container.to_string()).or_default();
+            node.declared = Some("array");
+            let item = node.items.get_or_insert_with(Default::default);
+            item.declared = Some("object");
+            item.properties.entry(key_field.to_string()).or_default().declare_identifier(id);
+            item.require(key_field);
+            declare_segment(item, next, column);
+            if required {
+                parent.require(container);
+            }
+        }
+        KeySegment::InnerObject(id, field, next) => {
+            parent.properties.entry(field.to_string()).or_default().declare_identifier(id);
+            parent.require(field);
+            declare_segment(parent, next, column);
+        }
+        KeySegment::PlainArray(container) => {
+            let node = parent.properties.entry(container.to_string()).or_default();
+            node.declared = Some("array");
+            if let Some(col) = column {
+                node.items.get_or_insert_with(Default::default).declare_format(&col.format);
+            }
+            if required {
+                parent.require(container);
+            }
+        }
+    }
+}
+
+/// The schema for a single output row, from the header `keys`, the resolved `columns`
+/// (aligned by index with `keys`) and sample `rows`.
+pub fn row_schema(rows: &[IndexMap<String, Value>], keys: &[String], columns: &[Column]) -> Value {
+    let mut root = SchemaNode { declared: Some("object"), ..Default::default() };
+    for (index, key) in keys.iter().enumerate() {
+        let column = columns.get(index);
+        match column.and_then(|c| c.key.as_ref()) {
+            Some(segment) => declare_segment(&mut root, segment, column),
+            None if !key.is_empty() => declare_segment(&mut root, &KeySegment::Simple(key.as_str().into()), column),
+            None => {}
+        }
+    }
+    for row in rows {
+        for (key, value) in row {
+            root.properties.entry(key.clone()).or_default().observe(value);
+        }
+    }
+    root.to_json()
+}
+
+/// The full Draft 2020-12 schema for an array of output rows.
+pub fn json_schema(rows: &[IndexMap<String, Value>], keys: &[String], columns: &[Column], title: Option<&str>) -> Value {
+    let mut schema = json!({
+        "$schema": JSON_SCHEMA_DRAFT,
+        "type": "array",
+        "items": row_schema(rows, keys, columns),
+    });
+    if let Some(title) = title {
+        schema["title"] = json!(title);
+    }
+    schema
+}
+
+/// Reads up to `sample_size` rows with `opts` and returns the schema for the output that
+/// `opts` produces, without having to keep a full `ResultSet` around.
+pub fn json_schema_from_sample(opts: &OptionSet, sample_size: usize) -> Result<Value, GenericError> {
+    let mut sample_opts = opts.clone();
+    sample_opts.read_mode = ReadMode::Sync;
+    sample_opts.max = Some(sample_size as u32);
+    let result = process_spreadsheet_direct(&sample_opts)?;
+    Ok(result.json_schema())
+}
+
+impl ResultSet {
+    /// The Draft 2020-12 JSON Schema for this result set's (first sheet's) rows -- see
+    /// `schema::json_schema`.
+    pub fn json_schema(&self) -> Value {
+        json_schema(&self.data.first_sheet(), &self.keys, &self.columns, Some(&self.filename))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn write_csv_fixture(filename: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(filename);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_schema_types_formats_and_nullability() {
+        let path = write_csv_fixture("schema_types.csv", "sku,qty,added,opened,note\nA1,3,2023-06-15,10:30,x\nA2,,2023-06-16,11:00,\n");
+        let opts = OptionSet::new(&path).override_columns(&[
+            json!({"source_key": "sku", "format": "text", "required": true}),
+            json!({"source_key": "qty", "format": "integer"}),
+            json!({"source_key": "added", "format": "date"}),
+            json!({"source_key": "opened", "format": "time"}),
+        ]);
+        let schema = process_spreadsheet_direct(&opts).unwrap().json_schema();
+        assert_eq!(schema["$schema"], JSON_SCHEMA_DRAFT);
+        let props = &schema["items"]["properties"];
+        assert_eq!(props["sku"], json!({"type": "string"}));
+        assert_eq!(props["added"], json!({"type": "string", "format": "date"}));
+        // Text under a numeric format passes through unconverted, so the empty cell is ""
+        assert_eq!(props["qty"], json!({"type": ["integer", "string"]}));
+        assert_eq!(props["opened"], json!({"type": "string", "pattern": TIME_PATTERN}));
+        assert_eq!(props["note"], json!({"type": "string"}));
+        assert_eq!(schema["items"]["required"], json!(["sku"]));
+    }
+
+    #[test]
+    fn test_schema_reflects_key_segment_nesting() {
+        let year = |y: i64, leaf: &str, fmt: Format| {
+            let mut col = Column::new_format(fmt, None);
+            col.key = Some(KeySegment::Array(
+                Arc::from("sales"),
+                Identifier::from_int(y),
+                Arc::from("year"),
+                Arc::new(KeySegment::Object(Arc::from("totals"), Arc::new(KeySegment::Simple(Arc::from(leaf))))),
+            ));
+            col
+        };
+        let mut tags = Column::new_format(Format::Text, None);
+        tags.key = Some(KeySegment::PlainArray(Arc::from("tags")));
+        let mut name = Column::new(Some("name")).set_required(true);
+        name.format = Format::Text;
+        let keys: Vec<String> = ["name", "sales", "sales", "tags"].iter().map(|s| s.to_string()).collect();
+        let columns = vec![name, year(2015, "amount", Format::Float), year(2025, "amount", Format::Float), tags];
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"name": "a", "sales": [{"year": 2015, "totals": {"amount": 1.5}}, {"year": 2025, "totals": {"amount": null}}], "tags": ["x"]}
+        ])).unwrap();
+        let row = row_schema(&rows, &keys, &columns);
+        assert_eq!(row["required"], json!(["name"]));
+        let sales = &row["properties"]["sales"];
+        assert_eq!(sales["type"], "array");
+        let item = &sales["items"];
+        assert_eq!(item["properties"]["year"], json!({"type": "integer", "enum": [2015, 2025]}));
+        assert_eq!(item["required"], json!(["year"]));
+        assert_eq!(item["properties"]["totals"]["properties"]["amount"], json!({"type": ["number", "null"]}));
+        assert_eq!(row["properties"]["tags"], json!({"type": "array", "items": {"type": "string"}}));
+    }
+
+    #[test]
+    fn test_schema_widens_declared_types_by_unconverted_values() {
+        let keys = vec!["qty".to_string()];
+        let columns = vec![Column::new_format(Format::Integer, None)];
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([{"qty": 3}, {"qty": "n/a"}, {"qty": 1.5}])).unwrap();
+        assert_eq!(row_schema(&rows, &keys, &columns)["properties"]["qty"], json!({"type": ["number", "string"]}));
+    }
+
+    #[test]
+    fn test_schema_from_sample_infers_auto_column_types() {
+        let schema = json_schema_from_sample(&OptionSet::new("data/sample-data-1.csv"), 20).unwrap();
+        let props = &schema["items"]["properties"];
+        assert_eq!(props["age"], json!({"type": "integer"}));
+        assert_eq!(props["first_name"], json!({"type": "string"}));
+    }
+}
@@ -0,0 +1,235 @@
+//! Splits a worksheet holding several tables into rectangular blocks. Blocks are separated
+//! by blank rows, then by blank columns within each band of rows. Each block with at
+//! least two rows and two populated columns is a table whose header is found with the
+//! usual detection heuristics (see `detect::detect_header_and_data_rows`). A narrow row
+//! above a table, inside the block or in its own block just before it, is its title.
+//! Footer rows at the bottom of a block -- totals (see `detect::detect_data_end`) and
+//! notes with text in the first column only -- are left out of the table.
+use serde_json::{json, Value};
+
+use crate::cell_range::CellRange;
+use crate::detect::{detect_data_end, detect_header_and_data_rows};
+
+/// Options for splitting a sheet into tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSegmentation {
+    /// Blank rows needed to separate two tables
+    pub min_blank_rows: usize,
+    /// Blank columns needed to separate two side-by-side tables
+    pub min_blank_cols: usize,
+    /// Names each table after its title row where it has one
+    pub title_names: bool,
+}
+
+impl Default for TableSegmentation {
+    fn default() -> Self {
+        TableSegmentation { min_blank_rows: 1, min_blank_cols: 1, title_names: false }
+    }
+}
+
+impl TableSegmentation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_blank_rows(mut self, rows: usize) -> Self {
+        self.min_blank_rows = rows.max(1);
+        self
+    }
+
+    pub fn min_blank_cols(mut self, cols: usize) -> Self {
+        self.min_blank_cols = cols.max(1);
+        self
+    }
+
+    pub fn title_names(mut self) -> Self {
+        self.title_names = true;
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "min_blank_rows": self.min_blank_rows,
+            "min_blank_cols": self.min_blank_cols,
+            "title_names": self.title_names,
+        })
+    }
+}
+
+/// One table found in a sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedTable {
+    /// Text of the title row above the table, if any
+    pub title: Option<String>,
+    /// From the header row, or first data row without one, to the end of the block
+    pub range: CellRange,
+    /// Relative to the first row of `range`: `Some(0)` or `None` without a header
+    pub header_row_index: Option<usize>,
+    /// Relative to the first row of `range`
+    pub data_row_index: usize,
+}
+
+impl DetectedTable {
+    /// The title when `title_names` is set and the table has one, otherwise
+    /// `{sheet} table {n}` with `n` counting from 1.
+    pub fn name(&self, sheet_name: &str, index: usize, segmentation: &TableSegmentation) -> String {
+        match (&self.title, segmentation.title_names) {
+            (Some(title), true) => title.clone(),
+            _ => format!("{} table {}", sheet_name, index + 1),
+        }
+    }
+}
+
+/// Finds every table in a sheet's cell text, where `origin` is the 0-based (row, column)
+/// of `rows[0][0]` in the sheet.
+pub fn segment_tables(rows: &[Vec<String>], origin: (u32, u32), segmentation: &TableSegmentation) -> Vec<DetectedTable> {
+    let is_blank = |cell: &String| cell.trim().is_empty();
+    let blank_rows: Vec<bool> = rows.iter().map(|row| row.iter().all(is_blank)).collect();
+    let mut tables = vec![];
+    let mut pending_title: Option<String> = None;
+    for (top, bottom) in split_runs(&blank_rows, segmentation.min_blank_rows) {
+        let band = &rows[top..=bottom];
+        let width = band.iter().map(|row| row.len()).max().unwrap_or(0);
+        let blank_cols: Vec<bool> = (0..width)
+            .map(|col| band.iter().all(|row| row.get(col).is_none_or(is_blank)))
+            .collect();
+        for (left, right) in split_runs(&blank_cols, segmentation.min_blank_cols) {
+            let block: Vec<Vec<String>> = band
+                .iter()
+                .map(|row| (left..=right).map(|col| row.get(col).cloned().unwrap_or_default()).collect())
+                .collect();
+            // Rows blank within these columns only belong to a neighbouring block
+            let Some(first) = block.iter().position(|row| !row.iter().all(is_blank)) else {
+                continue;
+            };
+            let last = block.iter().rposition(|row| !row.iter().all(is_blank)).unwrap_or(first);
+            let block = &block[first..=last];
+            let populated = |row: &Vec<String>| row.iter().filter(|c| !is_blank(c)).count();
+            if block.len() < 2 || block.iter().map(populated).max().unwrap_or(0) < 2 {
+                // Notes or a title on its own, which may name the next table
+                pending_title = first_text(block);
+                continue;
+            }
+            let detected = detect_header_and_data_rows(block);
+            let start = detected.header_index.unwrap_or(detected.data_index);
+            let end = table_end(block, detected.data_index, segmentation.min_blank_rows);
+            let inner_title = block[..start].iter().find(|row| populated(row) > 0).and_then(|row| first_text(std::slice::from_ref(row)));
+            let row_offset = origin.0 + (top + first + start) as u32;
+            tables.push(DetectedTable {
+                title: inner_title.or(pending_title.take()),
+                range: CellRange {
+                    start: (row_offset, origin.1 + left as u32),
+                    end_row: Some(origin.0 + (top + first + end - 1) as u32),
+                    end_col: origin.1 + right as u32,
+                },
+                header_row_index: detected.header_index.map(|_| 0),
+                data_row_index: detected.data_index - start,
+            });
+        }
+    }
+    tables
+}
+
+/// Exclusive end of a block's data: the detected data end, less trailing notes with only
+/// their first cell filled, keeping at least one data row.
+fn table_end(block: &[Vec<String>], data_index: usize, blank_run: usize) -> usize {
+    let first_data_end = (data_index + 1).min(block.len());
+    let mut end = detect_data_end(block[data_index.min(block.len())..].iter().cloned(), data_index, blank_run).max(first_data_end);
+    while end > first_data_end && is_note_row(&block[end - 1]) {
+        end -= 1;
+    }
+    end
+}
+
+/// Text in the first cell only, as in "Note: provisional" or "Source: ONS".
+fn is_note_row(row: &[String]) -> bool {
+    match row.split_first() {
+        Some((first, rest)) => {
+            let first = first.trim();
+            !first.is_empty() && first.parse::<f64>().is_err() && rest.iter().all(|c| c.trim().is_empty())
+        }
+        None => false,
+    }
+}
+
+/// Inclusive (first, last) spans of `false` entries, split wherever `true` entries run
+/// for at least `min_gap` in a row. Shorter runs stay inside a span.
+fn split_runs(gaps: &[bool], min_gap: usize) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start: Option<usize> = None;
+    let mut last_filled = 0;
+    let mut gap = 0;
+    for (index, is_gap) in gaps.iter().enumerate() {
+        if *is_gap {
+            gap += 1;
+            if gap == min_gap {
+                if let Some(first) = start.take() {
+                    spans.push((first, last_filled));
+                }
+            }
+        } else {
+            gap = 0;
+            last_filled = index;
+            start.get_or_insert(index);
+        }
+    }
+    if let Some(first) = start {
+        spans.push((first, last_filled));
+    }
+    spans
+}
+
+fn first_text(rows: &[Vec<String>]) -> Option<String> {
+    rows.iter().flatten().map(|c| c.trim()).find(|c| !c.is_empty()).map(|c| c.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_segments_stacked_and_side_by_side_tables() {
+        let rows = grid(&[
+            &["Sales by region", "", "", "", ""],
+            &["region", "revenue", "", "code", "label"],
+            &["north", "1200", "", "N", "North"],
+            &["south", "950", "", "S", "South"],
+            &["", "", "", "", ""],
+            &["Headcount", "", "", "", ""],
+            &["", "", "", "", ""],
+            &["team", "staff", "", "", ""],
+            &["IT", "12", "", "", ""],
+            &["Note: provisional", "", "", "", ""],
+        ]);
+        let tables = segment_tables(&rows, (2, 1), &TableSegmentation::new().title_names());
+        assert_eq!(tables.len(), 3);
+        assert_eq!(tables[0].title.as_deref(), Some("Sales by region"));
+        assert_eq!(tables[0].range.to_string(), "B4:C6");
+        assert_eq!((tables[0].header_row_index, tables[0].data_row_index), (Some(0), 1));
+        assert_eq!(tables[1].range.to_string(), "E4:F6");
+        assert_eq!(tables[1].title, None);
+        assert_eq!(tables[2].title.as_deref(), Some("Headcount"));
+        // The trailing note isn't part of the table
+        assert_eq!(tables[2].range.to_string(), "B10:C11");
+        assert_eq!(tables[2].name("Report", 2, &TableSegmentation::new()), "Report table 3");
+        assert_eq!(split_runs(&[true, false, true, false, true, true, false], 2), vec![(1, 3), (6, 6)]);
+    }
+
+    #[test]
+    fn test_segments_stop_at_total_and_note_rows() {
+        let rows = grid(&[
+            &["item", "qty", "price"],
+            &["pen", "3", "1.5"],
+            &["ink", "2", "4"],
+            &["Total", "5", "5.5"],
+            &["Source: stock ledger", "", ""],
+        ]);
+        let tables = segment_tables(&rows, (0, 0), &TableSegmentation::new());
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].range.to_string(), "A1:C3");
+    }
+}
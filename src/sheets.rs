@@ -0,0 +1,210 @@
+//! Worksheet selection: exact names and indices as before, plus glob and regex patterns,
+//! exclusion lists, and skipping hidden, very hidden or chart sheets using calamine's
+//! sheet metadata. In strict mode a selection that matches nothing is an error rather
+//! than a silent fallback to the first sheet.
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use calamine::{Reader, Sheet, SheetType, SheetVisible, Sheets};
+use heck::ToSnakeCase;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::error::GenericError;
+use crate::reshape::glob_match;
+use crate::OptionSet;
+
+#[derive(Debug, Clone)]
+pub enum SheetPattern {
+    /// A sheet name, compared in snake_case like `OptionSet::sheet_name`.
+    Name(String),
+    /// A glob with `*` and `?`, ignoring case.
+    Glob(String),
+    Regex(Arc<Regex>),
+}
+
+impl SheetPattern {
+    pub fn name(name: &str) -> Self {
+        SheetPattern::Name(name.to_string())
+    }
+
+    pub fn glob(pattern: &str) -> Self {
+        SheetPattern::Glob(pattern.to_string())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, GenericError> {
+        Regex::new(pattern).map(|re| SheetPattern::Regex(Arc::new(re))).map_err(|_| GenericError("invalid_sheet_pattern"))
+    }
+
+    /// Reads `/.../` as a regex, text containing `*` or `?` as a glob, and anything else
+    /// as a sheet name.
+    pub fn parse(text: &str) -> Result<Self, GenericError> {
+        match text.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            Some(pattern) => SheetPattern::regex(pattern),
+            None if text.contains(['*', '?']) => Ok(SheetPattern::glob(text)),
+            None => Ok(SheetPattern::name(text)),
+        }
+    }
+
+    pub fn matches(&self, sheet_name: &str) -> bool {
+        match self {
+            SheetPattern::Name(name) => name.to_snake_case() == sheet_name.to_snake_case(),
+            SheetPattern::Glob(pattern) => glob_match(&pattern.to_lowercase(), &sheet_name.to_lowercase()),
+            SheetPattern::Regex(re) => re.is_match(sheet_name),
+        }
+    }
+}
+
+impl std::fmt::Display for SheetPattern {
+    /// The `parse` form of the pattern.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SheetPattern::Name(name) => write!(f, "{}", name),
+            SheetPattern::Glob(pattern) => write!(f, "{}", pattern),
+            SheetPattern::Regex(re) => write!(f, "/{}/", re.as_str()),
+        }
+    }
+}
+
+/// Pattern selection and eligibility rules applied on top of `OptionSet.selected` and
+/// `OptionSet.indices`.
+#[derive(Debug, Clone, Default)]
+pub struct SheetFilter {
+    /// Sheets to select, in addition to any selected by name.
+    pub include: Vec<SheetPattern>,
+    /// Sheets never selected, however they are requested.
+    pub exclude: Vec<SheetPattern>,
+    /// Skips hidden and very hidden sheets.
+    pub skip_hidden: bool,
+    /// Skips very hidden sheets only.
+    pub skip_very_hidden: bool,
+    pub skip_charts: bool,
+    /// Errors with `no_matching_sheets` instead of falling back to the first sheet.
+    pub strict: bool,
+}
+
+impl SheetFilter {
+    /// Whether a sheet may be selected at all. Sheets without metadata (e.g. from
+    /// formats that don't record it) count as visible worksheets.
+    pub fn is_eligible(&self, sheet_name: &str, meta: Option<&Sheet>) -> bool {
+        if self.exclude.iter().any(|p| p.matches(sheet_name)) {
+            return false;
+        }
+        let Some(meta) = meta else {
+            return true;
+        };
+        let skipped = match meta.visible {
+            SheetVisible::Visible => false,
+            SheetVisible::Hidden => self.skip_hidden,
+            SheetVisible::VeryHidden => self.skip_hidden || self.skip_very_hidden,
+        };
+        let chart = self.skip_charts && meta.typ == SheetType::ChartSheet;
+        !(skipped || chart)
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.include.is_empty()
+            && self.exclude.is_empty()
+            && !self.skip_hidden
+            && !self.skip_very_hidden
+            && !self.skip_charts
+            && !self.strict
+    }
+
+    pub fn to_json(&self) -> Value {
+        let patterns = |items: &[SheetPattern]| items.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+        json!({
+            "include": patterns(&self.include),
+            "exclude": patterns(&self.exclude),
+            "skip_hidden": self.skip_hidden,
+            "skip_very_hidden": self.skip_very_hidden,
+            "skip_charts": self.skip_charts,
+            "strict": self.strict,
+        })
+    }
+}
+
+/// The outcome of sheet selection for one workbook.
+#[derive(Debug, Clone)]
+pub struct MatchedSheets {
+    /// Selected sheet names, in the order requested.
+    pub selected: Vec<String>,
+    /// Workbook positions of `selected`.
+    pub indices: Vec<usize>,
+    /// Every sheet in the workbook.
+    pub sheet_names: Vec<String>,
+    /// Sheets left after exclusions and visibility and type rules, in workbook order.
+    pub eligible: Vec<String>,
+}
+
+/// Selects sheets by name, then by `SheetFilter::include` pattern, falling back to the
+/// requested indices when neither matches. Without any match the first eligible sheet is
+/// used, unless the filter is strict and a sheet other than the default first one was
+/// asked for.
+pub fn select_worksheets(workbook: &mut Sheets<BufReader<File>>, opts: &OptionSet) -> Result<MatchedSheets, GenericError> {
+    let sheet_names = workbook.worksheets().into_iter().map(|ws| ws.0).collect::<Vec<String>>();
+    let metadata = workbook.sheets_metadata().to_vec();
+    let filter = &opts.sheet_filter;
+    let eligible: Vec<bool> = sheet_names
+        .iter()
+        .map(|name| filter.is_eligible(name, metadata.iter().find(|m| &m.name == name)))
+        .collect();
+    let mut picked: Vec<usize> = vec![];
+    for sheet_key in opts.selected.iter().flatten() {
+        if let Some(index) = sheet_names.iter().position(|s| s.to_snake_case() == sheet_key.to_snake_case()) {
+            pick(&mut picked, &eligible, index);
+        }
+    }
+    for pattern in &filter.include {
+        for (index, name) in sheet_names.iter().enumerate() {
+            if pattern.matches(name) {
+                pick(&mut picked, &eligible, index);
+            }
+        }
+    }
+    let by_name = opts.selected.is_some() || !filter.include.is_empty();
+    if picked.is_empty() && !(by_name && filter.strict) {
+        for index in opts.indices.iter().map(|i| *i as usize) {
+            if index < sheet_names.len() {
+                pick(&mut picked, &eligible, index);
+            }
+        }
+    }
+    if picked.is_empty() {
+        let default_request = !by_name && opts.indices == [0];
+        if filter.strict && !default_request {
+            return Err(GenericError("no_matching_sheets"));
+        }
+        if let Some(index) = eligible.iter().position(|e| *e) {
+            picked.push(index);
+        }
+    }
+    Ok(MatchedSheets {
+        selected: picked.iter().map(|i| sheet_names[*i].clone()).collect(),
+        eligible: sheet_names.iter().zip(eligible.iter()).filter(|(_, e)| **e).map(|(n, _)| n.clone()).collect(),
+        indices: picked,
+        sheet_names,
+    })
+}
+
+fn pick(picked: &mut Vec<usize>, eligible: &[bool], index: usize) {
+    if eligible[index] && !picked.contains(&index) {
+        picked.push(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sheet_patterns_parse_and_match() {
+        assert!(SheetPattern::parse("Sales*").unwrap().matches("sales 2026"));
+        assert!(SheetPattern::parse("/^Q[1-4]$/").unwrap().matches("Q3"));
+        assert!(!SheetPattern::parse("/^Q[1-4]$/").unwrap().matches("Q5"));
+        assert!(SheetPattern::parse("Results 1").unwrap().matches("results-1"));
+        assert!(SheetPattern::parse("/(unclosed/").is_err());
+        assert_eq!(SheetPattern::parse("/^Q\\d$/").unwrap().to_string(), "/^Q\\d$/");
+    }
+}
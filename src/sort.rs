@@ -0,0 +1,745 @@
+//! Sorting and de-duplication of output rows.
+//!
+//! `SortSpec` orders rows by one or more keys, each ascending or descending, comparing
+//! numbers numerically, dates chronologically and strings by a `Collation`. `Dedup` drops
+//! repeated rows, either exact copies or rows sharing key columns, keeping the first or
+//! last one.
+//!
+//! Each row's sort keys are read once into typed values with a total order, so a date is
+//! parsed once per row rather than once per comparison.
+//!
+//! Captured rows are sorted in memory. When rows stream to a save callback,
+//! `ExternalSorter` keeps at most one chunk of rows in memory. Each full chunk is sorted
+//! and spilled to a temporary JSON lines file, and the chunks are merged once reading is
+//! done, at most `MAX_MERGE_FILES` at a time. This lets a CSV far larger than
+//! `max_row_count` be sorted in async mode.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use indexmap::IndexMap;
+use serde_json::{json, Value};
+
+use crate::error::GenericError;
+
+/// Rows held in memory per chunk by `ExternalSorter` unless `SortSpec::chunk_size` is
+/// set.
+pub const DEFAULT_SORT_CHUNK_SIZE: usize = 50_000;
+
+/// Chunk files merged at once. With more chunks than this, runs of consecutive chunks are
+/// first merged into larger ones.
+pub const MAX_MERGE_FILES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// Byte order, so uppercase sorts before lowercase.
+    #[default]
+    Binary,
+    CaseInsensitive,
+    /// Case-insensitive, with runs of digits compared by value ("item2" < "item10").
+    Natural,
+}
+
+impl Collation {
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+            "binary" | "ordinal" => Some(Collation::Binary),
+            "case_insensitive" | "nocase" | "ci" => Some(Collation::CaseInsensitive),
+            "natural" | "numeric" => Some(Collation::Natural),
+            _ => None,
+        }
+    }
+
+    /// Text as this collation compares it, before ties fall back to byte order. Binary
+    /// collation compares bytes only, so needs none.
+    fn fold(&self, text: &str) -> String {
+        match self {
+            Collation::Binary => String::new(),
+            Collation::CaseInsensitive | Collation::Natural => text.to_lowercase(),
+        }
+    }
+
+    fn compare_folded(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => Ordering::Equal,
+            Collation::CaseInsensitive => a.cmp(b),
+            Collation::Natural => natural_compare(a, b),
+        }
+    }
+}
+
+impl std::fmt::Display for Collation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            Collation::Binary => "binary",
+            Collation::CaseInsensitive => "case_insensitive",
+            Collation::Natural => "natural",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+fn natural_compare(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_digits = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (take_digits(&mut a), take_digits(&mut b));
+                let (xt, yt) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = xt.len().cmp(&yt.len()).then_with(|| xt.cmp(yt));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Reads ISO 8601 / RFC 3339 dates and date-times, the forms date columns are written in.
+fn parse_datetime(text: &str) -> Option<NaiveDateTime> {
+    let bytes = text.as_bytes();
+    if bytes.len() < 10 || bytes[4] != b'-' || !bytes[0].is_ascii_digit() {
+        return None;
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.naive_utc());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(text, fmt).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)))
+}
+
+/// A non-null value read once into a totally ordered form. Values of different types
+/// order by type -- booleans, numbers, dates, other text, then arrays and objects -- so
+/// strings that read as ISO dates sort chronologically ahead of other text.
+#[derive(Debug, Clone)]
+enum SortValue {
+    Bool(bool),
+    /// The value as a float, plus the exact integer where there is one to break ties
+    /// between large integers.
+    Number(f64, Option<i64>),
+    Date(NaiveDateTime),
+    /// Collation, the text as folded for it, and the text itself
+    Text(Collation, String, String),
+    Nested(String),
+}
+
+impl SortValue {
+    fn new(value: &Value, collation: Collation) -> Option<Self> {
+        Some(match value {
+            Value::Null => return None,
+            Value::Bool(b) => SortValue::Bool(*b),
+            Value::Number(n) => SortValue::Number(n.as_f64().unwrap_or_default(), n.as_i64()),
+            Value::String(text) => match parse_datetime(text) {
+                Some(dt) => SortValue::Date(dt),
+                None => SortValue::Text(collation, collation.fold(text), text.clone()),
+            },
+            Value::Array(_) | Value::Object(_) => SortValue::Nested(value.to_string()),
+        })
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            SortValue::Bool(_) => 0,
+            SortValue::Number(..) => 1,
+            SortValue::Date(_) => 2,
+            SortValue::Text(..) => 3,
+            SortValue::Nested(_) => 4,
+        }
+    }
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortValue::Bool(x), SortValue::Bool(y)) => x.cmp(y),
+            (SortValue::Number(x, xi), SortValue::Number(y, yi)) => x.total_cmp(y).then_with(|| xi.cmp(yi)),
+            (SortValue::Date(x), SortValue::Date(y)) => x.cmp(y),
+            (SortValue::Text(collation, xf, x), SortValue::Text(_, yf, y)) => collation.compare_folded(xf, yf).then_with(|| x.cmp(y)),
+            (SortValue::Nested(x), SortValue::Nested(y)) => x.cmp(y),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortValue {}
+
+/// One row's value for one sort key. Nulls and missing fields sort last in either
+/// direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyValue {
+    value: Option<SortValue>,
+    descending: bool,
+}
+
+impl Ord for KeyValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.value, &other.value) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(x), Some(y)) if self.descending => y.cmp(x),
+            (Some(x), Some(y)) => x.cmp(y),
+        }
+    }
+}
+
+impl PartialOrd for KeyValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Typed comparison of two values: numbers by value, strings that read as dates
+/// chronologically ahead of other strings, other strings by `collation`, mixed types by
+/// type, and nulls last.
+pub fn compare_values(a: &Value, b: &Value, collation: Collation) -> Ordering {
+    let key = |value: &Value| KeyValue { value: SortValue::new(value, collation), descending: false };
+    key(a).cmp(&key(b))
+}
+
+/// One key of a sort specification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+    pub collation: Collation,
+}
+
+impl SortKey {
+    pub fn asc(field: &str) -> Self {
+        SortKey { field: field.to_string(), descending: false, collation: Collation::Binary }
+    }
+
+    pub fn desc(field: &str) -> Self {
+        SortKey { field: field.to_string(), descending: true, collation: Collation::Binary }
+    }
+
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// Parses `"field"`, `"-field"` (descending) or
+    /// `{"field": "name", "order": "desc", "collation": "natural"}`. Fails for an empty
+    /// field, an unknown order or collation, or any other JSON value.
+    pub fn from_json(json: &Value) -> Result<Self, GenericError> {
+        let key = match json {
+            Value::String(text) => match text.strip_prefix('-') {
+                Some(field) => SortKey::desc(field),
+                None => SortKey::asc(text.strip_prefix('+').unwrap_or(text)),
+            },
+            Value::Object(map) => {
+                let field = map.get("field").and_then(|v| v.as_str()).ok_or(GenericError("invalid_sort_key"))?;
+                let descending = match map.get("order").and_then(|v| v.as_str()).map(|order| order.to_lowercase()) {
+                    Some(order) if order.starts_with("desc") => true,
+                    Some(order) if order.starts_with("asc") => false,
+                    Some(_) => return Err(GenericError("invalid_sort_order")),
+                    None => map.get("desc").and_then(|v| v.as_bool()).unwrap_or(false),
+                };
+                let collation = match map.get("collation").and_then(|v| v.as_str()) {
+                    Some(key) => Collation::from_key(key).ok_or(GenericError("invalid_collation"))?,
+                    None => Collation::default(),
+                };
+                SortKey { field: field.to_string(), descending, collation }
+            }
+            _ => return Err(GenericError("invalid_sort_key")),
+        };
+        if key.field.trim().is_empty() {
+            return Err(GenericError("invalid_sort_key"));
+        }
+        Ok(key)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "field": self.field,
+            "order": if self.descending { "desc" } else { "asc" },
+            "collation": self.collation.to_string(),
+        })
+    }
+
+    fn key_value(&self, row: &IndexMap<String, Value>) -> KeyValue {
+        KeyValue { value: row.get(&self.field).and_then(|v| SortValue::new(v, self.collation)), descending: self.descending }
+    }
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let order = if self.descending { "desc" } else { "asc" };
+        write!(f, "{} {}", self.field, order)
+    }
+}
+
+/// Multi-key sort of output rows. The sort is stable, so rows with equal keys keep their
+/// read order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SortSpec {
+    pub keys: Vec<SortKey>,
+    /// Rows held in memory per chunk when sorting externally. Defaults to
+    /// `DEFAULT_SORT_CHUNK_SIZE`.
+    pub chunk_size: Option<usize>,
+}
+
+impl SortSpec {
+    pub fn new(keys: Vec<SortKey>) -> Self {
+        SortSpec { keys, chunk_size: None }
+    }
+
+    /// Sort keys from field names, with a leading `-` for descending order, e.g.
+    /// `SortSpec::by(&["country", "-score"])`. Fails for an empty field name.
+    pub fn by(fields: &[&str]) -> Result<Self, GenericError> {
+        let keys = fields.iter().map(|f| SortKey::from_json(&json!(f))).collect::<Result<_, _>>()?;
+        Ok(SortSpec::new(keys))
+    }
+
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = Some(size.max(1));
+        self
+    }
+
+    /// Parses an array of keys (see `SortKey::from_json`) or
+    /// `{"keys": [...], "chunk_size": 50000}`, failing on the first invalid key.
+    pub fn from_json(json: &Value) -> Result<Self, GenericError> {
+        let (keys, chunk_size) = match json {
+            Value::Object(map) => (map.get("keys").cloned().unwrap_or(Value::Null), map.get("chunk_size").and_then(|v| v.as_u64())),
+            _ => (json.clone(), None),
+        };
+        let keys = match keys {
+            Value::Array(items) => items.iter().map(SortKey::from_json).collect::<Result<_, _>>()?,
+            Value::Null => vec![],
+            single => vec![SortKey::from_json(&single)?],
+        };
+        Ok(SortSpec { keys, chunk_size: chunk_size.map(|size| (size as usize).max(1)) })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "keys": self.keys.iter().map(|k| k.to_json()).collect::<Vec<Value>>(),
+            "chunk_size": self.chunk_size,
+        })
+    }
+
+    /// The row's typed value for each key, compared in order.
+    fn row_key(&self, row: &IndexMap<String, Value>) -> Vec<KeyValue> {
+        self.keys.iter().map(|key| key.key_value(row)).collect()
+    }
+
+    pub fn compare(&self, a: &IndexMap<String, Value>, b: &IndexMap<String, Value>) -> Ordering {
+        self.row_key(a).cmp(&self.row_key(b))
+    }
+
+    /// Sorts rows in memory.
+    pub fn apply(&self, rows: Vec<IndexMap<String, Value>>) -> Vec<IndexMap<String, Value>> {
+        let mut keyed: Vec<(Vec<KeyValue>, IndexMap<String, Value>)> = rows.into_iter().map(|row| (self.row_key(&row), row)).collect();
+        keyed.sort_by(|a, b| a.0.cmp(&b.0));
+        keyed.into_iter().map(|(_, row)| row).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupKeep {
+    #[default]
+    First,
+    Last,
+}
+
+/// Drops repeated rows. Rows are duplicates when all their fields match, or only the `by`
+/// columns when set.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Dedup {
+    pub by: Vec<String>,
+    pub keep: DedupKeep,
+}
+
+impl Dedup {
+    /// Drops exact duplicate rows.
+    pub fn rows() -> Self {
+        Dedup::default()
+    }
+
+    /// Drops rows whose `by` columns repeat an earlier row's.
+    pub fn by(columns: &[&str]) -> Self {
+        Dedup { by: columns.iter().map(|s| s.to_string()).collect(), keep: DedupKeep::First }
+    }
+
+    /// Keeps the last of each set of duplicates, in the place of the first.
+    pub fn keep_last(mut self) -> Self {
+        self.keep = DedupKeep::Last;
+        self
+    }
+
+    /// Parses `{"by": ["id"], "keep": "last"}`. Both fields are optional.
+    pub fn from_json(json: &Value) -> Self {
+        let by = match json.get("by") {
+            Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect(),
+            Some(Value::String(s)) => vec![s.clone()],
+            _ => vec![],
+        };
+        let keep = match json.get("keep").and_then(|v| v.as_str()) {
+            Some(keep) if keep.eq_ignore_ascii_case("last") => DedupKeep::Last,
+            _ => DedupKeep::First,
+        };
+        Dedup { by, keep }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({"by": self.by, "keep": if self.keep == DedupKeep::Last { "last" } else { "first" }})
+    }
+
+    fn row_key(&self, row: &IndexMap<String, Value>) -> String {
+        if self.by.is_empty() {
+            json!(row).to_string()
+        } else {
+            Value::Array(self.by.iter().map(|k| row.get(k).cloned().unwrap_or(Value::Null)).collect()).to_string()
+        }
+    }
+
+    pub fn apply(&self, rows: Vec<IndexMap<String, Value>>) -> Vec<IndexMap<String, Value>> {
+        let mut stream = DedupStream::new(self.clone());
+        let mut output: Vec<IndexMap<String, Value>> = rows.into_iter().filter_map(|row| stream.push(row)).collect();
+        output.extend(stream.finish());
+        output
+    }
+}
+
+/// Streaming de-duplication. Keeping the first row passes rows straight through, holding
+/// only the keys seen so far. Keeping the last holds one row per key until `finish`.
+#[derive(Debug, Clone)]
+pub struct DedupStream {
+    dedup: Dedup,
+    seen: HashSet<String>,
+    latest: IndexMap<String, IndexMap<String, Value>>,
+}
+
+impl DedupStream {
+    pub fn new(dedup: Dedup) -> Self {
+        DedupStream { dedup, seen: HashSet::new(), latest: IndexMap::new() }
+    }
+
+    /// Returns the row if it should be output now.
+    pub fn push(&mut self, row: IndexMap<String, Value>) -> Option<IndexMap<String, Value>> {
+        let key = self.dedup.row_key(&row);
+        match self.dedup.keep {
+            DedupKeep::First => self.seen.insert(key).then_some(row),
+            DedupKeep::Last => {
+                self.latest.insert(key, row);
+                None
+            }
+        }
+    }
+
+    /// The rows held back for `DedupKeep::Last`.
+    pub fn finish(&mut self) -> Vec<IndexMap<String, Value>> {
+        std::mem::take(&mut self.latest).into_values().collect()
+    }
+}
+
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Sorts a stream of rows with bounded memory: see the module notes.
+#[derive(Debug)]
+pub struct ExternalSorter {
+    spec: SortSpec,
+    chunk_size: usize,
+    buffer: Vec<IndexMap<String, Value>>,
+    chunks: Vec<PathBuf>,
+}
+
+impl ExternalSorter {
+    pub fn new(spec: SortSpec) -> Self {
+        let chunk_size = spec.chunk_size.unwrap_or(DEFAULT_SORT_CHUNK_SIZE).max(1);
+        ExternalSorter { spec, chunk_size, buffer: Vec::new(), chunks: Vec::new() }
+    }
+
+    pub fn push(&mut self, row: IndexMap<String, Value>) -> Result<(), GenericError> {
+        self.buffer.push(row);
+        if self.buffer.len() >= self.chunk_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of chunks spilled to temporary files so far.
+    pub fn spilled_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn spill(&mut self) -> Result<(), GenericError> {
+        let rows = self.spec.apply(std::mem::take(&mut self.buffer));
+        self.write_chunk(rows.into_iter().map(Ok))
+    }
+
+    /// Writes already sorted rows to a new chunk file at the end of `chunks`.
+    fn write_chunk<I>(&mut self, rows: I) -> Result<(), GenericError>
+    where
+        I: Iterator<Item = Result<IndexMap<String, Value>, GenericError>>,
+    {
+        let path = std::env::temp_dir().join(format!(
+            "spreadsheet-to-json-sort-{}-{}.jsonl",
+            std::process::id(),
+            SPILL_COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        // Registered before writing so a partial file is still removed on drop
+        self.chunks.push(path.clone());
+        let file = File::create(&path).map_err(|_| GenericError("sort_spill_error"))?;
+        let mut writer = BufWriter::new(file);
+        for row in rows {
+            serde_json::to_writer(&mut writer, &row?).map_err(|_| GenericError("sort_spill_error"))?;
+            writer.write_all(b"\n").map_err(|_| GenericError("sort_spill_error"))?;
+        }
+        writer.flush().map_err(|_| GenericError("sort_spill_error"))
+    }
+
+    /// Merges runs of `MAX_MERGE_FILES` consecutive chunks into one chunk each, keeping
+    /// the chunks in input order.
+    fn merge_pass(&mut self) -> Result<(), GenericError> {
+        let pass = self.chunks.clone();
+        for group in pass.chunks(MAX_MERGE_FILES) {
+            let mut merge = ChunkMerge::open(&self.spec, group)?;
+            self.write_chunk(std::iter::from_fn(|| merge.next_row()))?;
+            self.chunks.retain(|path| !group.contains(path));
+            for path in group {
+                let _ = fs::remove_file(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// The pushed rows in sorted order. Rows still in memory are sorted directly if
+    /// nothing was spilled; otherwise they are spilled too and all chunks are merged.
+    pub fn finish(mut self) -> Result<SortedRows, GenericError> {
+        if self.chunks.is_empty() {
+            let rows = self.spec.apply(std::mem::take(&mut self.buffer));
+            return Ok(SortedRows { memory: rows.into_iter(), merge: None, paths: vec![] });
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        while self.chunks.len() > MAX_MERGE_FILES {
+            self.merge_pass()?;
+        }
+        let merge = ChunkMerge::open(&self.spec, &self.chunks)?;
+        let paths = std::mem::take(&mut self.chunks);
+        Ok(SortedRows { memory: vec![].into_iter(), merge: Some(merge), paths })
+    }
+}
+
+impl Drop for ExternalSorter {
+    fn drop(&mut self) {
+        for path in &self.chunks {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The next row of one chunk, ordered so the `BinaryHeap` pops the smallest key first
+/// and, on ties, the earliest chunk, which keeps the merge stable.
+struct MergeHead {
+    key: Vec<KeyValue>,
+    source: usize,
+    row: IndexMap<String, Value>,
+}
+
+impl Ord for MergeHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.cmp(&self.key).then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl PartialOrd for MergeHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for MergeHead {}
+
+/// A k-way merge of sorted chunk files.
+struct ChunkMerge {
+    spec: SortSpec,
+    sources: Vec<Lines<BufReader<File>>>,
+    heads: BinaryHeap<MergeHead>,
+}
+
+impl ChunkMerge {
+    fn open(spec: &SortSpec, paths: &[PathBuf]) -> Result<Self, GenericError> {
+        let mut sources = Vec::with_capacity(paths.len());
+        for path in paths {
+            let file = File::open(path).map_err(|_| GenericError("sort_merge_error"))?;
+            sources.push(BufReader::new(file).lines());
+        }
+        let mut merge = ChunkMerge { spec: spec.clone(), sources, heads: BinaryHeap::with_capacity(paths.len()) };
+        for source in 0..merge.sources.len() {
+            merge.read_next(source)?;
+        }
+        Ok(merge)
+    }
+
+    /// Queues the next row of chunk `source`, if it has one.
+    fn read_next(&mut self, source: usize) -> Result<(), GenericError> {
+        if let Some(line) = self.sources[source].next() {
+            let line = line.map_err(|_| GenericError("sort_merge_error"))?;
+            let row: IndexMap<String, Value> = serde_json::from_str(&line).map_err(|_| GenericError("sort_merge_error"))?;
+            self.heads.push(MergeHead { key: self.spec.row_key(&row), source, row });
+        }
+        Ok(())
+    }
+
+    fn next_row(&mut self) -> Option<Result<IndexMap<String, Value>, GenericError>> {
+        let head = self.heads.pop()?;
+        Some(self.read_next(head.source).map(|_| head.row))
+    }
+}
+
+/// Iterator over the output of `ExternalSorter::finish`. Temporary chunk files are
+/// removed when it is dropped.
+pub struct SortedRows {
+    memory: std::vec::IntoIter<IndexMap<String, Value>>,
+    merge: Option<ChunkMerge>,
+    paths: Vec<PathBuf>,
+}
+
+impl Iterator for SortedRows {
+    type Item = Result<IndexMap<String, Value>, GenericError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.merge {
+            Some(merge) => merge.next_row(),
+            None => self.memory.next().map(Ok),
+        }
+    }
+}
+
+impl Drop for SortedRows {
+    fn drop(&mut self) {
+        // Close the chunk files before removing them
+        self.merge = None;
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(value: Value) -> Vec<IndexMap<String, Value>> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_sort_keys_compare_numbers_dates_and_collated_strings() {
+        let data = rows(json!([
+            {"name": "item10", "score": 2, "date": "2026-01-02"},
+            {"name": "Item2", "score": 10, "date": "2026-01-02T09:30:00"},
+            {"name": "item1", "score": null, "date": "2025-12-31"},
+            {"name": "item3", "score": 2.5, "date": "2026-01-02"},
+        ]));
+        let names = |rows: Vec<IndexMap<String, Value>>| rows.iter().map(|r| r["name"].clone()).collect::<Vec<Value>>();
+        assert_eq!(names(SortSpec::by(&["-score"]).unwrap().apply(data.clone())), json!(["Item2", "item3", "item10", "item1"]).as_array().unwrap().clone());
+        assert_eq!(names(SortSpec::by(&["date", "name"]).unwrap().apply(data.clone())), json!(["item1", "item10", "item3", "Item2"]).as_array().unwrap().clone());
+        let natural = SortSpec::from_json(&json!([{"field": "name", "collation": "natural"}])).unwrap();
+        assert_eq!(names(natural.apply(data)), json!(["item1", "Item2", "item3", "item10"]).as_array().unwrap().clone());
+    }
+
+    #[test]
+    fn test_mixed_dates_and_text_sort_in_a_total_order() {
+        // Compared pairwise, "2026-01-02" > "2025-12-31" as dates but "2025-12-31" > "11"
+        // and "11" < "2026-01-02" as text, which is no order at all
+        let data = rows(json!([
+            {"v": "b"}, {"v": "2026-01-02"}, {"v": "11"}, {"v": "2025-12-31"}, {"v": 3}, {"v": "a"}, {"v": null},
+        ]));
+        let sorted: Vec<Value> = SortSpec::by(&["v"]).unwrap().apply(data).iter().map(|r| r["v"].clone()).collect();
+        assert_eq!(sorted, vec![json!(3), json!("2025-12-31"), json!("2026-01-02"), json!("11"), json!("a"), json!("b"), Value::Null]);
+    }
+
+    #[test]
+    fn test_invalid_sort_keys_are_errors() {
+        assert!(SortSpec::by(&["name", "-"]).is_err());
+        assert!(SortSpec::from_json(&json!(["name", 3])).is_err());
+        assert!(SortSpec::from_json(&json!([{"field": "name", "collation": "klingon"}])).is_err());
+        assert!(SortSpec::from_json(&json!([{"order": "desc"}])).is_err());
+        assert!(SortSpec::from_json(&json!({"keys": ["name", "-age"], "chunk_size": 10})).is_ok());
+    }
+
+    #[test]
+    fn test_dedup_by_key_keeps_first_or_last() {
+        let data = rows(json!([
+            {"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 1, "v": "c"}, {"id": 2, "v": "b"},
+        ]));
+        assert_eq!(json!(Dedup::rows().apply(data.clone())), json!([{"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 1, "v": "c"}]));
+        assert_eq!(json!(Dedup::by(&["id"]).apply(data.clone())), json!([{"id": 1, "v": "a"}, {"id": 2, "v": "b"}]));
+        let last = Dedup::from_json(&json!({"by": "id", "keep": "last"}));
+        assert_eq!(json!(last.apply(data)), json!([{"id": 1, "v": "c"}, {"id": 2, "v": "b"}]));
+    }
+
+    #[test]
+    fn test_external_sorter_merges_spilled_chunks_stably() {
+        let mut sorter = ExternalSorter::new(SortSpec::by(&["group"]).unwrap().chunk_size(3));
+        for i in 0..10 {
+            sorter.push(rows(json!([{"group": (i * 7) % 3, "seq": i}])).remove(0)).unwrap();
+        }
+        assert_eq!(sorter.spilled_chunks(), 3);
+        let sorted: Vec<IndexMap<String, Value>> = sorter.finish().unwrap().collect::<Result<_, _>>().unwrap();
+        let pairs: Vec<(i64, i64)> = sorted.iter().map(|r| (r["group"].as_i64().unwrap(), r["seq"].as_i64().unwrap())).collect();
+        assert_eq!(pairs, vec![(0, 0), (0, 3), (0, 6), (0, 9), (1, 1), (1, 4), (1, 7), (2, 2), (2, 5), (2, 8)]);
+    }
+
+    #[test]
+    fn test_external_sorter_merges_more_chunks_than_the_fan_in_in_passes() {
+        let mut sorter = ExternalSorter::new(SortSpec::by(&["group"]).unwrap().chunk_size(2));
+        let total = MAX_MERGE_FILES * 5;
+        for i in 0..total {
+            sorter.push(rows(json!([{"group": (i * 7) % 5, "seq": i}])).remove(0)).unwrap();
+        }
+        assert_eq!(sorter.spilled_chunks(), total / 2);
+        let sorted: Vec<IndexMap<String, Value>> = sorter.finish().unwrap().collect::<Result<_, _>>().unwrap();
+        let pairs: Vec<(i64, i64)> = sorted.iter().map(|r| (r["group"].as_i64().unwrap(), r["seq"].as_i64().unwrap())).collect();
+        let mut expected = pairs.clone();
+        expected.sort();
+        assert_eq!(pairs.len(), total);
+        assert_eq!(pairs, expected, "sorted by group, then read order");
+    }
+}
@@ -0,0 +1,468 @@
+//! SQL output: column type resolution shared by the SQL-oriented outputs, plus a
+//! generator for `CREATE TABLE` and batched `INSERT` (or PostgreSQL `COPY ... FROM
+//! STDIN`) statements in the PostgreSQL, MySQL and SQLite dialects.
+//!
+//! A result set's rows are schemaless JSON objects; a table needs one declared type per
+//! column, so each top-level output key is typed from its `Column`'s explicit `Format`
+//! where there is one, and otherwise from the values actually seen in that column.
+use std::path::Path;
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::key_segment::KeySegment;
+use crate::{Column, Format, ResultSet};
+
+/// Rows per generated `INSERT` statement.
+pub const DEFAULT_INSERT_BATCH_SIZE: usize = 500;
+
+/// The logical type of a table column, before any dialect-specific spelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlType {
+    Integer,
+    Real,
+    Text,
+    Boolean,
+    Date,
+    DateTime,
+    Time,
+    /// Nested output (`KeySegment::Object`/`Array`/`PlainArray`, or a `Format::Array`
+    /// column) -- stored as serialized JSON text.
+    Json,
+}
+
+impl SqlType {
+    /// The type an explicit `Format` override pins a column to, or `None` for
+    /// `Format::Auto`, which defers to the data.
+    pub fn from_format(format: &Format) -> Option<SqlType> {
+        match format {
+            Format::Auto => None,
+            Format::Text | Format::DateTimeCustom(_) => Some(SqlType::Text),
+            Format::Integer => Some(SqlType::Integer),
+            Format::Decimal(_) | Format::Float => Some(SqlType::Real),
+            Format::Boolean | Format::Truthy | Format::TruthyCustom(_) => Some(SqlType::Boolean),
+            Format::Date => Some(SqlType::Date),
+            Format::DateTime | Format::DateTimeSimple => Some(SqlType::DateTime),
+            Format::Time | Format::Hm => Some(SqlType::Time),
+            Format::Array(..) => Some(SqlType::Json),
+        }
+    }
+
+    /// The type a single converted cell suggests, or `None` for null. Strings are not
+    /// sniffed for dates here: without an explicit `date` format a date-looking column
+    /// is still stored as text, which SQLite/PostgreSQL/MySQL all compare correctly in
+    /// ISO form anyway.
+    pub fn from_value(value: &Value) -> Option<SqlType> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(SqlType::Boolean),
+            Value::Number(n) if n.is_i64() || n.is_u64() => Some(SqlType::Integer),
+            Value::Number(_) => Some(SqlType::Real),
+            Value::String(_) => Some(SqlType::Text),
+            Value::Array(_) | Value::Object(_) => Some(SqlType::Json),
+        }
+    }
+
+    /// Whether a converted cell can be stored in a column of this type as it is. Numbers
+    /// suit numeric columns, booleans boolean ones and strings date/time ones, while text
+    /// and JSON columns take anything. A column pinned by an explicit `Format` can still
+    /// hold the odd value that didn't convert, such as "n/a" in an integer column.
+    pub fn accepts(&self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (SqlType::Text | SqlType::Json, _)
+                | (SqlType::Integer | SqlType::Real, Value::Number(_))
+                | (SqlType::Boolean, Value::Bool(_))
+                | (SqlType::Date | SqlType::DateTime | SqlType::Time, Value::String(_))
+        )
+    }
+
+    /// Integer widens to Real; any other disagreement falls back to Text.
+    pub fn merge(self, other: SqlType) -> SqlType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (SqlType::Integer, SqlType::Real) | (SqlType::Real, SqlType::Integer) => SqlType::Real,
+            _ => SqlType::Text,
+        }
+    }
+}
+
+/// A table column: a top-level output key and its resolved type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlColumn {
+    pub name: String,
+    pub sql_type: SqlType,
+}
+
+impl SqlColumn {
+    pub fn new(name: &str, sql_type: SqlType) -> Self {
+        SqlColumn { name: name.to_string(), sql_type }
+    }
+}
+
+/// Resolves one `SqlColumn` per top-level output key, in header order, followed by any
+/// top-level keys only found in the rows. Columns sharing a nested container
+/// (`KeySegment::Object`/`Array` with the same outer key) collapse into a single JSON
+/// column; `KeySegment::Excluded` columns are left out. A column that is null in every
+/// row and has no explicit format defaults to `Text`.
+pub fn sql_columns(rows: &[IndexMap<String, Value>], keys: &[String], columns: &[Column]) -> Vec<SqlColumn> {
+    let mut declared: IndexMap<String, Option<SqlType>> = IndexMap::new();
+    for (index, key) in keys.iter().enumerate() {
+        let col = columns.get(index);
+        let (name, sql_type) = match col.and_then(|c| c.key.as_ref()) {
+            Some(KeySegment::Excluded) => continue,
+            Some(KeySegment::Simple(name)) => (name.to_string(), col.and_then(|c| SqlType::from_format(&c.format))),
+            Some(segment) => (segment.to_string(), Some(SqlType::Json)),
+            None => (key.clone(), col.and_then(|c| SqlType::from_format(&c.format))),
+        };
+        if name.is_empty() {
+            continue;
+        }
+        let entry = declared.entry(name).or_insert(None);
+        if entry.is_none() {
+            *entry = sql_type;
+        }
+    }
+    let mut observed: IndexMap<String, Option<SqlType>> = IndexMap::new();
+    for row in rows {
+        for (key, value) in row {
+            let entry = observed.entry(key.clone()).or_insert(None);
+            if let Some(value_type) = SqlType::from_value(value) {
+                *entry = Some(entry.map_or(value_type, |t| t.merge(value_type)));
+            }
+        }
+    }
+    let mut resolved: Vec<SqlColumn> = declared
+        .iter()
+        .map(|(name, sql_type)| {
+            let sql_type = sql_type.or_else(|| observed.get(name).copied().flatten()).unwrap_or(SqlType::Text);
+            SqlColumn::new(name, sql_type)
+        })
+        .collect();
+    for (name, sql_type) in &observed {
+        if !declared.contains_key(name) {
+            resolved.push(SqlColumn::new(name, sql_type.unwrap_or(SqlType::Text)));
+        }
+    }
+    resolved
+}
+
+/// Quotes an identifier with ANSI double quotes, doubling any embedded quote.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// The SQL dialect statements are generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqlDialect {
+    #[default]
+    PostgreSql,
+    MySql,
+    Sqlite,
+}
+
+impl SqlDialect {
+    /// Quotes an identifier: backticks for MySQL (which only honours ANSI double quotes
+    /// under `ANSI_QUOTES`), double quotes otherwise, doubling any embedded quote char.
+    pub fn quote_identifier(&self, name: &str) -> String {
+        match self {
+            SqlDialect::MySql => format!("`{}`", name.replace('`', "``")),
+            _ => quote_identifier(name),
+        }
+    }
+
+    /// The declared column type. For SQLite, `BOOLEAN`, `DATE`, `DATETIME` and `TIME`
+    /// aren't storage classes, but SQLite keeps the declared name (so tools reading the
+    /// schema back see the intent) while applying NUMERIC affinity -- which leaves ISO
+    /// date strings untouched as text.
+    pub fn type_name(&self, sql_type: SqlType) -> &'static str {
+        match (self, sql_type) {
+            (SqlDialect::PostgreSql | SqlDialect::MySql, SqlType::Integer) => "BIGINT",
+            (SqlDialect::Sqlite, SqlType::Integer) => "INTEGER",
+            (SqlDialect::PostgreSql, SqlType::Real) => "DOUBLE PRECISION",
+            (SqlDialect::MySql, SqlType::Real) => "DOUBLE",
+            (SqlDialect::Sqlite, SqlType::Real) => "REAL",
+            (_, SqlType::Text) => "TEXT",
+            (_, SqlType::Boolean) => "BOOLEAN",
+            (_, SqlType::Date) => "DATE",
+            (SqlDialect::PostgreSql, SqlType::DateTime) => "TIMESTAMP",
+            (_, SqlType::DateTime) => "DATETIME",
+            (_, SqlType::Time) => "TIME",
+            (SqlDialect::PostgreSql, SqlType::Json) => "JSONB",
+            (SqlDialect::MySql, SqlType::Json) => "JSON",
+            // SQLite has no JSON type; its JSON functions work on plain text
+            (SqlDialect::Sqlite, SqlType::Json) => "TEXT",
+        }
+    }
+
+    /// Renders a string literal. MySQL treats backslash as an escape character inside
+    /// string literals by default, so it is doubled there as well as the quote.
+    pub fn quote_string(&self, value: &str) -> String {
+        match self {
+            SqlDialect::MySql => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''")),
+            _ => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+
+    /// Renders a converted cell as a literal for a column of `sql_type`. A value the
+    /// column doesn't accept (see `SqlType::accepts`) becomes `NULL`, as it does in Arrow
+    /// output, rather than a literal the database would reject. JSON columns take every
+    /// value as JSON text.
+    pub fn literal(&self, value: Option<&Value>, sql_type: SqlType) -> String {
+        match value {
+            None | Some(Value::Null) => "NULL".to_string(),
+            Some(value) if !sql_type.accepts(value) => "NULL".to_string(),
+            Some(value) if sql_type == SqlType::Json => self.quote_string(&value.to_string()),
+            Some(Value::Bool(b)) => match self {
+                SqlDialect::PostgreSql => if *b { "TRUE" } else { "FALSE" }.to_string(),
+                _ => if *b { "1" } else { "0" }.to_string(),
+            },
+            Some(Value::Number(n)) => match sql_type {
+                SqlType::Text | SqlType::Json | SqlType::Date | SqlType::DateTime | SqlType::Time => self.quote_string(&n.to_string()),
+                _ => n.to_string(),
+            },
+            Some(Value::String(s)) => {
+                if *self == SqlDialect::MySql && sql_type == SqlType::DateTime {
+                    // MySQL's DATETIME rejects the `Z` suffix of this crate's default
+                    // ISO 8601 rendering
+                    self.quote_string(s.trim_end_matches('Z'))
+                } else {
+                    self.quote_string(s)
+                }
+            }
+            Some(other) => self.quote_string(&other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for SqlDialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let result = match self {
+            SqlDialect::PostgreSql => "postgresql",
+            SqlDialect::MySql => "mysql",
+            SqlDialect::Sqlite => "sqlite",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+impl FromStr for SqlDialect {
+    type Err = ();
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        match key.to_lowercase().as_str() {
+            "pg" | "postgres" | "postgresql" => Ok(SqlDialect::PostgreSql),
+            "mysql" | "mariadb" => Ok(SqlDialect::MySql),
+            "sqlite" | "sqlite3" => Ok(SqlDialect::Sqlite),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The `CREATE TABLE IF NOT EXISTS` statement for `columns`.
+pub fn create_table_statement(table: &str, columns: &[SqlColumn], dialect: SqlDialect) -> String {
+    let defs: Vec<String> = columns
+        .iter()
+        .map(|c| format!("  {} {}", dialect.quote_identifier(&c.name), dialect.type_name(c.sql_type)))
+        .collect();
+    format!("CREATE TABLE IF NOT EXISTS {} (\n{}\n);", dialect.quote_identifier(table), defs.join(",\n"))
+}
+
+/// Multi-row `INSERT` statements of at most `batch_size` rows each.
+pub fn insert_statements(table: &str, columns: &[SqlColumn], rows: &[IndexMap<String, Value>], dialect: SqlDialect, batch_size: usize) -> Vec<String> {
+    let names: Vec<String> = columns.iter().map(|c| dialect.quote_identifier(&c.name)).collect();
+    let prefix = format!("INSERT INTO {} ({}) VALUES", dialect.quote_identifier(table), names.join(", "));
+    rows.chunks(batch_size.max(1))
+        .map(|chunk| {
+            let tuples: Vec<String> = chunk
+                .iter()
+                .map(|row| {
+                    let values: Vec<String> = columns.iter().map(|c| dialect.literal(row.get(&c.name), c.sql_type)).collect();
+                    format!("  ({})", values.join(", "))
+                })
+                .collect();
+            format!("{}\n{};", prefix, tuples.join(",\n"))
+        })
+        .collect()
+}
+
+/// Escapes a value for PostgreSQL's `COPY` text format: backslash, tab, newline and
+/// carriage return are backslash-escaped, and null, like a value the column doesn't
+/// accept, is `\N`.
+fn copy_field(value: Option<&Value>, sql_type: SqlType) -> String {
+    let text = match value {
+        None | Some(Value::Null) => return "\\N".to_string(),
+        Some(value) if !sql_type.accepts(value) => return "\\N".to_string(),
+        Some(value) if sql_type == SqlType::Json => value.to_string(),
+        Some(Value::Bool(b)) => if *b { "t" } else { "f" }.to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    };
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// A PostgreSQL `COPY ... FROM STDIN` block (tab-separated text format, terminated by
+/// `\.`), ready to pipe into `psql`.
+pub fn copy_statement(table: &str, columns: &[SqlColumn], rows: &[IndexMap<String, Value>]) -> String {
+    let dialect = SqlDialect::PostgreSql;
+    let names: Vec<String> = columns.iter().map(|c| dialect.quote_identifier(&c.name)).collect();
+    let mut lines = vec![format!("COPY {} ({}) FROM STDIN;", dialect.quote_identifier(table), names.join(", "))];
+    for row in rows {
+        let fields: Vec<String> = columns.iter().map(|c| copy_field(row.get(&c.name), c.sql_type)).collect();
+        lines.push(fields.join("\t"));
+    }
+    lines.push("\\.".to_string());
+    lines.join("\n")
+}
+
+/// How rows are rendered after the `CREATE TABLE` statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlRowMode {
+    /// Batched multi-row `INSERT` statements of at most this many rows.
+    Insert(usize),
+    /// PostgreSQL `COPY ... FROM STDIN` (the dialect is forced to PostgreSQL).
+    Copy,
+}
+
+impl Default for SqlRowMode {
+    fn default() -> Self {
+        SqlRowMode::Insert(DEFAULT_INSERT_BATCH_SIZE)
+    }
+}
+
+fn sheet_script(table: &str, rows: &[IndexMap<String, Value>], keys: &[String], columns: &[Column], dialect: SqlDialect, mode: SqlRowMode) -> Vec<String> {
+    let sql_cols = sql_columns(rows, keys, columns);
+    let dialect = if mode == SqlRowMode::Copy { SqlDialect::PostgreSql } else { dialect };
+    let mut statements = vec![create_table_statement(table, &sql_cols, dialect)];
+    if !rows.is_empty() {
+        match mode {
+            SqlRowMode::Insert(batch_size) => statements.extend(insert_statements(table, &sql_cols, rows, dialect, batch_size)),
+            SqlRowMode::Copy => statements.push(copy_statement(table, &sql_cols, rows)),
+        }
+    }
+    statements
+}
+
+/// Renders a whole result set as a SQL script: `CREATE TABLE` plus row statements,
+/// one table per sheet (named after each sheet's snake_cased key) for multi-sheet
+/// result sets, else a single `table` (or, if empty, `result.filename`'s stem).
+pub fn to_sql_script(result: &ResultSet, table: &str, dialect: SqlDialect, mode: SqlRowMode) -> String {
+    let sheets = result.data.sheets();
+    let statements: Vec<String> = if sheets.is_empty() {
+        let table = if table.is_empty() {
+            Path::new(&result.filename).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("data".to_string())
+        } else {
+            table.to_string()
+        };
+        sheet_script(&table, &result.data.first_sheet(), &result.keys, &result.columns, dialect, mode)
+    } else {
+        sheets.iter().flat_map(|sheet| sheet_script(&sheet.key(), &sheet.rows, &sheet.keys, &[], dialect, mode)).collect()
+    };
+    statements.join("\n\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn write_csv_fixture(filename: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(filename);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_sql_columns_prefer_declared_formats_and_collapse_nested_keys() {
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"zip": 2134, "qty": 2, "price": 3, "dims": {"w": 1}, "note": null},
+            {"zip": 90210, "qty": 5, "price": 4.5, "dims": {"w": 2}, "extra": true}
+        ])).unwrap();
+        let keys: Vec<String> = ["zip", "qty", "price", "dims", "dims", "note"].iter().map(|s| s.to_string()).collect();
+        let dims = |leaf: &str| {
+            let mut col = Column::new(None);
+            col.key = Some(KeySegment::Object(Arc::from("dims"), Arc::new(KeySegment::Simple(Arc::from(leaf)))));
+            col
+        };
+        let cols = vec![Column::new_format(Format::Text, None), Column::new(None), Column::new(None), dims("w"), dims("h")];
+        let resolved = sql_columns(&rows, &keys, &cols);
+        assert_eq!(resolved, vec![
+            SqlColumn::new("zip", SqlType::Text),
+            SqlColumn::new("qty", SqlType::Integer),
+            SqlColumn::new("price", SqlType::Real),
+            SqlColumn::new("dims", SqlType::Json),
+            SqlColumn::new("note", SqlType::Text),
+            SqlColumn::new("extra", SqlType::Boolean),
+        ]);
+    }
+
+    #[test]
+    fn test_quote_identifier_doubles_embedded_quotes() {
+        assert_eq!(quote_identifier("first name"), "\"first name\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(SqlDialect::MySql.quote_identifier("a`b"), "`a``b`");
+    }
+
+    #[test]
+    fn test_literals_escape_per_dialect() {
+        let value = json!("O'Brien \\ co");
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&value), SqlType::Text), "'O''Brien \\ co'");
+        assert_eq!(SqlDialect::MySql.literal(Some(&value), SqlType::Text), "'O''Brien \\\\ co'");
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&json!(true)), SqlType::Boolean), "TRUE");
+        assert_eq!(SqlDialect::Sqlite.literal(Some(&json!(true)), SqlType::Boolean), "1");
+        assert_eq!(SqlDialect::MySql.literal(Some(&json!("2023-06-15T10:17:00Z")), SqlType::DateTime), "'2023-06-15T10:17:00'");
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&json!(2134)), SqlType::Text), "'2134'");
+        assert_eq!(SqlDialect::PostgreSql.literal(None, SqlType::Integer), "NULL");
+    }
+
+    #[test]
+    fn test_values_a_column_type_rejects_become_null() {
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&json!("n/a")), SqlType::Integer), "NULL");
+        assert_eq!(SqlDialect::MySql.literal(Some(&json!("n/a")), SqlType::Real), "NULL");
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&json!(1)), SqlType::Boolean), "NULL");
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&json!(45000)), SqlType::Date), "NULL");
+        assert_eq!(SqlDialect::PostgreSql.literal(Some(&json!("red")), SqlType::Json), "'\"red\"'");
+        let cols = vec![SqlColumn::new("qty", SqlType::Integer)];
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([{"qty": 3}, {"qty": "n/a"}])).unwrap();
+        assert_eq!(copy_statement("t", &cols, &rows), "COPY \"t\" (\"qty\") FROM STDIN;\n3\n\\N\n\\.");
+    }
+
+    #[test]
+    fn test_create_table_and_batched_inserts() {
+        let cols = vec![SqlColumn::new("id", SqlType::Integer), SqlColumn::new("name", SqlType::Text)];
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"id": 1, "name": "a"}, {"id": 2, "name": null}, {"id": 3, "name": "c"}
+        ])).unwrap();
+        assert_eq!(
+            create_table_statement("items", &cols, SqlDialect::MySql),
+            "CREATE TABLE IF NOT EXISTS `items` (\n  `id` BIGINT,\n  `name` TEXT\n);"
+        );
+        let inserts = insert_statements("items", &cols, &rows, SqlDialect::PostgreSql, 2);
+        assert_eq!(inserts.len(), 2);
+        assert_eq!(inserts[0], "INSERT INTO \"items\" (\"id\", \"name\") VALUES\n  (1, 'a'),\n  (2, NULL);");
+        assert_eq!(inserts[1], "INSERT INTO \"items\" (\"id\", \"name\") VALUES\n  (3, 'c');");
+    }
+
+    #[test]
+    fn test_copy_statement_escapes_text_format() {
+        let cols = vec![SqlColumn::new("note", SqlType::Text), SqlColumn::new("ok", SqlType::Boolean)];
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"note": "tab\there\nnew \\ line", "ok": true}, {"note": null, "ok": false}
+        ])).unwrap();
+        assert_eq!(
+            copy_statement("t", &cols, &rows),
+            "COPY \"t\" (\"note\", \"ok\") FROM STDIN;\ntab\\there\\nnew \\\\ line\tt\n\\N\tf\n\\."
+        );
+    }
+
+    #[test]
+    fn test_to_sql_script_from_result_set() {
+        let path = write_csv_fixture("sql_script.csv", "sku,qty\nA1,3\nA2,4\n");
+        let result = crate::process_spreadsheet_direct(&crate::OptionSet::new(&path)).unwrap();
+        let script = to_sql_script(&result, "stock", SqlDialect::Sqlite, SqlRowMode::default());
+        assert!(script.starts_with("CREATE TABLE IF NOT EXISTS \"stock\" (\n  \"sku\" TEXT,\n  \"qty\" INTEGER\n);"));
+        assert!(script.contains("('A1', 3),\n  ('A2', 4);"));
+        assert!(SqlDialect::from_str("postgres").is_ok_and(|d| d == SqlDialect::PostgreSql));
+    }
+}
@@ -0,0 +1,315 @@
+//! SQLite import target, behind the optional `sqlite` feature -- for offline analysis and
+//! test fixtures where a spreadsheet should simply become a table, with no server.
+//!
+//! Tables are created from the resolved header keys and column formats (see
+//! `sql::sql_columns`) and rows are bulk-inserted inside transactions of at most
+//! `DEFAULT_TRANSACTION_SIZE` rows. Dates and times are stored as ISO 8601 text, which
+//! SQLite's own date functions read directly, and nested output as JSON text.
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+use rusqlite::types::{ToSqlOutput, Value as SqlValue};
+use rusqlite::{params_from_iter, Connection};
+use serde_json::Value;
+
+use crate::error::GenericError;
+use crate::reader::SaveRowFn;
+use crate::headers::column_output_keys;
+use crate::sql::{create_table_statement, quote_identifier, sql_columns, SqlColumn, SqlDialect, SqlType};
+use crate::{Column, ResultSet, SheetDataSet};
+
+/// Rows inserted per transaction.
+pub const DEFAULT_TRANSACTION_SIZE: usize = 1000;
+
+fn insert_sql(table: &str, columns: &[SqlColumn]) -> String {
+    let names: Vec<String> = columns.iter().map(|c| quote_identifier(&c.name)).collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    format!("INSERT INTO {} ({}) VALUES ({})", quote_identifier(table), names.join(", "), placeholders)
+}
+
+/// Binds a converted cell for a column of `sql_type`. A value the column doesn't accept
+/// (see `SqlType::accepts`) is stored as NULL, as in `sql::insert_statements`.
+fn to_sql_value(value: Option<&Value>, sql_type: SqlType) -> ToSqlOutput<'static> {
+    let sql_value = match value {
+        None | Some(Value::Null) => SqlValue::Null,
+        Some(value) if !sql_type.accepts(value) => SqlValue::Null,
+        Some(value) if sql_type == SqlType::Json => SqlValue::Text(value.to_string()),
+        Some(Value::Bool(b)) => SqlValue::Integer(*b as i64),
+        Some(Value::Number(n)) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Some(Value::String(s)) => SqlValue::Text(s.clone()),
+        Some(other) => SqlValue::Text(other.to_string()),
+    };
+    ToSqlOutput::Owned(sql_value)
+}
+
+/// Inserts `rows` into an existing table, one transaction per `DEFAULT_TRANSACTION_SIZE`
+/// rows, returning the number of rows inserted.
+pub fn insert_rows(conn: &mut Connection, table: &str, columns: &[SqlColumn], rows: &[IndexMap<String, Value>]) -> Result<usize, GenericError> {
+    let sql = insert_sql(table, columns);
+    for chunk in rows.chunks(DEFAULT_TRANSACTION_SIZE) {
+        let tx = conn.transaction().map_err(|_| GenericError("sqlite_error"))?;
+        {
+            let mut stmt = tx.prepare_cached(&sql).map_err(|_| GenericError("sqlite_error"))?;
+            for row in chunk {
+                let values = columns.iter().map(|c| to_sql_value(row.get(&c.name), c.sql_type));
+                stmt.execute(params_from_iter(values)).map_err(|_| GenericError("sqlite_insert_error"))?;
+            }
+        }
+        tx.commit().map_err(|_| GenericError("sqlite_error"))?;
+    }
+    Ok(rows.len())
+}
+
+/// Creates `table` unless it exists. A table needs at least one column, so with neither
+/// keys nor rows to take them from this fails rather than issuing `CREATE TABLE t ()`.
+fn create_table(conn: &Connection, table: &str, columns: &[SqlColumn]) -> Result<(), GenericError> {
+    if columns.is_empty() {
+        return Err(GenericError("sqlite_table_without_columns"));
+    }
+    conn.execute(&create_table_statement(table, columns, SqlDialect::Sqlite), []).map_err(|_| GenericError("sqlite_create_table_error"))?;
+    Ok(())
+}
+
+fn write_table(conn: &mut Connection, table: &str, rows: &[IndexMap<String, Value>], keys: &[String], columns: &[Column]) -> Result<usize, GenericError> {
+    let sql_cols = sql_columns(rows, keys, columns);
+    create_table(conn, table, &sql_cols)?;
+    insert_rows(conn, table, &sql_cols, rows)
+}
+
+/// Creates `table` from a result set's (first sheet's) keys and column formats and
+/// inserts its rows, returning the number of rows inserted.
+pub fn write_sqlite(result: &ResultSet, conn: &mut Connection, table: &str) -> Result<usize, GenericError> {
+    write_table(conn, table, &result.data.first_sheet(), &result.keys, &result.columns)
+}
+
+/// Writes every sheet of a multi-sheet result set to its own table, named after the
+/// sheet's snake_cased key (`SheetDataSet::key`), returning the table names. A
+/// single-sheet result set writes one table named after `result.filename`'s stem.
+pub fn write_sqlite_tables(result: &ResultSet, conn: &mut Connection) -> Result<Vec<String>, GenericError> {
+    let sheets: Vec<SheetDataSet> = result.data.sheets();
+    if sheets.is_empty() {
+        let table = Path::new(&result.filename).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("data".to_string());
+        write_sqlite(result, conn, &table)?;
+        return Ok(vec![table]);
+    }
+    let mut tables = Vec::with_capacity(sheets.len());
+    for sheet in &sheets {
+        let table = sheet.key();
+        write_table(conn, &table, &sheet.rows, &sheet.keys, &[])?;
+        tables.push(table);
+    }
+    Ok(tables)
+}
+
+/// Opens (or creates) the database file at `path` and writes the result set to it --
+/// one table per sheet for multi-sheet result sets, else a single `table`.
+pub fn write_sqlite_file(result: &ResultSet, path: &Path, table: &str) -> Result<Vec<String>, GenericError> {
+    let mut conn = Connection::open(path).map_err(|_| GenericError("sqlite_open_error"))?;
+    if result.multimode() {
+        write_sqlite_tables(result, &mut conn)
+    } else {
+        write_sqlite(result, &mut conn, table)?;
+        Ok(vec![table.to_string()])
+    }
+}
+
+/// Streams rows into a SQLite table for the asynchronous (`ReadMode::Async`) path. The
+/// table is created from the first `batch_size` rows plus `columns` (matched by output
+/// key, since a save callback only ever sees converted rows), then each batch is
+/// inserted in its own transaction. Keys first seen in a later batch are added with
+/// `ALTER TABLE ... ADD COLUMN`, typed from that batch's values.
+pub struct SqliteRowWriter {
+    conn: Connection,
+    table: String,
+    columns: Vec<Column>,
+    batch_size: usize,
+    buffer: Vec<IndexMap<String, Value>>,
+    sql_columns: Option<Vec<SqlColumn>>,
+    num_rows: usize,
+}
+
+impl SqliteRowWriter {
+    pub fn new(conn: Connection, table: &str, columns: &[Column], batch_size: usize) -> Self {
+        SqliteRowWriter {
+            conn,
+            table: table.to_string(),
+            columns: columns.to_vec(),
+            batch_size: batch_size.max(1),
+            buffer: Vec::new(),
+            sql_columns: None,
+            num_rows: 0,
+        }
+    }
+
+    pub fn push_row(&mut self, row: IndexMap<String, Value>) -> Result<(), GenericError> {
+        self.buffer.push(row);
+        if self.buffer.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), GenericError> {
+        let sql_cols = match self.sql_columns.take() {
+            None => {
+                let row_keys: Vec<String> = self.buffer.first().map(|row| row.keys().cloned().collect()).unwrap_or_default();
+                let keys = column_output_keys(&self.columns, &row_keys);
+                let sql_cols = sql_columns(&self.buffer, &keys, &self.columns);
+                create_table(&self.conn, &self.table, &sql_cols)?;
+                sql_cols
+            }
+            Some(mut sql_cols) => {
+                for column in sql_columns(&self.buffer, &[], &[]) {
+                    if !sql_cols.iter().any(|c| c.name == column.name) {
+                        let sql = format!(
+                            "ALTER TABLE {} ADD COLUMN {} {}",
+                            quote_identifier(&self.table),
+                            quote_identifier(&column.name),
+                            SqlDialect::Sqlite.type_name(column.sql_type)
+                        );
+                        self.conn.execute(&sql, []).map_err(|_| GenericError("sqlite_alter_table_error"))?;
+                        sql_cols.push(column);
+                    }
+                }
+                sql_cols
+            }
+        };
+        self.num_rows += insert_rows(&mut self.conn, &self.table, &sql_cols, &self.buffer)?;
+        self.sql_columns = Some(sql_cols);
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Inserts any buffered rows (creating the table if no batch has been flushed yet)
+    /// and returns the total number of rows inserted. Fails with
+    /// `sqlite_table_without_columns` if no rows were pushed and no `columns` given, as
+    /// there is nothing to create the table from.
+    pub fn finish(&mut self) -> Result<usize, GenericError> {
+        self.flush()?;
+        Ok(self.num_rows)
+    }
+
+    /// Releases the connection, e.g. to query the imported table.
+    pub fn into_connection(self) -> Connection {
+        self.conn
+    }
+}
+
+/// Wraps a shared `SqliteRowWriter` as a `SaveRowFn` for `process_spreadsheet_async`.
+/// Keep a clone of the `Arc` to call `finish()` on once processing returns.
+pub fn sqlite_save_fn(writer: Arc<Mutex<SqliteRowWriter>>) -> SaveRowFn {
+    Box::new(move |row| {
+        let mut guard = writer.lock().map_err(|_| GenericError("sqlite_error"))?;
+        guard.push_row(row)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_spreadsheet_direct, DateTimeMode, Format, OptionSet};
+    use serde_json::json;
+
+    fn write_csv_fixture(filename: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(filename);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn declared_types(conn: &Connection, table: &str) -> Vec<(String, String)> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table))).unwrap();
+        stmt.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_write_sqlite_creates_typed_table_and_inserts_rows() {
+        let path = write_csv_fixture("sqlite_typed.csv", "sku,qty,price,active,added\nA1,3,2.5,yes,2023-06-15\nA2,4,3,no,2023-06-16\n");
+        let opts = OptionSet::new(&path).override_columns(&[
+            json!({"source_key": "active", "format": "truthy"}),
+            json!({"source_key": "added", "format": "date"}),
+        ]);
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(write_sqlite(&result, &mut conn, "stock").unwrap(), 2);
+
+        let types = declared_types(&conn, "stock");
+        assert_eq!(types, vec![
+            ("sku".to_string(), "TEXT".to_string()),
+            ("qty".to_string(), "INTEGER".to_string()),
+            ("price".to_string(), "REAL".to_string()),
+            ("active".to_string(), "BOOLEAN".to_string()),
+            ("added".to_string(), "DATE".to_string()),
+        ]);
+        let (qty, active, added): (i64, i64, String) = conn
+            .query_row("SELECT qty, active, added FROM stock WHERE sku = 'A1'", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap();
+        assert_eq!((qty, active, added.as_str()), (3, 1, "2023-06-15"));
+    }
+
+    #[test]
+    fn test_write_sqlite_tables_writes_one_table_per_sheet() {
+        let opts = OptionSet::new("data/sample-data-2.ods").read_mode_preview();
+        let result = process_spreadsheet_direct(&opts).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tables = write_sqlite_tables(&result, &mut conn).unwrap();
+        let sheets = result.data.sheets();
+        assert_eq!(tables.len(), sheets.len());
+        for (table, sheet) in tables.iter().zip(sheets.iter()) {
+            let count: i64 = conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", quote_identifier(table)), [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(count as usize, sheet.rows.len());
+        }
+    }
+
+    #[test]
+    fn test_row_writer_adds_late_keys_and_rejects_an_empty_table() {
+        let rows: Vec<IndexMap<String, Value>> = serde_json::from_value(json!([
+            {"sku": "A1", "qty": 3}, {"sku": "A2", "qty": 4}, {"sku": "A3", "qty": 5, "note": "late"}
+        ])).unwrap();
+        let mut writer = SqliteRowWriter::new(Connection::open_in_memory().unwrap(), "stock", &[], 2);
+        for row in rows.clone() {
+            writer.push_row(row).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 3);
+        let conn = writer.into_connection();
+        assert_eq!(declared_types(&conn, "stock").last(), Some(&("note".to_string(), "TEXT".to_string())));
+        let note: String = conn.query_row("SELECT note FROM stock WHERE sku = 'A3'", [], |r| r.get(0)).unwrap();
+        assert_eq!(note, "late");
+
+        // A column matched by source key keeps its format, and a value that doesn't fit
+        // it is stored as NULL
+        let cols = vec![Column::from_source_key_with_format("qty", None, Format::Float, None, DateTimeMode::Full, false)];
+        let mut typed = SqliteRowWriter::new(Connection::open_in_memory().unwrap(), "stock", &cols, 8);
+        typed.push_row(rows[0].clone()).unwrap();
+        typed.push_row(serde_json::from_value(json!({"sku": "A4", "qty": "n/a"})).unwrap()).unwrap();
+        assert_eq!(typed.finish().unwrap(), 2);
+        let conn = typed.into_connection();
+        assert_eq!(declared_types(&conn, "stock"), vec![("qty".to_string(), "REAL".to_string()), ("sku".to_string(), "TEXT".to_string())]);
+        let qty: Option<f64> = conn.query_row("SELECT qty FROM stock WHERE sku = 'A4'", [], |r| r.get(0)).unwrap();
+        assert_eq!(qty, None);
+
+        let mut empty = SqliteRowWriter::new(Connection::open_in_memory().unwrap(), "none", &[], 2);
+        assert_eq!(empty.finish().unwrap_err().to_string(), "sqlite_table_without_columns");
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_save_fn_streams_rows_in_async_mode() {
+        let writer = Arc::new(Mutex::new(SqliteRowWriter::new(Connection::open_in_memory().unwrap(), "people", &[], 64)));
+        let opts = OptionSet::new("data/sample-data-1.csv").read_mode_async();
+        crate::process_spreadsheet_async(&opts, sqlite_save_fn(writer.clone()), None).await.unwrap();
+        assert_eq!(writer.lock().unwrap().finish().unwrap(), 400);
+
+        let writer = Arc::try_unwrap(writer).ok().unwrap().into_inner().unwrap();
+        let conn = writer.into_connection();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM people WHERE age > 0", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 400);
+    }
+}
@@ -0,0 +1,200 @@
+//! Excel tables (ListObjects) and defined names as data sources. Both resolve to a sheet
+//! and a `CellRange`, which is then read like any other worksheet. A table supplies its
+//! own header row and extent, so no header detection runs; a defined name gives the
+//! extent, with its first row as the header unless `header_row` says otherwise.
+use std::fs::File;
+use std::io::BufReader;
+
+use calamine::{Data, Range, Reader, Sheets};
+use heck::ToSnakeCase;
+use serde_json::{json, Value};
+
+use crate::cell_range::CellRange;
+use crate::error::GenericError;
+use crate::OptionSet;
+
+/// A named source within a workbook, in place of a whole sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeSource {
+    /// An Excel table by name (xlsx and xlsm only)
+    Table(String),
+    /// A workbook-level defined name referring to a single range
+    DefinedName(String),
+}
+
+impl RangeSource {
+    pub fn name(&self) -> &str {
+        match self {
+            RangeSource::Table(name) | RangeSource::DefinedName(name) => name,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            RangeSource::Table(name) => json!({ "table": name }),
+            RangeSource::DefinedName(name) => json!({ "defined_name": name }),
+        }
+    }
+}
+
+/// An Excel table's name, location and columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub name: String,
+    pub sheet_name: String,
+    pub columns: Vec<String>,
+    /// The header row, if shown, and the data rows, without any totals row
+    pub range: CellRange,
+    pub header_rows: u32,
+}
+
+impl TableInfo {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "sheet": self.sheet_name,
+            "range": self.range.to_string(),
+            "columns": self.columns,
+            "header_row": self.header_rows > 0,
+        })
+    }
+}
+
+/// Lists the tables in an xlsx workbook, in sheet order, via calamine's table metadata.
+/// Other formats have none.
+///
+/// calamine gives each table's data rows but not its header row count, so a table has a
+/// header when the row above its data holds its column names. A totals row is only left
+/// out of tables that also show their header.
+pub fn read_table_info(workbook: &mut Sheets<BufReader<File>>) -> Vec<TableInfo> {
+    let Sheets::Xlsx(xlsx) = workbook else {
+        return vec![];
+    };
+    if xlsx.load_tables().is_err() {
+        return vec![];
+    }
+    let mut tables = vec![];
+    for sheet_name in xlsx.sheet_names() {
+        let table_names: Vec<String> = xlsx.table_names_in_sheet(&sheet_name).into_iter().cloned().collect();
+        if table_names.is_empty() {
+            continue;
+        }
+        let Ok(sheet_range) = xlsx.worksheet_range(&sheet_name) else {
+            continue;
+        };
+        for table_name in table_names {
+            let Ok(table) = xlsx.table_by_name(&table_name) else {
+                continue;
+            };
+            let (Some(start), Some(end)) = (table.data().start(), table.data().end()) else {
+                continue;
+            };
+            let header_rows = u32::from(shows_header(&sheet_range, start, table.columns()));
+            tables.push(TableInfo {
+                name: table.name().to_string(),
+                sheet_name: sheet_name.clone(),
+                columns: table.columns().to_vec(),
+                range: CellRange { start: (start.0 - header_rows, start.1), end_row: Some(end.0), end_col: end.1 },
+                header_rows,
+            });
+        }
+    }
+    tables
+}
+
+/// Whether the row above a table's data, starting at `data_start`, holds its column names.
+fn shows_header(sheet_range: &Range<Data>, data_start: (u32, u32), columns: &[String]) -> bool {
+    let Some(row) = data_start.0.checked_sub(1) else {
+        return false;
+    };
+    !columns.is_empty()
+        && columns.iter().enumerate().all(|(offset, column)| {
+            let cell = sheet_range.get_value((row, data_start.1 + offset as u32));
+            cell.is_some_and(|cell| cell.to_string().trim() == column.trim())
+        })
+}
+
+/// Resolves `OptionSet.range_source` to the sheet to read and options restricted to the
+/// table or named range.
+pub fn resolve_range_source(
+    workbook: &mut Sheets<BufReader<File>>,
+    opts: &OptionSet,
+) -> Result<Option<(String, OptionSet)>, GenericError> {
+    let Some(source) = &opts.range_source else {
+        return Ok(None);
+    };
+    let mut resolved = opts.clone();
+    match source {
+        RangeSource::Table(name) => {
+            let tables = read_table_info(workbook);
+            let table = tables
+                .iter()
+                .find(|t| t.name.to_snake_case() == name.to_snake_case())
+                .ok_or(GenericError("table_not_found"))?;
+            resolved.cell_range = Some(table.range.to_string());
+            resolved.detect_header = false;
+            if table.header_rows > 0 {
+                resolved.omit_header = false;
+                resolved.header_row = Some(0);
+                resolved.data_row_index = Some(table.header_rows as usize);
+                resolved.header_row_span = table.header_rows as usize;
+            } else {
+                // A table without a visible header row still names its columns
+                resolved.header_row = None;
+                resolved.data_row_index = None;
+                resolved = resolved.omit_header();
+                if resolved.rows.columns.is_empty() {
+                    let keys: Vec<&str> = table.columns.iter().map(|c| c.as_str()).collect();
+                    resolved = resolved.override_headers(&keys);
+                }
+            }
+            Ok(Some((table.sheet_name.clone(), resolved)))
+        }
+        RangeSource::DefinedName(name) => {
+            let refers_to = workbook
+                .defined_names()
+                .iter()
+                .find(|(n, _)| n.to_snake_case() == name.to_snake_case())
+                .map(|(_, r)| r.clone())
+                .ok_or(GenericError("defined_name_not_found"))?;
+            let (sheet_name, cell_range) = parse_sheet_range(&refers_to).ok_or(GenericError("unsupported_defined_name"))?;
+            if !workbook.sheet_names().contains(&sheet_name) {
+                return Err(GenericError("unsupported_defined_name"));
+            }
+            resolved.cell_range = Some(cell_range.to_string());
+            if resolved.header_row.is_none() && !resolved.omit_header {
+                resolved.header_row = Some(0);
+            }
+            Ok(Some((sheet_name, resolved)))
+        }
+    }
+}
+
+/// Splits a reference like `'Q1 Sales'!$B$2:$F$40` into its sheet name and range. A single
+/// cell counts as a one-cell range.
+pub fn parse_sheet_range(refers_to: &str) -> Option<(String, CellRange)> {
+    let refers_to = refers_to.trim().trim_start_matches('=');
+    let (sheet, cells) = refers_to.rsplit_once('!')?;
+    let sheet = match sheet.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => sheet.to_string(),
+    };
+    let cell_range = match cells.contains(':') {
+        true => CellRange::parse(cells),
+        false => CellRange::parse(&format!("{0}:{0}", cells)),
+    };
+    Some((sheet, cell_range.ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sheet_ranges() {
+        let (sheet, range) = parse_sheet_range("='Q1 ''final'''!$B$2:$F$40").unwrap();
+        assert_eq!((sheet.as_str(), range.to_string().as_str()), ("Q1 'final'", "B2:F40"));
+        assert_eq!(parse_sheet_range("Data!$C$3").unwrap().1.to_string(), "C3:C3");
+        assert!(parse_sheet_range("SUM(Data!A1:A3)+1").is_none());
+    }
+}
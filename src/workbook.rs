@@ -0,0 +1,275 @@
+//! Workbook-level metadata: per-sheet dimensions, visibility and type, defined names and
+//! document properties. Xlsx and xlsb dimensions come from the range each worksheet
+//! declares, and document properties straight from the zip container, so no cells are
+//! converted for those formats.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use calamine::{Reader, Sheet, SheetType, SheetVisible, Sheets};
+use quick_xml::escape::resolve_xml_entity;
+use quick_xml::events::Event;
+use serde_json::{json, Value};
+
+use crate::headers::to_a1_col_key;
+
+/// Size and state of one sheet in a workbook.
+#[derive(Debug, Clone)]
+pub struct SheetInfo {
+    pub name: String,
+    /// 0-based position in the workbook
+    pub index: usize,
+    /// 0-based (row, column) of the top-left cell of the used range, `None` when the
+    /// sheet is empty or holds no cells, like a chart sheet.
+    pub start: Option<(u32, u32)>,
+    pub rows: usize,
+    pub cols: usize,
+    pub visible: SheetVisible,
+    pub typ: SheetType,
+}
+
+impl SheetInfo {
+    /// The used range in A1 notation, e.g. `B2:F40`.
+    pub fn range_ref(&self) -> Option<String> {
+        let (row, col) = self.start?;
+        let end_row = row as usize + self.rows.max(1);
+        let end_col = col as usize + self.cols.max(1) - 1;
+        Some(format!(
+            "{}{}:{}{}",
+            to_a1_col_key(col as usize).to_uppercase(),
+            row + 1,
+            to_a1_col_key(end_col).to_uppercase(),
+            end_row
+        ))
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.visible != SheetVisible::Visible
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "index": self.index,
+            "rows": self.rows,
+            "cols": self.cols,
+            "range": self.range_ref(),
+            "visible": visibility_key(self.visible),
+            "type": sheet_type_key(self.typ),
+        })
+    }
+}
+
+/// A workbook-level defined name and the formula or range it refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinedName {
+    pub name: String,
+    pub refers_to: String,
+}
+
+impl DefinedName {
+    pub fn to_json(&self) -> Value {
+        json!({ "name": self.name, "refers_to": self.refers_to })
+    }
+}
+
+/// Core document properties. Dates are kept as written, normally ISO 8601 UTC.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentProperties {
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub author: Option<String>,
+    pub last_modified_by: Option<String>,
+    pub created: Option<String>,
+    pub modified: Option<String>,
+}
+
+impl DocumentProperties {
+    pub fn is_empty(&self) -> bool {
+        *self == DocumentProperties::default()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "title": self.title,
+            "subject": self.subject,
+            "author": self.author,
+            "last_modified_by": self.last_modified_by,
+            "created": self.created,
+            "modified": self.modified,
+        })
+    }
+
+    /// Reads the properties part of an OOXML (`docProps/core.xml`) or ODS (`meta.xml`)
+    /// container. Legacy xls files and unreadable containers give `None`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+        let mut xml = String::new();
+        for part in ["docProps/core.xml", "meta.xml"] {
+            if let Ok(mut file) = archive.by_name(part) {
+                file.read_to_string(&mut xml).ok()?;
+                break;
+            }
+        }
+        if xml.is_empty() {
+            return None;
+        }
+        let texts = xml_element_texts(&xml);
+        let text = |tag: &str| texts.get(tag).cloned();
+        let props = DocumentProperties {
+            title: text("dc:title"),
+            subject: text("dc:subject"),
+            author: text("dc:creator").or_else(|| text("meta:initial-creator")),
+            last_modified_by: text("cp:lastModifiedBy"),
+            created: text("dcterms:created").or_else(|| text("meta:creation-date")),
+            modified: text("dcterms:modified").or_else(|| text("dc:date")),
+        };
+        Some(props)
+    }
+}
+
+/// Collects dimensions, visibility and type for every sheet. Xlsx and xlsb sheets report
+/// the range they declare, which is read without parsing any cells. Other formats, or
+/// sheets that declare nothing, fall back to loading the range.
+pub fn read_sheet_info(workbook: &mut Sheets<BufReader<File>>) -> Vec<SheetInfo> {
+    let metadata: Vec<Sheet> = workbook.sheets_metadata().to_vec();
+    metadata
+        .into_iter()
+        .enumerate()
+        .map(|(index, meta)| {
+            let bounds = if meta.typ == SheetType::WorkSheet {
+                declared_bounds(workbook, &meta.name).or_else(|| range_bounds(workbook, &meta.name))
+            } else {
+                None
+            };
+            let (start, rows, cols) = match bounds {
+                Some((start, end)) => (
+                    Some(start),
+                    (end.0 - start.0 + 1) as usize,
+                    (end.1 - start.1 + 1) as usize,
+                ),
+                None => (None, 0, 0),
+            };
+            SheetInfo { name: meta.name, index, start, rows, cols, visible: meta.visible, typ: meta.typ }
+        })
+        .collect()
+}
+
+pub fn read_defined_names(workbook: &Sheets<BufReader<File>>) -> Vec<DefinedName> {
+    workbook
+        .defined_names()
+        .iter()
+        .map(|(name, refers_to)| DefinedName { name: name.clone(), refers_to: refers_to.clone() })
+        .collect()
+}
+
+type Bounds = ((u32, u32), (u32, u32));
+
+fn declared_bounds(workbook: &mut Sheets<BufReader<File>>, name: &str) -> Option<Bounds> {
+    let dims = match workbook {
+        Sheets::Xlsx(xlsx) => xlsx.worksheet_cells_reader(name).ok()?.dimensions(),
+        Sheets::Xlsb(xlsb) => xlsb.worksheet_cells_reader(name).ok()?.dimensions(),
+        _ => return None,
+    };
+    // A bare `A1` is also written for sheets without any cells
+    if dims.start == dims.end && dims.start == (0, 0) {
+        return range_bounds(workbook, name);
+    }
+    Some((dims.start, dims.end))
+}
+
+fn range_bounds(workbook: &mut Sheets<BufReader<File>>, name: &str) -> Option<Bounds> {
+    let range = workbook.worksheet_range(name).ok()?;
+    Some((range.start()?, range.end()?))
+}
+
+fn visibility_key(visible: SheetVisible) -> &'static str {
+    match visible {
+        SheetVisible::Visible => "visible",
+        SheetVisible::Hidden => "hidden",
+        SheetVisible::VeryHidden => "very_hidden",
+    }
+}
+
+fn sheet_type_key(typ: SheetType) -> &'static str {
+    match typ {
+        SheetType::WorkSheet => "worksheet",
+        SheetType::DialogSheet => "dialog_sheet",
+        SheetType::MacroSheet => "macro_sheet",
+        SheetType::ChartSheet => "chart_sheet",
+        SheetType::Vba => "vba",
+    }
+}
+
+/// The trimmed, unescaped text of each element with text of its own, by qualified tag
+/// name. Only an element's first occurrence counts.
+fn xml_element_texts(xml: &str) -> HashMap<String, String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut texts = HashMap::new();
+    let mut current: Option<(String, String)> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => current = Some((String::from_utf8_lossy(e.name().as_ref()).to_string(), String::new())),
+            Ok(Event::Text(e)) => {
+                if let (Some((_, text)), Ok(part)) = (current.as_mut(), e.decode()) {
+                    text.push_str(&part);
+                }
+            }
+            Ok(Event::CData(e)) => {
+                if let (Some((_, text)), Ok(part)) = (current.as_mut(), e.decode()) {
+                    text.push_str(&part);
+                }
+            }
+            // `&amp;` and the like, and numeric references such as `&#233;`
+            Ok(Event::GeneralRef(e)) => {
+                if let Some((_, text)) = current.as_mut() {
+                    match e.resolve_char_ref() {
+                        Ok(Some(c)) => text.push(c),
+                        _ => {
+                            if let Some(entity) = e.decode().ok().and_then(|name| resolve_xml_entity(&name)) {
+                                text.push_str(entity);
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(Event::End(_)) => {
+                if let Some((tag, text)) = current.take() {
+                    let text = text.trim();
+                    if !text.is_empty() {
+                        texts.entry(tag).or_insert_with(|| text.to_string());
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    texts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sheet_range_ref_and_xml_element_texts() {
+        let info = SheetInfo {
+            name: "Data".to_string(),
+            index: 0,
+            start: Some((1, 1)),
+            rows: 39,
+            cols: 5,
+            visible: SheetVisible::Visible,
+            typ: SheetType::WorkSheet,
+        };
+        assert_eq!(info.range_ref().unwrap(), "B2:F40");
+        let xml = r#"<cp:coreProperties><dc:title>Q1 &amp; Q2</dc:title><dcterms:created xsi:type="dcterms:W3CDTF">2026-01-05T09:00:00Z</dcterms:created><dc:subject/><dc:creator>Ren&#233;e&#x2019;s team</dc:creator></cp:coreProperties>"#;
+        let texts = xml_element_texts(xml);
+        assert_eq!(texts["dc:title"], "Q1 & Q2");
+        assert_eq!(texts["dcterms:created"], "2026-01-05T09:00:00Z");
+        assert_eq!(texts["dc:creator"], "Ren\u{e9}e\u{2019}s team");
+        assert_eq!(texts.get("dc:subject"), None);
+    }
+}